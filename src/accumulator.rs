@@ -0,0 +1,441 @@
+//! Statistics accumulated over many measurements.
+//!
+//! This module provides the `Accumulator` struct, keeping the count, total, minimum and maximum
+//! of the durations recorded under a name. It can log a summary by itself every given number
+//! of samples, which suits streaming workloads. The statistics can be shipped elsewhere, e.g.
+//! to an aggregation service, in a compact binary form with `to_bytes()` and `from_bytes()`.
+
+use std::time::Duration;
+
+use crate::logging::Level;
+use crate::profiler::log_gated;
+use crate::snapshot::{encode_name_len, DecodeError, EncodeError};
+
+/// The `Accumulator` struct accumulates statistics over recorded durations.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::Accumulator;
+///
+/// let mut accumulator = Accumulator::new("query").with_auto_report(1000, true);
+/// for ms in [10, 20, 30] {
+///     accumulator.record(Duration::from_millis(ms));
+/// }
+/// assert_eq!(accumulator.count(), 3);
+/// assert_eq!(accumulator.mean(), Some(Duration::from_millis(20)));
+/// assert_eq!(accumulator.max(), Some(Duration::from_millis(30)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    name: String,
+    count: u64,
+    total: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+    report_every: u64,
+    reset_after_report: bool,
+    level: Level,
+    warmup: u64,
+    skipped: u64,
+}
+
+impl Accumulator {
+    /// Creates a new empty `Accumulator` with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Accumulator {
+            name: name.into(),
+            count: 0,
+            total: Duration::ZERO,
+            min: None,
+            max: None,
+            report_every: 0,
+            reset_after_report: false,
+            level: Level::Info,
+            warmup: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Makes `record()` log a summary every `every` samples, the summary including the sample
+    /// just recorded. If `reset_after` is `true`, the statistics are reset after each summary,
+    /// so each one covers the last window of `every` samples. An `every` of zero disables the
+    /// automatic summary.
+    pub fn with_auto_report(mut self, every: usize, reset_after: bool) -> Self {
+        self.report_every = every as u64;
+        self.reset_after_report = reset_after;
+        self
+    }
+
+    /// Sets the log level of the summaries logged by `record()`.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Makes `record()` discard the first `skip` durations, e.g. the first runs slowed down by
+    /// cold caches. They are left out of all the statistics, and of the sample count of the
+    /// automatic summaries. The warmup happens once: `reset()` does not start a new one.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::Accumulator;
+    ///
+    /// let mut accumulator = Accumulator::new("query").with_warmup(1);
+    /// accumulator.record(Duration::from_millis(500)); // cold cache, discarded
+    /// accumulator.record(Duration::from_millis(10));
+    /// assert_eq!(accumulator.count(), 1);
+    /// assert_eq!(accumulator.max(), Some(Duration::from_millis(10)));
+    /// ```
+    pub fn with_warmup(mut self, skip: usize) -> Self {
+        self.warmup = skip as u64;
+        self
+    }
+
+    /// Returns `true` while the warmup durations set with `with_warmup()` are being discarded.
+    pub fn is_warming_up(&self) -> bool {
+        self.skipped < self.warmup
+    }
+
+    /// Records a duration, logging a summary if an automatic one is due. The duration is
+    /// discarded during the warmup set with `with_warmup()`.
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.is_warming_up() {
+            self.skipped += 1;
+            return;
+        }
+        self.count += 1;
+        self.total += elapsed;
+        self.min = Some(self.min.map_or(elapsed, |min| min.min(elapsed)));
+        self.max = Some(self.max.map_or(elapsed, |max| max.max(elapsed)));
+
+        if self.report_every > 0 && self.count.is_multiple_of(self.report_every) {
+            self.log(self.level);
+            if self.reset_after_report {
+                self.reset();
+            }
+        }
+    }
+
+    /// Returns the name of the accumulator.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the sum of the recorded durations.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the smallest recorded duration, if any.
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// Returns the largest recorded duration, if any.
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// Returns the mean of the recorded durations, if any.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(Duration::from_nanos(
+            (self.total.as_nanos() / self.count as u128) as u64,
+        ))
+    }
+
+    /// Clears the statistics, keeping the name and settings.
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.total = Duration::ZERO;
+        self.min = None;
+        self.max = None;
+    }
+
+    /// Clears the minimum and maximum only, keeping the count and total. The following calls
+    /// to `min()` and `max()` return `None` until a duration is recorded, and then only track
+    /// the durations recorded since. This lets the extremes reflect the steady state once a
+    /// warmup period is over, while the mean still covers all the samples.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::Accumulator;
+    ///
+    /// let mut accumulator = Accumulator::new("query");
+    /// accumulator.record(Duration::from_millis(500)); // cold cache
+    /// accumulator.reset_extremes();
+    /// accumulator.record(Duration::from_millis(10));
+    /// assert_eq!(accumulator.count(), 2);
+    /// assert_eq!(accumulator.max(), Some(Duration::from_millis(10)));
+    /// ```
+    pub fn reset_extremes(&mut self) {
+        self.min = None;
+        self.max = None;
+    }
+
+    /// Returns a one-line summary of the statistics. The minimum and maximum are shown as `-`
+    /// when cleared by `reset_extremes()` with no duration recorded since.
+    pub fn summary(&self) -> String {
+        let Some(mean) = self.mean() else {
+            return format!("TimeLapse {} - no samples", self.name);
+        };
+        let extreme = |extreme: Option<Duration>| {
+            extreme.map_or_else(|| "-".to_string(), |extreme| format!("{:?}", extreme))
+        };
+        format!(
+            "TimeLapse {} - {} samples, total: {:?}, mean: {:?}, min: {}, max: {}",
+            self.name,
+            self.count,
+            self.total,
+            mean,
+            extreme(self.min),
+            extreme(self.max)
+        )
+    }
+
+    /// Logs the summary of the statistics at the given level. Returns `true` if the summary was
+    /// logged, which is not the case if the total is below the floor set with
+    /// `TimeLapse::set_min_loggable()` or if the region of the name is disabled (see
+    /// `registry::set_region_enabled()`).
+    pub fn log(&self, level: Level) -> bool {
+        log_gated(&self.name, self.total, level, || self.summary())
+    }
+
+    /// Encodes the name and statistics of the accumulator into a compact fixed-layout binary
+    /// form. The settings, i.e. the automatic summary, level and warmup, are not encoded.
+    ///
+    /// The wire layout is:
+    ///
+    /// | Offset | Size  | Content                                                          |
+    /// |--------|-------|------------------------------------------------------------------|
+    /// | 0      | 8     | Count, `u64` little-endian                                       |
+    /// | 8      | 8     | Total in nanoseconds, `u64` little-endian                        |
+    /// | 16     | 8     | Minimum in nanoseconds, `u64` little-endian (`u64::MAX`: none)   |
+    /// | 24     | 8     | Maximum in nanoseconds, `u64` little-endian (`u64::MAX`: none)   |
+    /// | 32     | 4     | Name length in bytes, `u32` little-endian                        |
+    /// | 36     | len   | Name as UTF-8 bytes                                              |
+    ///
+    /// Durations larger than `u64::MAX` nanoseconds (more than 584 years) saturate, at
+    /// `u64::MAX - 1` for the minimum and maximum. A name of `u32::MAX` bytes or more cannot be
+    /// encoded and gives `EncodeError::NameTooLong`.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::Accumulator;
+    ///
+    /// let mut accumulator = Accumulator::new("query");
+    /// accumulator.record(Duration::from_millis(10));
+    /// let bytes = accumulator.to_bytes().unwrap();
+    /// assert_eq!(bytes.len(), 36 + "query".len());
+    /// let decoded = Accumulator::from_bytes(&bytes).unwrap();
+    /// assert_eq!(decoded.max(), Some(Duration::from_millis(10)));
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let len = encode_name_len(self.name.len())?;
+        let nanos = |elapsed: Duration| u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let extreme = |extreme: Option<Duration>| {
+            extreme.map_or(NO_EXTREME, |extreme| nanos(extreme).min(NO_EXTREME - 1))
+        };
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.name.len());
+        bytes.extend_from_slice(&self.count.to_le_bytes());
+        bytes.extend_from_slice(&nanos(self.total).to_le_bytes());
+        bytes.extend_from_slice(&extreme(self.min).to_le_bytes());
+        bytes.extend_from_slice(&extreme(self.max).to_le_bytes());
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(self.name.as_bytes());
+        Ok(bytes)
+    }
+
+    /// Decodes an accumulator from the binary form produced by `to_bytes()`, with the default
+    /// settings of `new()`. See `to_bytes()` for the wire layout.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DecodeError::Truncated);
+        }
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let extreme_at = |offset: usize| {
+            let nanos = u64_at(offset);
+            (nanos != NO_EXTREME).then(|| Duration::from_nanos(nanos))
+        };
+        let len = u32::from_le_bytes(bytes[32..36].try_into().unwrap()) as usize;
+        let rest = &bytes[HEADER_LEN..];
+        if rest.len() < len {
+            return Err(DecodeError::Truncated);
+        }
+        if rest.len() > len {
+            return Err(DecodeError::TrailingBytes);
+        }
+        let name = std::str::from_utf8(rest).map_err(|_| DecodeError::InvalidName)?;
+        let mut accumulator = Accumulator::new(name);
+        accumulator.count = u64_at(0);
+        accumulator.total = Duration::from_nanos(u64_at(8));
+        accumulator.min = extreme_at(16);
+        accumulator.max = extreme_at(24);
+        Ok(accumulator)
+    }
+}
+
+/// Size of the fixed part of the binary encoding: count, total, minimum, maximum and name
+/// length.
+const HEADER_LEN: usize = 36;
+
+/// Sentinel used in the binary encoding for a cleared minimum or maximum.
+const NO_EXTREME: u64 = u64::MAX;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulator_stats() {
+        let mut accumulator = Accumulator::new("test");
+        assert_eq!(accumulator.mean(), None);
+        assert_eq!(accumulator.summary(), "TimeLapse test - no samples");
+
+        for ms in [30, 10, 20] {
+            accumulator.record(Duration::from_millis(ms));
+        }
+        assert_eq!(accumulator.count(), 3);
+        assert_eq!(accumulator.total(), Duration::from_millis(60));
+        assert_eq!(accumulator.min(), Some(Duration::from_millis(10)));
+        assert_eq!(accumulator.max(), Some(Duration::from_millis(30)));
+        assert_eq!(accumulator.mean(), Some(Duration::from_millis(20)));
+        assert!(accumulator
+            .summary()
+            .starts_with("TimeLapse test - 3 samples"));
+
+        accumulator.reset();
+        assert_eq!(accumulator.count(), 0);
+        assert_eq!(accumulator.min(), None);
+    }
+
+    #[test]
+    fn test_accumulator_log() {
+        let mut accumulator = Accumulator::new("test_accumulator_log");
+        accumulator.record(Duration::from_millis(10));
+        assert!(accumulator.log(Level::Info));
+        crate::registry::set_region_enabled("test_accumulator_log_disabled", false);
+        let mut disabled = Accumulator::new("test_accumulator_log_disabled");
+        disabled.record(Duration::from_millis(10));
+        assert!(!disabled.log(Level::Info));
+    }
+
+    #[test]
+    fn test_accumulator_bytes() {
+        let mut accumulator = Accumulator::new("région").with_warmup(1);
+        let empty = Accumulator::from_bytes(&accumulator.to_bytes().unwrap()).unwrap();
+        assert_eq!(empty.name(), "région");
+        assert_eq!((empty.count(), empty.min()), (0, None));
+
+        for ms in [500, 30, 10] {
+            accumulator.record(Duration::from_millis(ms));
+        }
+        let bytes = accumulator.to_bytes().unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN + "région".len());
+        let decoded = Accumulator::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.count(), 2);
+        assert_eq!(decoded.total(), Duration::from_millis(40));
+        assert_eq!(decoded.min(), Some(Duration::from_millis(10)));
+        assert_eq!(decoded.max(), Some(Duration::from_millis(30)));
+
+        accumulator.reset_extremes();
+        let cleared = Accumulator::from_bytes(&accumulator.to_bytes().unwrap()).unwrap();
+        assert_eq!((cleared.count(), cleared.max()), (2, None));
+
+        assert_eq!(
+            Accumulator::from_bytes(&bytes[..HEADER_LEN]).unwrap_err(),
+            DecodeError::Truncated
+        );
+        let mut long = bytes;
+        long.push(0);
+        assert_eq!(
+            Accumulator::from_bytes(&long).unwrap_err(),
+            DecodeError::TrailingBytes
+        );
+    }
+
+    #[test]
+    fn test_accumulator_auto_report() {
+        let mut windowed = Accumulator::new("windowed").with_auto_report(3, true);
+        for _ in 0..2 {
+            windowed.record(Duration::from_millis(1));
+        }
+        assert_eq!(windowed.count(), 2);
+        windowed.record(Duration::from_millis(1));
+        assert_eq!(windowed.count(), 0);
+        windowed.record(Duration::from_millis(1));
+        assert_eq!(windowed.count(), 1);
+
+        let mut running = Accumulator::new("running").with_auto_report(3, false);
+        for _ in 0..7 {
+            running.record(Duration::from_millis(1));
+        }
+        assert_eq!(running.count(), 7);
+    }
+
+    #[test]
+    fn test_accumulator_reset_extremes() {
+        let mut accumulator = Accumulator::new("warmup");
+        for ms in [100, 1, 50] {
+            accumulator.record(Duration::from_millis(ms));
+        }
+        accumulator.reset_extremes();
+        assert_eq!(accumulator.count(), 3);
+        assert_eq!(accumulator.total(), Duration::from_millis(151));
+        assert_eq!((accumulator.min(), accumulator.max()), (None, None));
+        assert!(accumulator.summary().ends_with("min: -, max: -"));
+
+        for ms in [20, 10, 30] {
+            accumulator.record(Duration::from_millis(ms));
+        }
+        assert_eq!(accumulator.count(), 6);
+        assert_eq!(accumulator.total(), Duration::from_millis(211));
+        assert_eq!(accumulator.min(), Some(Duration::from_millis(10)));
+        assert_eq!(accumulator.max(), Some(Duration::from_millis(30)));
+        assert!(accumulator.summary().ends_with("min: 10ms, max: 30ms"));
+    }
+
+    #[test]
+    fn test_accumulator_warmup() {
+        let mut accumulator = Accumulator::new("test_accumulator_warmup")
+            .with_warmup(2)
+            .with_auto_report(2, true);
+        assert!(accumulator.is_warming_up());
+        accumulator.record(Duration::from_millis(500));
+        accumulator.record(Duration::from_millis(1));
+        assert!(!accumulator.is_warming_up());
+        assert_eq!(accumulator.count(), 0);
+        assert_eq!(accumulator.mean(), None);
+
+        for ms in [10, 30, 20] {
+            accumulator.record(Duration::from_millis(ms));
+        }
+        // The auto report reset the statistics after the second sample past the warmup.
+        assert_eq!(accumulator.count(), 1);
+        accumulator.reset();
+        accumulator.record(Duration::from_millis(10));
+        assert_eq!(accumulator.count(), 1);
+
+        let mut accumulator = Accumulator::new("test_accumulator_warmup").with_warmup(1);
+        for ms in [900, 10, 30, 20] {
+            accumulator.record(Duration::from_millis(ms));
+        }
+        assert_eq!(accumulator.count(), 3);
+        assert_eq!(accumulator.total(), Duration::from_millis(60));
+        assert_eq!(accumulator.min(), Some(Duration::from_millis(10)));
+        assert_eq!(accumulator.max(), Some(Duration::from_millis(30)));
+        assert_eq!(accumulator.mean(), Some(Duration::from_millis(20)));
+    }
+}
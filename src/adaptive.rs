@@ -0,0 +1,173 @@
+//! Logging of the calls slower than their own history.
+//!
+//! This module provides the `AdaptiveReporter` struct. Instead of a fixed threshold, it keeps
+//! a `Histogram` of the prior measurements of each name, and logs a measurement only if it
+//! exceeds the current estimate of a high percentile (p99 by default) for that name. This
+//! surfaces genuine outliers even as the baseline of the workload shifts.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::logging::Level;
+use crate::profiler::{format_message, log_gated};
+use crate::Histogram;
+
+/// The `AdaptiveReporter` struct logs the measurements slower than a percentile of the prior
+/// measurements of the same name. It is thread-safe.
+///
+/// No measurement is logged until a name has a warmup number of prior measurements.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::AdaptiveReporter;
+///
+/// let reporter = AdaptiveReporter::new().with_warmup(10);
+/// for _ in 0..10 {
+///     assert!(!reporter.record("query", Duration::from_micros(100)));
+/// }
+/// assert!(!reporter.record("query", Duration::from_micros(90)));
+/// assert!(reporter.record("query", Duration::from_millis(5)));
+/// ```
+#[derive(Debug)]
+pub struct AdaptiveReporter {
+    histograms: Mutex<HashMap<String, Histogram>>,
+    percentile: f64,
+    warmup: u64,
+    level: Level,
+}
+
+impl AdaptiveReporter {
+    /// Creates a new `AdaptiveReporter` logging at the warn level the measurements above the
+    /// p99 of their name, after a warmup of 100 measurements.
+    pub fn new() -> Self {
+        AdaptiveReporter {
+            histograms: Mutex::new(HashMap::new()),
+            percentile: 99.0,
+            warmup: 100,
+            level: Level::Warn,
+        }
+    }
+
+    /// Sets the percentile (from 0 to 100) a measurement must exceed to be logged.
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        self.percentile = percentile;
+        self
+    }
+
+    /// Sets the number of prior measurements a name needs before any is logged.
+    pub fn with_warmup(mut self, warmup: u64) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Sets the log level of the messages.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Records a measurement under the given name, logging it if it exceeds the current
+    /// percentile estimate of that name. Returns `true` if the measurement was logged, which is
+    /// not the case either if it is below the floor set with `TimeLapse::set_min_loggable()` or
+    /// if the region is disabled (see `registry::set_region_enabled()`). The measurement is
+    /// recorded in all cases.
+    pub fn record(&self, name: &str, elapsed: Duration) -> bool {
+        let threshold = {
+            let mut histograms = self
+                .histograms
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let histogram = match histograms.get_mut(name) {
+                Some(histogram) => histogram,
+                None => histograms.entry(name.to_string()).or_default(),
+            };
+            let threshold = if histogram.count() >= self.warmup {
+                histogram.percentile(self.percentile)
+            } else {
+                None
+            };
+            histogram.record(elapsed);
+            threshold
+        };
+        match threshold {
+            Some(threshold) if elapsed > threshold => log_gated(name, elapsed, self.level, || {
+                format!(
+                    "{} (above p{} of {:?})",
+                    format_message(name, elapsed),
+                    self.percentile,
+                    threshold
+                )
+            }),
+            _ => false,
+        }
+    }
+
+    /// Returns the current percentile estimate of the given name, if anything was recorded
+    /// under it.
+    pub fn threshold(&self, name: &str) -> Option<Duration> {
+        self.histograms
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .and_then(|histogram| histogram.percentile(self.percentile))
+    }
+}
+
+/// Implements the `Default` trait for the `AdaptiveReporter` struct.
+impl std::default::Default for AdaptiveReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_adaptive_per_name() {
+        let reporter = AdaptiveReporter::new().with_warmup(5);
+        for _ in 0..5 {
+            assert!(!reporter.record("fast", Duration::from_micros(10)));
+            assert!(!reporter.record("slow", Duration::from_millis(10)));
+        }
+        assert!(reporter.record("fast", Duration::from_millis(1)));
+        assert!(!reporter.record("slow", Duration::from_millis(1)));
+        assert!(reporter.threshold("fast").unwrap() < reporter.threshold("slow").unwrap());
+        assert_eq!(reporter.threshold("unknown"), None);
+    }
+
+    #[test]
+    fn test_adaptive_disabled() {
+        let name = "test_adaptive_disabled";
+        crate::registry::set_region_enabled(name, false);
+        let reporter = AdaptiveReporter::new().with_warmup(5);
+        for _ in 0..5 {
+            assert!(!reporter.record(name, Duration::from_micros(10)));
+        }
+        assert!(!reporter.record(name, Duration::from_millis(1)));
+        assert!(reporter.threshold(name).is_some());
+    }
+
+    #[test]
+    fn test_adaptive_threads() {
+        let reporter = Arc::new(AdaptiveReporter::new());
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let reporter = Arc::clone(&reporter);
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        reporter.record("shared", Duration::from_micros(10));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert!(reporter.record("shared", Duration::from_millis(1)));
+    }
+}
@@ -0,0 +1,171 @@
+//! Heap allocation counting, enabled with the `alloc-count` feature.
+//!
+//! This module provides the `CountingAllocator` struct, a global allocator wrapping the system
+//! one that counts the allocations and allocated bytes of each thread. Each `TimeLapse` takes a
+//! snapshot of the counters of its thread when started, and `TimeLapse::allocs()` returns what
+//! was allocated since. The `profile_end_allocs!` macro logs it along with the elapsed time.
+//!
+//! The counters are only updated when `CountingAllocator` is set as the global allocator of the
+//! program, otherwise all counts stay at zero:
+//!
+//! ```rust
+//! use timelapse::allocs::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static GLOBAL: CountingAllocator = CountingAllocator;
+//! ```
+//!
+//! As the counters are per thread, the allocations made by other threads while a region runs,
+//! including the threads it spawns, are not counted. A reallocation is counted as one
+//! allocation of its new size.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use crate::logging::Level;
+use crate::profiler::{format_message, log_gated};
+use crate::TimeLapse;
+
+thread_local! {
+    static ALLOCS: Cell<AllocStats> = const { Cell::new(AllocStats { count: 0, bytes: 0 }) };
+}
+
+/// The `profile_end_allocs!` macro logs at the info level the elapsed time of the profiling
+/// instance created by `profile_start!`, along with the number of allocations and allocated
+/// bytes of the current thread since it was started.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::allocs::CountingAllocator;
+/// use timelapse::{profile_end_allocs, profile_start, TimeLapse};
+///
+/// #[global_allocator]
+/// static GLOBAL: CountingAllocator = CountingAllocator;
+///
+/// profile_start!(building);
+/// let values: Vec<u64> = (0..1000).collect();
+/// assert!(building.allocs().count() >= 1);
+/// profile_end_allocs!(building); // "TimeLapse building - Elapsed time: ... (1 allocations, 8000 bytes)"
+/// ```
+#[macro_export]
+macro_rules! profile_end_allocs {
+    ($name:ident) => {
+        $name.log_allocs(stringify!($name), $crate::Level::Info);
+    };
+}
+
+/// The `AllocStats` struct holds a number of allocations and of allocated bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    count: u64,
+    bytes: u64,
+}
+
+impl AllocStats {
+    /// Returns the number of allocations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the number of allocated bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the allocations made since an earlier reading of the counters.
+    fn since(self, start: AllocStats) -> AllocStats {
+        AllocStats {
+            count: self.count.saturating_sub(start.count),
+            bytes: self.bytes.saturating_sub(start.bytes),
+        }
+    }
+}
+
+/// Returns the counters of the current thread since it started.
+pub fn current() -> AllocStats {
+    ALLOCS.try_with(Cell::get).unwrap_or_default()
+}
+
+/// Counts an allocation of `size` bytes for the current thread.
+fn count(size: usize) {
+    // The counters may be gone while the thread is being destroyed.
+    let _ = ALLOCS.try_with(|allocs| {
+        let stats = allocs.get();
+        allocs.set(AllocStats {
+            count: stats.count + 1,
+            bytes: stats.bytes + size as u64,
+        });
+    });
+}
+
+/// The `CountingAllocator` struct is a global allocator counting the allocations of each
+/// thread, delegating the allocations themselves to the system allocator.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountingAllocator;
+
+// SAFETY: all allocation requests are forwarded unchanged to the system allocator, counting
+// them neither allocates nor unwinds.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+impl TimeLapse {
+    /// Returns the allocations made by the current thread since the timer was started.
+    /// Only counted when `CountingAllocator` is the global allocator.
+    pub fn allocs(&self) -> AllocStats {
+        current().since(self.alloc_mark())
+    }
+
+    /// Logs the elapsed time with a given name, along with the allocations made by the current
+    /// thread since the timer was started.
+    pub fn log_allocs(&self, name: &str, level: Level) {
+        let allocs = self.allocs();
+        let elapsed = self.elapsed();
+        log_gated(name, elapsed, level, || {
+            format!(
+                "{} ({} allocations, {} bytes)",
+                format_message(name, elapsed),
+                allocs.count,
+                allocs.bytes
+            )
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn test_allocs_region() {
+        let profiler = TimeLapse::new();
+        assert_eq!(profiler.allocs(), AllocStats::default());
+
+        let values = std::hint::black_box(vec![0u8; 100]);
+        let allocs = profiler.allocs();
+        assert_eq!(allocs.count(), 1);
+        assert_eq!(allocs.bytes(), 100);
+        drop(values);
+        profile_end_allocs!(profiler);
+    }
+}
@@ -0,0 +1,126 @@
+//! Classification of an elapsed time against a time budget.
+//!
+//! This module provides the `BudgetStatus` enum, returned by `TimeLapse::classify()`. Unlike
+//! `TimeLapse::deadline_reached()`, it carries the headroom left or the overage, so callers can
+//! match on the outcome and react differently, e.g. warning when at the limit and failing when
+//! over it.
+
+use std::time::Duration;
+
+use crate::format::human;
+use crate::TimeLapse;
+
+/// The fraction of the budget, in percent, within which `TimeLapse::classify()` considers an
+/// elapsed time at the limit.
+pub const AT_LIMIT_TOLERANCE_PCT: u32 = 1;
+
+/// The `BudgetStatus` enum tells how an elapsed time compares to a budget.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::budget::BudgetStatus;
+/// use timelapse::TimeLapse;
+///
+/// let profiler = TimeLapse::new();
+/// // ... work ...
+/// match profiler.classify(Duration::from_secs(1)) {
+///     BudgetStatus::Under(headroom) => println!("{:?} to spare", headroom),
+///     BudgetStatus::AtLimit => println!("warning: at the budget limit"),
+///     BudgetStatus::Over(overage) => panic!("over budget by {:?}", overage),
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetStatus {
+    /// The elapsed time is below the budget, by the given headroom.
+    Under(Duration),
+    /// The elapsed time is within the tolerance of the budget.
+    AtLimit,
+    /// The elapsed time is above the budget, by the given overage.
+    Over(Duration),
+}
+
+/// Implements the `Display` trait for the `BudgetStatus` enum, e.g. `under budget by 2.00ms`.
+impl std::fmt::Display for BudgetStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetStatus::Under(headroom) => write!(f, "under budget by {}", human(*headroom)),
+            BudgetStatus::AtLimit => write!(f, "at budget limit"),
+            BudgetStatus::Over(overage) => write!(f, "over budget by {}", human(*overage)),
+        }
+    }
+}
+
+impl TimeLapse {
+    /// Classifies the elapsed time against a budget, being at the limit when within
+    /// `AT_LIMIT_TOLERANCE_PCT` percent of it. See `classify_with_tolerance()`.
+    pub fn classify(&self, budget: Duration) -> BudgetStatus {
+        self.classify_with_tolerance(budget, budget / 100 * AT_LIMIT_TOLERANCE_PCT)
+    }
+
+    /// Classifies the elapsed time against a budget: at the limit when it differs from the
+    /// budget by at most `tolerance`, otherwise under or over it by the difference.
+    pub fn classify_with_tolerance(&self, budget: Duration, tolerance: Duration) -> BudgetStatus {
+        classify(self.elapsed(), budget, tolerance)
+    }
+}
+
+/// Classifies an elapsed time against a budget. See `TimeLapse::classify_with_tolerance()`.
+fn classify(elapsed: Duration, budget: Duration, tolerance: Duration) -> BudgetStatus {
+    if elapsed.abs_diff(budget) <= tolerance {
+        BudgetStatus::AtLimit
+    } else if elapsed < budget {
+        BudgetStatus::Under(budget - elapsed)
+    } else {
+        BudgetStatus::Over(elapsed - budget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::VirtualClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_budget_classify() {
+        let budget = Duration::from_millis(100);
+        let tolerance = budget / 100 * AT_LIMIT_TOLERANCE_PCT;
+        let classify = |ms: u64| classify(Duration::from_millis(ms), budget, tolerance);
+        assert_eq!(classify(90), BudgetStatus::Under(Duration::from_millis(10)));
+        assert_eq!(classify(99), BudgetStatus::AtLimit);
+        assert_eq!(classify(100), BudgetStatus::AtLimit);
+        assert_eq!(classify(101), BudgetStatus::AtLimit);
+        assert_eq!(classify(130), BudgetStatus::Over(Duration::from_millis(30)));
+
+        assert_eq!(
+            BudgetStatus::Under(Duration::from_millis(2)).to_string(),
+            "under budget by 2.00ms"
+        );
+        assert_eq!(BudgetStatus::AtLimit.to_string(), "at budget limit");
+        assert_eq!(
+            BudgetStatus::Over(Duration::from_secs(2)).to_string(),
+            "over budget by 2.00s"
+        );
+    }
+
+    #[test]
+    fn test_budget_classify_timer() {
+        let clock = Arc::new(VirtualClock::new());
+        let mut profiler = TimeLapse::new().with_clock(clock.clone());
+        clock.advance(Duration::from_millis(5));
+        profiler.stop();
+        assert_eq!(
+            profiler.classify(Duration::from_millis(10)),
+            BudgetStatus::Under(Duration::from_millis(5))
+        );
+        assert_eq!(
+            profiler.classify(Duration::from_millis(1)),
+            BudgetStatus::Over(Duration::from_millis(4))
+        );
+        assert_eq!(
+            profiler.classify_with_tolerance(Duration::ZERO, Duration::from_secs(1)),
+            BudgetStatus::AtLimit
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Export of nested measurements to the Chrome tracing format.
+//!
+//! This module provides the `ChromeTracing` struct, collecting begin and end events from any
+//! thread and rendering them as a JSON array in the Trace Event Format, to load in
+//! `chrome://tracing` or in Perfetto (<https://ui.perfetto.dev>).
+//!
+//! The timestamps are in microseconds relative to a process-wide origin, taken when the first
+//! `ChromeTracing` is created, so the events of several collectors share the same time line.
+//! The thread ids are the ids of the Rust threads (`std::thread::ThreadId`), and the names of
+//! the named threads are emitted as metadata events, so the viewers label their tracks.
+
+use std::cell::Cell;
+use std::fmt::Write;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::json;
+
+/// The origin of the timestamps of all the collectors.
+static ORIGIN: OnceLock<Instant> = OnceLock::new();
+
+thread_local! {
+    static THREAD_ID: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Returns the id of the current thread, as numbered by `std::thread::ThreadId`.
+fn thread_id() -> u64 {
+    THREAD_ID.with(|id| {
+        *id.get().get_or_insert_with(|| {
+            // `ThreadId::as_u64()` is unstable, but its `Debug` format is `ThreadId(N)`.
+            let id = format!("{:?}", std::thread::current().id());
+            let digits: String = id.chars().filter(char::is_ascii_digit).collect();
+            digits.parse().unwrap_or(0)
+        })
+    })
+}
+
+/// An event of the trace.
+#[derive(Clone, Debug)]
+struct Event {
+    name: String,
+    phase: char,
+    timestamp: Duration,
+    tid: u64,
+}
+
+/// The `ChromeTracing` struct collects begin and end events in the Trace Event Format.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::chrome::ChromeTracing;
+///
+/// let tracing = ChromeTracing::new();
+/// {
+///     let _request = tracing.scope("request");
+///     tracing.begin("parse");
+///     // ... parsing ...
+///     tracing.end("parse");
+/// }
+/// let json = tracing.to_json();
+/// assert!(json.contains(r#""name":"parse","ph":"B""#));
+/// // tracing.write_to_file("trace.json") and open it in chrome://tracing
+/// ```
+#[derive(Debug)]
+pub struct ChromeTracing {
+    events: Mutex<Vec<Event>>,
+    thread_names: Mutex<Vec<(u64, String)>>,
+}
+
+impl ChromeTracing {
+    /// Creates a new empty `ChromeTracing`.
+    pub fn new() -> Self {
+        ORIGIN.get_or_init(Instant::now);
+        ChromeTracing {
+            events: Mutex::new(Vec::new()),
+            thread_names: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records the beginning of a region on the current thread.
+    pub fn begin(&self, name: impl Into<String>) {
+        self.record(name.into(), 'B');
+    }
+
+    /// Records the end of a region on the current thread. The regions of a thread must end in
+    /// the reverse order they began.
+    pub fn end(&self, name: impl Into<String>) {
+        self.record(name.into(), 'E');
+    }
+
+    /// Records the beginning of a region, and returns a guard recording its end when dropped.
+    pub fn scope(&self, name: impl Into<String>) -> TraceGuard<'_> {
+        let name = name.into();
+        self.begin(name.clone());
+        TraceGuard {
+            tracing: self,
+            name,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns the number of recorded events.
+    pub fn len(&self) -> usize {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Returns `true` if no event was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the recorded events.
+    pub fn clear(&self) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Renders the recorded events as a Trace Event Format JSON array, one event per line,
+    /// preceded by the `thread_name` metadata events of the named threads.
+    pub fn to_json(&self) -> String {
+        let pid = std::process::id();
+        let mut lines = Vec::new();
+        for (tid, name) in self
+            .thread_names
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            let mut line = String::from(r#"{"name":"thread_name","ph":"M","pid":"#);
+            let _ = write!(line, r#"{},"tid":{},"args":{{"name":"#, pid, tid);
+            json::write_string(&mut line, name);
+            line.push_str("}}");
+            lines.push(line);
+        }
+        for event in self
+            .events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            let mut line = String::from(r#"{"name":"#);
+            json::write_string(&mut line, &event.name);
+            let nanos = event.timestamp.as_nanos();
+            let _ = write!(
+                line,
+                r#","ph":"{}","ts":{}.{:03},"pid":{},"tid":{}}}"#,
+                event.phase,
+                nanos / 1_000,
+                nanos % 1_000,
+                pid,
+                event.tid
+            );
+            lines.push(line);
+        }
+        if lines.is_empty() {
+            return "[]\n".to_string();
+        }
+        format!("[\n{}\n]\n", lines.join(",\n"))
+    }
+
+    /// Writes the recorded events as a Trace Event Format JSON file. See `to_json()`.
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+
+    /// Records an event on the current thread.
+    fn record(&self, name: String, phase: char) {
+        let timestamp = ORIGIN.get_or_init(Instant::now).elapsed();
+        let tid = thread_id();
+        let mut thread_names = self
+            .thread_names
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !thread_names.iter().any(|(id, _)| *id == tid) {
+            if let Some(thread_name) = std::thread::current().name() {
+                thread_names.push((tid, thread_name.to_string()));
+            }
+        }
+        drop(thread_names);
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Event {
+                name,
+                phase,
+                timestamp,
+                tid,
+            });
+    }
+}
+
+/// Implements the `Default` trait for the `ChromeTracing` struct, empty.
+impl std::default::Default for ChromeTracing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `TraceGuard` struct records the end of a region when dropped. It is created by
+/// `ChromeTracing::scope()`.
+#[must_use = "the end of the region is recorded when the guard is dropped"]
+pub struct TraceGuard<'a> {
+    tracing: &'a ChromeTracing,
+    name: String,
+    // The end event must be recorded on the thread of the begin event.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for TraceGuard<'_> {
+    fn drop(&mut self) {
+        self.tracing.end(std::mem::take(&mut self.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Value;
+
+    #[test]
+    fn test_chrome_tracing_events() {
+        let tracing = ChromeTracing::new();
+        assert_eq!(tracing.to_json(), "[]\n");
+        {
+            let _outer = tracing.scope("outer \"quoted\"");
+            tracing.begin("inner");
+            tracing.end("inner");
+        }
+        assert_eq!(tracing.len(), 4);
+
+        let json = tracing.to_json();
+        let Some(Value::Array(events)) = json::parse(&json) else {
+            panic!("invalid trace: {}", json);
+        };
+        // The test threads are named after the tests.
+        let metadata = events[0].as_object().unwrap();
+        assert_eq!(metadata[1], ("ph".to_string(), Value::String("M".into())));
+        let phases: Vec<(String, String)> = events[1..]
+            .iter()
+            .map(|event| {
+                let event = event.as_object().unwrap();
+                match (&event[0].1, &event[1].1) {
+                    (Value::String(name), Value::String(phase)) => (name.clone(), phase.clone()),
+                    _ => panic!("invalid event"),
+                }
+            })
+            .collect();
+        assert_eq!(
+            phases,
+            [
+                ("outer \"quoted\"".to_string(), "B".to_string()),
+                ("inner".to_string(), "B".to_string()),
+                ("inner".to_string(), "E".to_string()),
+                ("outer \"quoted\"".to_string(), "E".to_string()),
+            ]
+        );
+
+        tracing.clear();
+        assert!(tracing.is_empty());
+    }
+
+    #[test]
+    fn test_chrome_tracing_threads() {
+        let tracing = ChromeTracing::new();
+        tracing.begin("main");
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let _worker = tracing.scope("worker");
+            });
+        });
+        tracing.end("main");
+
+        let events = tracing.events.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].tid, events[3].tid);
+        assert_eq!(events[1].tid, events[2].tid);
+        assert_ne!(events[0].tid, events[1].tid);
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    }
+}
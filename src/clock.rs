@@ -0,0 +1,174 @@
+//! Sources of time for the timers.
+//!
+//! This module provides the `Clock` trait, read by a `TimeLapse` for the current time. Timers
+//! read the system monotonic clock by default; another clock can be injected with
+//! `TimeLapse::with_clock()`, e.g. the `VirtualClock` of the `testing` module to test timing
+//! code deterministically.
+//!
+//! The `CoarseClock` struct trades resolution for nearly free reads, for extremely hot polling
+//! loops on platforms where reading the system clock is expensive.
+
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// The update interval of a `CoarseClock` created with `CoarseClock::new()`.
+pub const DEFAULT_COARSE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The `Clock` trait gives the current time of a monotonic clock. It is unwind safe so that
+/// the timers reading it stay usable across `catch_unwind()`.
+pub trait Clock: Send + Sync + RefUnwindSafe {
+    /// Returns the current time. Successive calls must never go backwards.
+    fn now(&self) -> Instant;
+}
+
+/// The `SystemClock` struct reads the system monotonic clock, as `Instant::now()` does. It is
+/// the clock of the timers with no injected clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The `CoarseClock` struct is a clock whose time is updated every interval by a background
+/// thread, and read from an atomic.
+///
+/// Reading it costs a single atomic load instead of a system call or a clock read, but its
+/// resolution is the update interval at best: the time read can be late by up to an interval,
+/// more when the updater thread is not scheduled in time, and elapsed times shorter than an
+/// interval mostly read as zero. The updater thread wakes up every interval as long as the
+/// clock lives, so shorter intervals cost more CPU time in the background. It suits polling
+/// loops comparing elapsed times with budgets much larger than the interval.
+///
+/// The updater thread is stopped and joined when the clock is dropped, i.e. when the last
+/// timer reading it is dropped.
+///
+/// # Usage
+/// ```rust
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use timelapse::clock::CoarseClock;
+/// use timelapse::TimeLapse;
+///
+/// let clock = Arc::new(CoarseClock::new());
+/// let profiler = TimeLapse::new().with_clock(clock);
+/// let mut polls = 0u64;
+/// while !profiler.deadline_reached(Duration::from_millis(20)) {
+///     polls += 1; // ... poll ...
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CoarseClock {
+    shared: Arc<CoarseTime>,
+    updater: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// The time of a `CoarseClock`, shared with its updater thread.
+#[derive(Debug)]
+struct CoarseTime {
+    origin: Instant,
+    nanos: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl CoarseTime {
+    /// Stores the current time.
+    fn update(&self) {
+        let nanos = u64::try_from(self.origin.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+}
+
+impl CoarseClock {
+    /// Creates a new `CoarseClock` updated every `DEFAULT_COARSE_INTERVAL`, and starts its
+    /// updater thread.
+    pub fn new() -> Self {
+        Self::with_interval(DEFAULT_COARSE_INTERVAL)
+    }
+
+    /// Creates a new `CoarseClock` updated every `interval`, and starts its updater thread.
+    ///
+    /// # Panics
+    /// Panics if the updater thread cannot be spawned.
+    pub fn with_interval(interval: Duration) -> Self {
+        let shared = Arc::new(CoarseTime {
+            origin: Instant::now(),
+            nanos: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+        });
+        let updater = {
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name("timelapse-coarse-clock".to_string())
+                .spawn(move || {
+                    while !shared.stopped.load(Ordering::Relaxed) {
+                        std::thread::park_timeout(interval);
+                        shared.update();
+                    }
+                })
+                .expect("failed to spawn the coarse clock updater thread")
+        };
+        CoarseClock {
+            shared,
+            updater: Mutex::new(Some(updater)),
+        }
+    }
+}
+
+/// Implements the `Default` trait for the `CoarseClock` struct, updated every
+/// `DEFAULT_COARSE_INTERVAL`.
+impl std::default::Default for CoarseClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for CoarseClock {
+    fn now(&self) -> Instant {
+        self.shared.origin + Duration::from_nanos(self.shared.nanos.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for CoarseClock {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+        let updater = self
+            .updater
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(updater) = updater.take() {
+            updater.thread().unpark();
+            let _ = updater.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coarse_clock() {
+        let clock = CoarseClock::with_interval(Duration::from_millis(1));
+        let start = clock.now();
+        let mut previous = start;
+        let waiting = Instant::now();
+        while clock.now() < start + Duration::from_millis(5) {
+            let now = clock.now();
+            assert!(now >= previous);
+            previous = now;
+            assert!(waiting.elapsed() < Duration::from_secs(5));
+            std::hint::spin_loop();
+        }
+
+        let slow = CoarseClock::with_interval(Duration::from_secs(3600));
+        let dropping = Instant::now();
+        drop(slow);
+        assert!(dropping.elapsed() < Duration::from_secs(1));
+    }
+}
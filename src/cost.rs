@@ -0,0 +1,152 @@
+//! Elapsed time combined with a custom cost model.
+//!
+//! This module provides the `CostLapse` struct, timing a region like `TimeLapse` while summing
+//! the cost of the work recorded in it, as computed by a user-supplied closure: bytes
+//! processed, rows inserted, weighted requests... Its report gives the derived rate, in units
+//! of cost per second.
+
+use std::time::Duration;
+
+use crate::logging::Level;
+use crate::profiler::{format_message, log_gated};
+use crate::TimeLapse;
+
+/// The `CostLapse` struct measures the elapsed time and the summed cost of the work recorded
+/// with `record()`, the cost of each piece of work `T` being given by a closure.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::cost::CostLapse;
+///
+/// let mut parsing = CostLapse::new("parsing", "bytes", |chunk: &[u8]| chunk.len() as f64);
+/// for chunk in [&b"GET /"[..], &b"index.html"[..]] {
+///     // ... parse the chunk ...
+///     parsing.record(chunk);
+/// }
+/// assert_eq!(parsing.total_cost(), 15.0);
+/// println!("{}", parsing.report()); // TimeLapse parsing - Elapsed time: 2µs, 15 bytes (7500000.00 bytes/s)
+/// ```
+pub struct CostLapse<T> {
+    name: String,
+    unit: String,
+    cost: Box<dyn Fn(T) -> f64 + Send + Sync>,
+    total_cost: f64,
+    timer: TimeLapse,
+}
+
+impl<T> CostLapse<T> {
+    /// Creates a new `CostLapse` with the given name and cost unit, started at the current
+    /// time, the cost of a piece of work being given by `cost`.
+    pub fn new(
+        name: impl Into<String>,
+        unit: impl Into<String>,
+        cost: impl Fn(T) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        CostLapse {
+            name: name.into(),
+            unit: unit.into(),
+            cost: Box::new(cost),
+            total_cost: 0.0,
+            timer: TimeLapse::new(),
+        }
+    }
+
+    /// Adds the cost of a piece of work to the total.
+    pub fn record(&mut self, work: T) {
+        self.total_cost += (self.cost)(work);
+    }
+
+    /// Returns the summed cost of the recorded work.
+    pub fn total_cost(&self) -> f64 {
+        self.total_cost
+    }
+
+    /// Returns the elapsed time since the creation of the `CostLapse`.
+    pub fn elapsed(&self) -> Duration {
+        self.timer.elapsed()
+    }
+
+    /// Returns the rate of the recorded work, in units of cost per second, or `None` if no time
+    /// elapsed.
+    pub fn rate(&self) -> Option<f64> {
+        rate(self.total_cost, self.elapsed())
+    }
+
+    /// Returns the report of the elapsed time, the summed cost and the rate, e.g.
+    /// `TimeLapse parsing - Elapsed time: 2s, 3000 bytes (1500.00 bytes/s)`. The message
+    /// starts with the template set with `TimeLapse::set_log_format()`.
+    pub fn report(&self) -> String {
+        render(&self.name, &self.unit, self.total_cost, self.elapsed())
+    }
+
+    /// Logs the report at the given level. Returns `true` if the report was logged, which is
+    /// not the case if the elapsed time is below the floor set with
+    /// `TimeLapse::set_min_loggable()` or if the region is disabled (see
+    /// `registry::set_region_enabled()`).
+    pub fn log(&self, level: Level) -> bool {
+        let elapsed = self.elapsed();
+        log_gated(&self.name, elapsed, level, || {
+            render(&self.name, &self.unit, self.total_cost, elapsed)
+        })
+    }
+}
+
+/// Returns the rate of a cost over an elapsed time, per second.
+fn rate(cost: f64, elapsed: Duration) -> Option<f64> {
+    let secs = elapsed.as_secs_f64();
+    (secs > 0.0).then(|| cost / secs)
+}
+
+/// Renders the report of a `CostLapse`.
+fn render(name: &str, unit: &str, cost: f64, elapsed: Duration) -> String {
+    let mut report = format!("{}, {} {}", format_message(name, elapsed), cost, unit);
+    if let Some(rate) = rate(cost, elapsed) {
+        report.push_str(&format!(" ({:.2} {}/s)", rate, unit));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_lapse() {
+        let mut inserts = CostLapse::new("inserts", "rows", |batch: Vec<u32>| batch.len() as f64);
+        inserts.record(vec![1, 2, 3]);
+        inserts.record(Vec::new());
+        inserts.record(vec![4]);
+        assert_eq!(inserts.total_cost(), 4.0);
+        assert!(inserts.report().contains(", 4 rows"));
+
+        let mut weighted = CostLapse::new("requests", "points", |weight: f64| weight);
+        weighted.record(0.5);
+        weighted.record(2.0);
+        assert_eq!(weighted.total_cost(), 2.5);
+    }
+
+    #[test]
+    fn test_cost_lapse_log() {
+        let enabled = CostLapse::new("test_cost_lapse_log", "rows", |rows: u32| rows as f64);
+        assert!(enabled.log(Level::Info));
+        crate::registry::set_region_enabled("test_cost_lapse_log_disabled", false);
+        let disabled = CostLapse::new("test_cost_lapse_log_disabled", "rows", |rows: u32| {
+            rows as f64
+        });
+        assert!(!disabled.log(Level::Info));
+    }
+
+    #[test]
+    fn test_cost_lapse_render() {
+        assert_eq!(
+            render("parsing", "bytes", 3000.0, Duration::from_secs(2)),
+            "TimeLapse parsing - Elapsed time: 2s, 3000 bytes (1500.00 bytes/s)"
+        );
+        assert_eq!(
+            render("parsing", "bytes", 0.0, Duration::ZERO),
+            "TimeLapse parsing - Elapsed time: 0ns, 0 bytes"
+        );
+        assert_eq!(rate(10.0, Duration::from_millis(500)), Some(20.0));
+        assert_eq!(rate(10.0, Duration::ZERO), None);
+    }
+}
@@ -0,0 +1,91 @@
+//! CPU time measurements, enabled with the `cpu-time` feature.
+//!
+//! With this feature, each `TimeLapse` also reads the CPU time consumed by the process when
+//! started, so `TimeLapse::cpu_elapsed()` gives the CPU time spent by all the threads of the
+//! process since then, and `TimeLapse::cpu_utilization()` its ratio to the wall time elapsed.
+//! A utilization close to 1.0 denotes a CPU-bound single-threaded region, a lower one a region
+//! waiting on I/O or locks, and a higher one a region keeping several threads busy.
+//!
+//! The CPU time is read with `clock_gettime(CLOCK_PROCESS_CPUTIME_ID)`, so it is only
+//! available on Unix systems. Elsewhere, the CPU time always reads as zero.
+
+use std::time::Duration;
+
+use crate::TimeLapse;
+
+/// Returns the CPU time consumed by all the threads of the process since it started.
+#[cfg(unix)]
+pub fn process_cpu_time() -> Duration {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `now` is a valid, writable `timespec`.
+    if unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut now) } != 0 {
+        return Duration::ZERO;
+    }
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
+
+/// Returns the CPU time consumed by all the threads of the process since it started.
+/// Always zero on this platform.
+#[cfg(not(unix))]
+pub fn process_cpu_time() -> Duration {
+    Duration::ZERO
+}
+
+impl TimeLapse {
+    /// Returns the CPU time consumed by the process since the timer was started, frozen when
+    /// the timer is stopped. A timer not yet started returns zero.
+    pub fn cpu_elapsed(&self) -> Duration {
+        match self.cpu_marks() {
+            (_, Some(elapsed)) => elapsed,
+            _ if !self.is_started() => Duration::ZERO,
+            (start, None) => process_cpu_time().saturating_sub(start),
+        }
+    }
+
+    /// Returns the CPU utilization of the measured region: the CPU time consumed by the
+    /// process divided by the wall time elapsed. It can exceed 1.0 when several threads are
+    /// busy. Returns zero if no wall time has elapsed.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let profiler = TimeLapse::new();
+    /// std::thread::sleep(std::time::Duration::from_millis(20));
+    /// // Sleeping does not use the CPU.
+    /// assert!(profiler.cpu_utilization() < 0.5);
+    /// ```
+    pub fn cpu_utilization(&self) -> f64 {
+        let wall = self.elapsed();
+        if wall.is_zero() {
+            return 0.0;
+        }
+        self.cpu_elapsed().as_secs_f64() / wall.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_utilization() {
+        assert_eq!(TimeLapse::uninit().cpu_utilization(), 0.0);
+        assert_eq!(TimeLapse::uninit().cpu_elapsed(), Duration::ZERO);
+
+        let mut profiler = TimeLapse::new();
+        let busy = TimeLapse::new();
+        while busy.elapsed() < Duration::from_millis(20) {
+            std::hint::black_box(busy.elapsed());
+        }
+        profiler.stop();
+        let frozen = profiler.cpu_elapsed();
+        assert!(frozen >= Duration::from_millis(5));
+        assert!(profiler.cpu_utilization() > 0.25);
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(profiler.cpu_elapsed(), frozen);
+    }
+}
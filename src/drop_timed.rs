@@ -0,0 +1,120 @@
+//! Timing of destructors.
+//!
+//! This module provides the `DropTimed` struct, wrapping a value to log how long dropping it
+//! takes, which surfaces the expensive destructors, e.g. of large collections or connection
+//! pools, that cannot be wrapped in a timed block like the rest of the code.
+//!
+//! # Drop order
+//!
+//! The wrapped value is held in a `ManuallyDrop`, so that Rust does not drop it on its own.
+//! The `Drop` implementation of `DropTimed` starts a timer, drops the value explicitly, and
+//! then logs the elapsed time: the measurement covers the destructor of the value and the
+//! destructors of all its fields, run in their usual order, and nothing else. The value is
+//! dropped where the `DropTimed` is, i.e. at the end of its scope or with `drop()`, and
+//! `into_inner()` gives it back without timing anything.
+
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+
+use crate::logging::Level;
+use crate::TimeLapse;
+
+/// The `DropTimed` struct logs the time taken by the drop of the value it wraps. It
+/// dereferences to the value.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::drop_timed::DropTimed;
+///
+/// let mut cache = DropTimed::new("cache", Vec::new());
+/// cache.extend(0..1_000_000);
+/// drop(cache); // "TimeLapse cache - Elapsed time: ..."
+/// ```
+#[derive(Debug)]
+pub struct DropTimed<T> {
+    value: ManuallyDrop<T>,
+    name: String,
+    level: Level,
+}
+
+impl<T> DropTimed<T> {
+    /// Creates a new `DropTimed` wrapping `value`, logging the time taken by its drop at the
+    /// info level under the given name.
+    pub fn new(name: impl Into<String>, value: T) -> Self {
+        DropTimed {
+            value: ManuallyDrop::new(value),
+            name: name.into(),
+            level: Level::Info,
+        }
+    }
+
+    /// Sets the level at which the time taken by the drop is logged.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Returns the wrapped value, without dropping nor timing it.
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        drop(std::mem::take(&mut this.name));
+        // SAFETY: `this` is never dropped, so the value is taken out exactly once.
+        unsafe { ManuallyDrop::take(&mut this.value) }
+    }
+}
+
+impl<T> Deref for DropTimed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for DropTimed<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for DropTimed<T> {
+    fn drop(&mut self) {
+        let timer = TimeLapse::new();
+        // SAFETY: the value is dropped only here, and never used again.
+        unsafe { ManuallyDrop::drop(&mut self.value) };
+        timer.log(&self.name, self.level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A value counting its drops.
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_drop_timed() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut timed =
+            DropTimed::new("tracked", vec![Tracked(Arc::clone(&drops))]).with_level(Level::Debug);
+        timed.push(Tracked(Arc::clone(&drops)));
+        assert_eq!(timed.len(), 2);
+        drop(timed);
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+        let timed = DropTimed::new("kept", Tracked(Arc::clone(&drops)));
+        let tracked = timed.into_inner();
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+        drop(tracked);
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+    }
+}
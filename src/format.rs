@@ -0,0 +1,370 @@
+//! Human-friendly formatting of durations.
+//!
+//! This module provides the `human()` function, rendering a duration with the unit best suited
+//! to its magnitude and at most two decimals (e.g. `12.35ms`, `1.50s`, `2m 05s`), which reads
+//! better in logs than the full precision of the `Debug` format.
+//!
+//! The `si()` function, also available as `TimeLapse::format_si()`, renders a duration in
+//! engineering notation instead: always in seconds, with an SI prefix (e.g. `12.3 ms`,
+//! `4.56 µs`), as expected by engineering reports.
+//!
+//! The `fixed_width()` function, also available as `TimeLapse::format_fixed_width()`, renders
+//! a duration right-justified in a constant number of characters, for the live readouts of
+//! terminal interfaces whose columns must not move between updates.
+//!
+//! The `parse_duration()` function goes the other way, reading durations written in config
+//! files or command-line arguments (e.g. `1.5ms`, `200us`, `2s`).
+
+use std::time::Duration;
+
+use crate::TimeLapse;
+
+/// The SI prefixes of `si()`, from nanoseconds, each a thousand times the previous one.
+const SI_PREFIXES: [&str; 7] = ["n", "µ", "m", "", "k", "M", "G"];
+
+/// The character filling the field of `fixed_width()` when the duration does not fit in it.
+pub const OVERFLOW_MARKER: char = '#';
+
+/// Formats a duration for humans:
+///
+/// - below a microsecond, in whole nanoseconds (`850ns`);
+/// - below a second, in microseconds or milliseconds with two decimals (`12.35ms`);
+/// - below a minute, in seconds with two decimals (`1.50s`);
+/// - above, in whole minutes and seconds (`2m 05s`), and hours (`1h 02m 05s`).
+///
+/// The unit is chosen after rounding, e.g. 999.999µs is `1.00ms` and 59.999s is `1m 00s`.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::format::human;
+///
+/// assert_eq!(human(Duration::from_micros(12_345)), "12.35ms");
+/// assert_eq!(human(Duration::from_secs(125)), "2m 05s");
+/// ```
+pub fn human(elapsed: Duration) -> String {
+    let nanos = elapsed.as_nanos();
+    if nanos < 1_000 {
+        return format!("{}ns", nanos);
+    }
+    let (value, unit) = scaled(nanos, 2);
+    if unit != "s" || round_to(value, 2) < 60.0 {
+        return format!("{:.2}{}", value, unit);
+    }
+    // Whole seconds are rounded too, e.g. 59.999s to 1m 00s.
+    let secs = elapsed.as_secs() + u64::from(elapsed.subsec_nanos() >= 500_000_000);
+    if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// Returns a duration of at least a microsecond in `µs`, `ms` or `s`, choosing the unit after
+/// rounding to the given number of decimals, so that rounding carries over to the next unit,
+/// e.g. 999.999µs is 1.00ms rather than 1000.00µs.
+fn scaled(nanos: u128, decimals: usize) -> (f64, &'static str) {
+    for (scale, unit) in [(1e3, "µs"), (1e6, "ms")] {
+        let value = nanos as f64 / scale;
+        if round_to(value, decimals) < 1000.0 {
+            return (value, unit);
+        }
+    }
+    (nanos as f64 / 1e9, "s")
+}
+
+/// Rounds a value to the given number of decimals.
+fn round_to(value: f64, decimals: usize) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// Formats a duration in engineering notation: in seconds with an SI prefix, the number
+/// being between 1 and 1000 with three significant digits (`850 ns`, `4.56 µs`, `12.3 ms`,
+/// `1.50 s`, `3.60 ks`).
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::format::si;
+///
+/// assert_eq!(si(Duration::from_micros(1_234)), "1.23 ms");
+/// assert_eq!(si(Duration::from_nanos(4_560)), "4.56 µs");
+/// ```
+pub fn si(elapsed: Duration) -> String {
+    let nanos = elapsed.as_nanos();
+    if nanos == 0 {
+        return "0 s".to_string();
+    }
+    let mut index = 0;
+    while index + 1 < SI_PREFIXES.len() && nanos >= 1000u128.pow(index as u32 + 1) {
+        index += 1;
+    }
+    let mut mantissa = nanos as f64 / 1000f64.powi(index as i32);
+    let decimals_for = |mantissa: f64| -> usize {
+        match mantissa {
+            m if m < 10.0 => 2,
+            m if m < 100.0 => 1,
+            _ => 0,
+        }
+    };
+    // Rounding to three digits may carry over to the next power of ten, e.g. 9.996 µs to
+    // 10.0 µs, or to the next prefix, e.g. 999.6 µs to 1.00 ms.
+    let factor = 10f64.powi(decimals_for(mantissa) as i32);
+    let rounded = (mantissa * factor).round() / factor;
+    let decimals = if rounded >= 1000.0 && index + 1 < SI_PREFIXES.len() {
+        mantissa /= 1000.0;
+        index += 1;
+        2
+    } else {
+        decimals_for(rounded)
+    };
+    format!("{:.*} {}s", decimals, mantissa, SI_PREFIXES[index])
+}
+
+/// Formats a duration right-justified in exactly `width` characters, in the unit `human()`
+/// would choose (`ns`, `µs`, `ms` or `s`, seconds being used above a minute too) with up to
+/// two decimals, dropping decimals when they do not fit. A duration still too large for the
+/// width is rendered as `width` times the `OVERFLOW_MARKER`, so that the field never grows.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::format::fixed_width;
+///
+/// assert_eq!(fixed_width(Duration::from_micros(12_345), 9), "  12.35ms");
+/// assert_eq!(fixed_width(Duration::from_micros(12_345), 5), " 12ms");
+/// assert_eq!(fixed_width(Duration::from_secs(12_345), 5), "#####");
+/// ```
+pub fn fixed_width(elapsed: Duration, width: usize) -> String {
+    let nanos = elapsed.as_nanos();
+    let mut decimals = if nanos < 1_000 { 0 } else { 2 };
+    loop {
+        // The unit is chosen again for each number of decimals, as dropping decimals may carry
+        // over to the next unit, e.g. 999.7µs to 1ms.
+        let text = if nanos < 1_000 {
+            format!("{}ns", nanos)
+        } else {
+            let (value, unit) = scaled(nanos, decimals);
+            format!("{:.*}{}", decimals, value, unit)
+        };
+        if text.chars().count() <= width {
+            return format!("{:>width$}", text, width = width);
+        }
+        if decimals == 0 {
+            return OVERFLOW_MARKER.to_string().repeat(width);
+        }
+        decimals -= 1;
+    }
+}
+
+impl TimeLapse {
+    /// Returns the elapsed time in engineering notation, e.g. `12.3 ms`. See `format::si()`.
+    pub fn format_si(&self) -> String {
+        si(self.elapsed())
+    }
+
+    /// Returns the elapsed time right-justified in exactly `width` characters, e.g.
+    /// `  12.35ms` for a width of 9. See `format::fixed_width()`.
+    pub fn format_fixed_width(&self, width: usize) -> String {
+        fixed_width(self.elapsed(), width)
+    }
+}
+
+/// Parses a duration made of a number, optionally fractional, and a unit among `ns`, `us` (or
+/// `µs`), `ms`, `s` and `m` (minutes), optionally separated by spaces. The conversion is exact
+/// down to the nanosecond: the digits beyond it are ignored.
+///
+/// # Errors
+/// Returns an error if the text is empty, has no unit or an unknown one, if the number is
+/// invalid, or if the duration does not fit in a `u64` of nanoseconds.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::format::{parse_duration, ParseDurationError};
+///
+/// assert_eq!(parse_duration("1.5ms"), Ok(Duration::from_micros(1_500)));
+/// assert_eq!(parse_duration("200us"), Ok(Duration::from_micros(200)));
+/// assert_eq!(parse_duration("2 m"), Ok(Duration::from_secs(120)));
+/// assert_eq!(parse_duration("200"), Err(ParseDurationError::MissingUnit));
+/// ```
+pub fn parse_duration(text: &str) -> Result<Duration, ParseDurationError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(ParseDurationError::Empty);
+    }
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let nanos_per_unit: u128 = match unit.trim_start() {
+        "" => return Err(ParseDurationError::MissingUnit),
+        "ns" => 1,
+        "us" | "µs" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        _ => return Err(ParseDurationError::InvalidUnit),
+    };
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if (integer.is_empty() && fraction.is_empty()) || fraction.contains('.') {
+        return Err(ParseDurationError::InvalidNumber);
+    }
+    let integer: u128 = if integer.is_empty() {
+        0
+    } else {
+        integer
+            .parse()
+            .map_err(|_| ParseDurationError::OutOfRange)?
+    };
+    let mut nanos = integer
+        .checked_mul(nanos_per_unit)
+        .ok_or(ParseDurationError::OutOfRange)?;
+    let mut scale = nanos_per_unit;
+    for digit in fraction.bytes() {
+        scale /= 10;
+        nanos += u128::from(digit - b'0') * scale;
+    }
+    u64::try_from(nanos)
+        .map(Duration::from_nanos)
+        .map_err(|_| ParseDurationError::OutOfRange)
+}
+
+/// The error returned by `parse_duration()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseDurationError {
+    /// The text is empty.
+    Empty,
+    /// The number has no unit.
+    MissingUnit,
+    /// The unit is not one of `ns`, `us`, `µs`, `ms`, `s` and `m`.
+    InvalidUnit,
+    /// The number is missing or malformed, e.g. with several decimal points.
+    InvalidNumber,
+    /// The duration does not fit in a `u64` of nanoseconds.
+    OutOfRange,
+}
+
+impl std::fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            ParseDurationError::Empty => "empty duration",
+            ParseDurationError::MissingUnit => {
+                "missing unit, expected one of ns, us, ms, s or m after the number"
+            }
+            ParseDurationError::InvalidUnit => "invalid unit, expected one of ns, us, ms, s or m",
+            ParseDurationError::InvalidNumber => "invalid number",
+            ParseDurationError::OutOfRange => "duration out of range",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human() {
+        assert_eq!(human(Duration::ZERO), "0ns");
+        assert_eq!(human(Duration::from_nanos(999)), "999ns");
+        assert_eq!(human(Duration::from_nanos(1_500)), "1.50µs");
+        assert_eq!(human(Duration::from_micros(12_345)), "12.35ms");
+        assert_eq!(human(Duration::from_millis(1_500)), "1.50s");
+        assert_eq!(human(Duration::from_secs(59)), "59.00s");
+        assert_eq!(human(Duration::from_secs(65)), "1m 05s");
+        assert_eq!(human(Duration::from_secs(3_725)), "1h 02m 05s");
+        assert_eq!(human(Duration::from_nanos(999_994)), "999.99µs");
+        assert_eq!(human(Duration::from_nanos(999_999)), "1.00ms");
+        assert_eq!(human(Duration::from_nanos(999_999_999)), "1.00s");
+        assert_eq!(human(Duration::from_millis(59_994)), "59.99s");
+        assert_eq!(human(Duration::from_millis(59_999)), "1m 00s");
+        assert_eq!(human(Duration::from_millis(3_599_600)), "1h 00m 00s");
+    }
+
+    #[test]
+    fn test_si() {
+        assert_eq!(si(Duration::ZERO), "0 s");
+        assert_eq!(si(Duration::from_nanos(850)), "850 ns");
+        assert_eq!(si(Duration::from_nanos(4_560)), "4.56 µs");
+        assert_eq!(si(Duration::from_micros(12_345)), "12.3 ms");
+        assert_eq!(si(Duration::from_millis(1_500)), "1.50 s");
+        assert_eq!(si(Duration::from_secs(3_600)), "3.60 ks");
+        assert_eq!(si(Duration::from_nanos(999_600)), "1.00 ms");
+        assert_eq!(si(Duration::from_nanos(9_996)), "10.0 µs");
+        assert_eq!(si(Duration::from_nanos(99_960)), "100 µs");
+        assert_eq!(si(Duration::from_nanos(999)), "999 ns");
+        assert_eq!(si(Duration::MAX), "18446744074 Gs");
+    }
+
+    #[test]
+    fn test_fixed_width() {
+        let fixed = |nanos: u64, width| fixed_width(Duration::from_nanos(nanos), width);
+        assert_eq!(fixed(0, 8), "     0ns");
+        assert_eq!(fixed(850, 8), "   850ns");
+        assert_eq!(fixed(4_560, 8), "  4.56µs");
+        assert_eq!(fixed(12_345_678, 8), " 12.35ms");
+        assert_eq!(fixed(1_500_000_000, 8), "   1.50s");
+        assert_eq!(fixed(125_000_000_000, 8), " 125.00s");
+        assert_eq!(fixed(12_345_678, 6), "12.3ms");
+        assert_eq!(fixed(12_345_678, 5), " 12ms");
+        assert_eq!(fixed(123_456_789, 4), "####");
+        assert_eq!(fixed(1, 0), "");
+        assert_eq!(fixed(999_999, 8), "  1.00ms");
+        assert_eq!(fixed(999_700, 6), "   1ms");
+        assert_eq!(fixed(999_999_999, 8), "   1.00s");
+        for nanos in [0, 999, 1_000, 45_678, 999_999_999, 59_999_000_000] {
+            assert_eq!(fixed(nanos, 9).chars().count(), 9);
+        }
+
+        let clock = std::sync::Arc::new(crate::testing::VirtualClock::new());
+        let mut profiler = TimeLapse::new().with_clock(clock.clone());
+        clock.advance(Duration::from_micros(1_500));
+        profiler.stop();
+        assert_eq!(profiler.format_fixed_width(8), "  1.50ms");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("250ns"), Ok(Duration::from_nanos(250)));
+        assert_eq!(parse_duration("200us"), Ok(Duration::from_micros(200)));
+        assert_eq!(parse_duration("3µs"), Ok(Duration::from_micros(3)));
+        assert_eq!(
+            parse_duration(" 16.7 ms "),
+            Ok(Duration::from_micros(16_700))
+        );
+        assert_eq!(parse_duration(".5s"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2."), Err(ParseDurationError::MissingUnit));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1.0000000001s"), Ok(Duration::from_secs(1)));
+        assert_eq!(
+            parse_duration("1.999999999999s"),
+            Ok(Duration::from_nanos(1_999_999_999))
+        );
+
+        assert_eq!(parse_duration(""), Err(ParseDurationError::Empty));
+        assert_eq!(parse_duration("5"), Err(ParseDurationError::MissingUnit));
+        assert_eq!(parse_duration("5min"), Err(ParseDurationError::InvalidUnit));
+        assert_eq!(parse_duration("-1ms"), Err(ParseDurationError::InvalidUnit));
+        assert_eq!(
+            parse_duration("1 m s"),
+            Err(ParseDurationError::InvalidUnit)
+        );
+        assert_eq!(parse_duration("ms"), Err(ParseDurationError::InvalidNumber));
+        assert_eq!(
+            parse_duration("1.2.3ms"),
+            Err(ParseDurationError::InvalidNumber)
+        );
+        assert_eq!(
+            parse_duration("99999999999m"),
+            Err(ParseDurationError::OutOfRange)
+        );
+        assert_eq!(
+            ParseDurationError::MissingUnit.to_string(),
+            "missing unit, expected one of ns, us, ms, s or m after the number"
+        );
+    }
+}
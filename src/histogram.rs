@@ -0,0 +1,448 @@
+//! A histogram of elapsed times.
+//!
+//! This module provides the `Histogram` struct, counting recorded durations into buckets
+//! delimited by fixed upper bounds. It gives an estimate of the percentiles of the recorded
+//! durations with a constant memory footprint.
+//!
+//! It also provides the `Log2Histogram` struct, needing no configuration: each duration is
+//! counted in the bucket of the highest set bit of its number of nanoseconds.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::format::human;
+
+/// The `Histogram` struct counts durations into buckets.
+///
+/// Each bucket counts the durations lower than or equal to its upper bound and greater than
+/// the bound of the previous bucket. A last overflow bucket counts the durations greater than
+/// all bounds.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::Histogram;
+///
+/// let mut histogram = Histogram::new(vec![
+///     Duration::from_millis(1),
+///     Duration::from_millis(10),
+///     Duration::from_millis(100),
+/// ]);
+/// for ms in [1, 2, 5, 8, 50] {
+///     histogram.record(Duration::from_millis(ms));
+/// }
+/// assert_eq!(histogram.count(), 5);
+/// assert_eq!(histogram.percentile(50.0), Some(Duration::from_millis(10)));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: Vec<Duration>,
+    counts: Vec<u64>,
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    /// Creates a new `Histogram` with the given bucket upper bounds.
+    /// The bounds are sorted and duplicates removed.
+    pub fn new(mut bounds: Vec<Duration>) -> Self {
+        bounds.sort();
+        bounds.dedup();
+        let counts = vec![0; bounds.len() + 1];
+        Histogram {
+            bounds,
+            counts,
+            count: 0,
+            sum: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Creates a new `Histogram` with `count` exponentially growing bucket upper bounds:
+    /// `start`, `start * factor`, `start * factor^2`, ...
+    pub fn exponential(start: Duration, factor: f64, count: usize) -> Self {
+        let bounds = (0..count)
+            .map(|i| {
+                let bound = start.as_secs_f64() * factor.powi(i as i32);
+                Duration::try_from_secs_f64(bound).unwrap_or(Duration::MAX)
+            })
+            .collect();
+        Histogram::new(bounds)
+    }
+
+    /// Records a duration into its bucket.
+    pub fn record(&mut self, elapsed: Duration) {
+        let index = self.bounds.partition_point(|bound| *bound < elapsed);
+        self.counts[index] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(elapsed);
+        self.max = self.max.max(elapsed);
+    }
+
+    /// Adds the durations recorded in `other` to this histogram, e.g. to combine the histograms
+    /// of several threads. The percentiles then reflect the combined distribution.
+    ///
+    /// # Errors
+    /// Returns `MergeError` if the histograms do not have the same bucket bounds, leaving this
+    /// histogram unchanged.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::Histogram;
+    ///
+    /// let bounds = vec![Duration::from_millis(1), Duration::from_millis(10)];
+    /// let mut total = Histogram::new(bounds.clone());
+    /// let mut worker = Histogram::new(bounds);
+    /// total.record(Duration::from_micros(500));
+    /// worker.record(Duration::from_millis(5));
+    /// total.merge(&worker).unwrap();
+    /// assert_eq!(total.counts(), &[1, 1, 0]);
+    /// ```
+    pub fn merge(&mut self, other: &Histogram) -> Result<(), MergeError> {
+        if self.bounds != other.bounds {
+            return Err(MergeError);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+        self.max = self.max.max(other.max);
+        Ok(())
+    }
+
+    /// Returns the bucket upper bounds, in ascending order.
+    pub fn bounds(&self) -> &[Duration] {
+        &self.bounds
+    }
+
+    /// Returns the count of each bucket, the last one being the overflow bucket.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the sum of the recorded durations.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Returns the largest recorded duration, zero if none was recorded.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns an estimate of the given percentile (from 0 to 100) of the recorded durations:
+    /// the upper bound of the bucket holding it, capped to the largest recorded duration.
+    /// Returns `None` if no duration was recorded.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let rank = rank.max(1);
+        let mut cumulative = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= rank {
+                let bound = self.bounds.get(index).copied().unwrap_or(self.max);
+                return Some(bound.min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Renders the histogram in the Prometheus text exposition format, as a metric named
+    /// `metric_name` with its `_bucket` lines (cumulative counts, `le` bounds in seconds, ending
+    /// with `+Inf`), and its `_sum` (in seconds) and `_count` lines.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::Histogram;
+    ///
+    /// let mut histogram = Histogram::new(vec![Duration::from_millis(1), Duration::from_millis(10)]);
+    /// histogram.record(Duration::from_millis(5));
+    /// let text = histogram.to_prometheus("request_duration_seconds");
+    /// assert!(text.contains("request_duration_seconds_bucket{le=\"0.01\"} 1\n"));
+    /// assert!(text.contains("request_duration_seconds_count 1\n"));
+    /// ```
+    pub fn to_prometheus(&self, metric_name: &str) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "# TYPE {} histogram", metric_name);
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                text,
+                "{}_bucket{{le=\"{}\"}} {}",
+                metric_name,
+                bound.as_secs_f64(),
+                cumulative
+            );
+        }
+        let _ = writeln!(text, "{}_bucket{{le=\"+Inf\"}} {}", metric_name, self.count);
+        let _ = writeln!(text, "{}_sum {}", metric_name, self.sum.as_secs_f64());
+        let _ = writeln!(text, "{}_count {}", metric_name, self.count);
+        text
+    }
+}
+
+/// Implements the `Default` trait for the `Histogram` struct: 32 buckets doubling from 1µs
+/// up to about 36 minutes.
+impl std::default::Default for Histogram {
+    fn default() -> Self {
+        Histogram::exponential(Duration::from_micros(1), 2.0, 32)
+    }
+}
+
+/// The error returned by `Histogram::merge()` when the histograms have different bucket
+/// bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MergeError;
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cannot merge histograms with different bucket bounds")
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// The number of buckets of a `Log2Histogram`: one for zero, and one for each bit of a `u64`.
+const LOG2_BUCKETS: usize = 65;
+
+/// The `Log2Histogram` struct counts durations into power-of-two buckets of nanoseconds.
+///
+/// The bucket `[2^(i-1), 2^i)` nanoseconds counts the durations whose highest set bit is the
+/// bit `i - 1`, and a first bucket counts the zero durations. Durations above `u64::MAX`
+/// nanoseconds are counted in the last bucket.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::histogram::Log2Histogram;
+///
+/// let mut histogram = Log2Histogram::new();
+/// for ns in [1_100, 1_500, 3_000] {
+///     histogram.record(Duration::from_nanos(ns));
+/// }
+/// assert_eq!(histogram.count(), 3);
+/// assert_eq!(histogram.report(), "[1.02µs-2.05µs): 2, [2.05µs-4.10µs): 1");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Log2Histogram {
+    counts: [u64; LOG2_BUCKETS],
+    count: u64,
+}
+
+impl Log2Histogram {
+    /// Creates a new empty `Log2Histogram`.
+    pub fn new() -> Self {
+        Log2Histogram {
+            counts: [0; LOG2_BUCKETS],
+            count: 0,
+        }
+    }
+
+    /// Records a duration into the bucket of its highest set bit of nanoseconds.
+    pub fn record(&mut self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let index = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.counts[index] += 1;
+        self.count += 1;
+    }
+
+    /// Adds the durations recorded in `other` to this histogram, the buckets of all
+    /// `Log2Histogram`s being the same.
+    pub fn merge(&mut self, other: &Log2Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+    }
+
+    /// Returns the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the buckets from the lowest to the highest populated one, as their lower bound
+    /// (inclusive), upper bound (exclusive) and count. The empty buckets between populated ones
+    /// are included with a zero count. Nothing is returned if no duration was recorded.
+    pub fn buckets(&self) -> Vec<(Duration, Duration, u64)> {
+        let first = self.counts.iter().position(|count| *count > 0);
+        let last = self.counts.iter().rposition(|count| *count > 0);
+        let (Some(first), Some(last)) = (first, last) else {
+            return Vec::new();
+        };
+        (first..=last)
+            .map(|index| {
+                let lower = if index == 0 { 0 } else { 1u128 << (index - 1) };
+                let upper = 1u128 << index;
+                (nanos(lower), nanos(upper), self.counts[index])
+            })
+            .collect()
+    }
+
+    /// Returns a one-line report of the buckets, e.g. `[1.02µs-2.05µs): 340, [2.05µs-4.10µs): 120`,
+    /// from the lowest to the highest populated one, the empty ones in between shown as zero.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (index, (lower, upper, count)) in self.buckets().into_iter().enumerate() {
+            if index > 0 {
+                report.push_str(", ");
+            }
+            let _ = write!(report, "[{}-{}): {}", human(lower), human(upper), count);
+        }
+        report
+    }
+}
+
+/// Implements the `Default` trait for the `Log2Histogram` struct, empty.
+impl std::default::Default for Log2Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a number of nanoseconds, possibly above `u64::MAX`, into a duration.
+fn nanos(nanos: u128) -> Duration {
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_record() {
+        let mut histogram =
+            Histogram::new(vec![Duration::from_millis(10), Duration::from_millis(1)]);
+        assert_eq!(
+            histogram.bounds(),
+            &[Duration::from_millis(1), Duration::from_millis(10)]
+        );
+        for ms in [0, 1, 2, 10, 11] {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.counts(), &[2, 2, 1]);
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.sum(), Duration::from_millis(24));
+        assert_eq!(histogram.max(), Duration::from_millis(11));
+    }
+
+    #[test]
+    fn test_histogram_percentile() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(99.0), None);
+        for _ in 0..99 {
+            histogram.record(Duration::from_micros(3));
+        }
+        histogram.record(Duration::from_millis(5));
+        assert_eq!(histogram.percentile(50.0), Some(Duration::from_micros(4)));
+        assert_eq!(histogram.percentile(99.0), Some(Duration::from_micros(4)));
+        assert_eq!(histogram.percentile(100.0), Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn test_histogram_merge() {
+        let bounds = vec![Duration::from_millis(1), Duration::from_millis(10)];
+        let mut fast = Histogram::new(bounds.clone());
+        let mut slow = Histogram::new(bounds);
+        for _ in 0..3 {
+            fast.record(Duration::from_micros(100));
+        }
+        slow.record(Duration::from_millis(5));
+        slow.record(Duration::from_millis(20));
+        assert_eq!(fast.percentile(90.0), Some(Duration::from_micros(100)));
+
+        assert_eq!(fast.merge(&slow), Ok(()));
+        assert_eq!(fast.counts(), &[3, 1, 1]);
+        assert_eq!(fast.count(), 5);
+        assert_eq!(fast.sum(), Duration::from_micros(25_300));
+        assert_eq!(fast.max(), Duration::from_millis(20));
+        assert_eq!(fast.percentile(50.0), Some(Duration::from_millis(1)));
+        assert_eq!(fast.percentile(80.0), Some(Duration::from_millis(10)));
+        assert_eq!(fast.percentile(90.0), Some(Duration::from_millis(20)));
+
+        let before = fast.clone();
+        let other = Histogram::new(vec![Duration::from_millis(1)]);
+        assert_eq!(fast.merge(&other), Err(MergeError));
+        assert_eq!(fast, before);
+        assert_eq!(
+            MergeError.to_string(),
+            "cannot merge histograms with different bucket bounds"
+        );
+
+        let mut log2 = Log2Histogram::new();
+        log2.record(Duration::from_nanos(3));
+        let mut other = Log2Histogram::new();
+        other.record(Duration::from_nanos(2));
+        other.record(Duration::from_nanos(9));
+        log2.merge(&other);
+        assert_eq!(log2.count(), 3);
+        assert_eq!(log2.report(), "[2ns-4ns): 2, [4ns-8ns): 0, [8ns-16ns): 1");
+    }
+
+    #[test]
+    fn test_histogram_to_prometheus() {
+        let mut histogram =
+            Histogram::new(vec![Duration::from_micros(500), Duration::from_millis(2)]);
+        for us in [100, 1000, 1500, 3000] {
+            histogram.record(Duration::from_micros(us));
+        }
+        assert_eq!(
+            histogram.to_prometheus("latency"),
+            "# TYPE latency histogram\n\
+             latency_bucket{le=\"0.0005\"} 1\n\
+             latency_bucket{le=\"0.002\"} 3\n\
+             latency_bucket{le=\"+Inf\"} 4\n\
+             latency_sum 0.0056\n\
+             latency_count 4\n"
+        );
+    }
+
+    #[test]
+    fn test_log2_histogram() {
+        let mut histogram = Log2Histogram::default();
+        assert_eq!(histogram.report(), "");
+        for ns in [0, 1, 3, 3, 16] {
+            histogram.record(Duration::from_nanos(ns));
+        }
+        histogram.record(Duration::MAX);
+        assert_eq!(histogram.count(), 6);
+        let buckets = histogram.buckets();
+        assert_eq!(buckets.len(), LOG2_BUCKETS);
+        assert_eq!(
+            &buckets[..6],
+            &[
+                (Duration::ZERO, Duration::from_nanos(1), 1),
+                (Duration::from_nanos(1), Duration::from_nanos(2), 1),
+                (Duration::from_nanos(2), Duration::from_nanos(4), 2),
+                (Duration::from_nanos(4), Duration::from_nanos(8), 0),
+                (Duration::from_nanos(8), Duration::from_nanos(16), 0),
+                (Duration::from_nanos(16), Duration::from_nanos(32), 1),
+            ]
+        );
+        assert_eq!(buckets[LOG2_BUCKETS - 1].2, 1);
+
+        let mut histogram = Log2Histogram::new();
+        for ns in [2, 9] {
+            histogram.record(Duration::from_nanos(ns));
+        }
+        assert_eq!(
+            histogram.report(),
+            "[2ns-4ns): 1, [4ns-8ns): 0, [8ns-16ns): 1"
+        );
+    }
+}
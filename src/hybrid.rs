@@ -0,0 +1,101 @@
+//! A clock counting the time the system spends suspended.
+//!
+//! `Instant`, used by `TimeLapse`, may or may not advance while the system is suspended or
+//! hibernated, depending on the platform:
+//!
+//! - on Linux, it reads `CLOCK_MONOTONIC`, which stops during suspend;
+//! - on macOS and iOS, it reads `CLOCK_UPTIME_RAW`, which stops during sleep;
+//! - on Windows, it reads `QueryPerformanceCounter`, which generally keeps counting.
+//!
+//! A long-lived timer in a desktop application may thus report much less than the time really
+//! elapsed once the laptop woke up. This module provides the `HybridClock` struct, recording
+//! both a monotonic and a wall-clock start, so the elapsed time can be taken including the
+//! suspended periods from the wall clock.
+//!
+//! The wall clock can be adjusted (by NTP or by the user), so the time including suspend is
+//! less reliable than the monotonic one. It is never reported as less than the monotonic time.
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// The `HybridClock` struct measures the elapsed time with both the monotonic and the wall
+/// clock.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::hybrid::HybridClock;
+///
+/// let clock = HybridClock::new();
+/// // ... long-lived work, possibly across a system sleep ...
+/// println!(
+///     "awake for {:?}, elapsed {:?}",
+///     clock.elapsed(),
+///     clock.elapsed_including_suspend()
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct HybridClock {
+    monotonic: Instant,
+    wall: SystemTime,
+}
+
+impl HybridClock {
+    /// Creates a new `HybridClock` started at the current time.
+    pub fn new() -> Self {
+        HybridClock {
+            monotonic: Instant::now(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    /// Returns the elapsed time measured by the monotonic clock, which may exclude the time
+    /// spent suspended depending on the platform.
+    pub fn elapsed(&self) -> Duration {
+        self.monotonic.elapsed()
+    }
+
+    /// Returns the elapsed time measured by the wall clock, including the time spent suspended.
+    /// If the wall clock was set back since the start, the monotonic elapsed time is returned,
+    /// as the result is never less than it.
+    pub fn elapsed_including_suspend(&self) -> Duration {
+        let monotonic = self.elapsed();
+        SystemTime::now()
+            .duration_since(self.wall)
+            .map_or(monotonic, |wall| wall.max(monotonic))
+    }
+
+    /// Returns an estimate of the time spent suspended since the start: the difference between
+    /// the wall-clock and the monotonic elapsed times.
+    pub fn suspended(&self) -> Duration {
+        self.elapsed_including_suspend()
+            .saturating_sub(self.elapsed())
+    }
+}
+
+/// Implements the `Default` trait for the `HybridClock` struct, started at the current time.
+impl std::default::Default for HybridClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hybrid_clock() {
+        let clock = HybridClock::new();
+        std::thread::sleep(Duration::from_millis(10));
+        let monotonic = clock.elapsed();
+        let including_suspend = clock.elapsed_including_suspend();
+        assert!(monotonic >= Duration::from_millis(10));
+        assert!(including_suspend >= monotonic);
+        assert!(clock.suspended() < Duration::from_millis(50));
+
+        let set_back = HybridClock {
+            monotonic: Instant::now(),
+            wall: SystemTime::now() + Duration::from_secs(3600),
+        };
+        assert!(set_back.elapsed_including_suspend() < Duration::from_secs(1));
+    }
+}
@@ -7,6 +7,109 @@
 //!
 //! ## Changelog
 //!
+//! ### [Unreleased]
+//!
+//! - Added the `Snapshot` type, a frozen measurement supporting `Add`, `Sub` (both saturating) and `Sum`.
+//! - Added `TimeLapse::stop()` to freeze the elapsed time until the next `reset()`.
+//! - Added `TimeLapse::log_vs_baseline()` to log the percentage difference from a baseline duration.
+//! - Added `to_bytes()` and `from_bytes()` to `Snapshot` and `Accumulator` for a compact binary encoding.
+//! - Added the `registry` module, a global registry of elapsed times by name, with `registry::scope()` returning a guard recording into it on drop.
+//! - Added the `time()` and `bench()` helpers, passing the measured results through the re-exported `black_box()`.
+//! - Added group and label naming (`group::label`) to `TimeLapse::log_in()`, `TimeLapse::print_in()`, the `profile_end*!` macros and the registry, with `registry::group_report()` rolling entries up by group.
+//! - Added `TimeLapse::wait_until()` and `TimeLapse::wait_until_capped()` to wait precisely for a minimum elapsed time.
+//! - The `Display` implementations of `TimeLapse` and `Snapshot` now give a compact form with the alternate flag (`{:#}`).
+//! - Added `TimeLapse::restart()` and `TimeLapse::grand_total()` to accumulate several timed segments on one instance.
+//! - Added `TimeLapse::set_log_format()` to customize the logged and printed messages with `{name}` and `{elapsed}` placeholders.
+//! - Added `TimeLapse::budget_fraction()` giving the elapsed time as a fraction of a budget.
+//! - Added laps to `TimeLapse` (`lap()` and `laps()`), and the `SharedTimeLapse` type recording them through a shared reference.
+//! - Added the `Histogram` type, and the `AdaptiveReporter` type logging only the measurements above the running p99 of their name.
+//! - Added the optional `tsc` feature providing `tsc::TscClock`, reading the CPU timestamp counter on x86_64 with a fallback to `Instant`.
+//! - Added the `token` module, timing regions spanning function boundaries with `token::begin()` and `token::end()`.
+//! - Added `registry::drain_report()` returning all the entries and clearing the registry atomically.
+//! - Added `TimeLapse::elapsed_approx()` comparing the elapsed time to an expected one within a tolerance.
+//! - Added `progress::ProgressGuard`, displaying a live elapsed time counter on terminals during long regions.
+//! - Added the `profile_match!()` macro, timing each arm of a `match` with a label.
+//! - Added `TimeLapse::uninit()` creating a timer without reading the clock, started later with `TimeLapse::start()`.
+//! - Added the `report` module with `report::compare_reports()`, a per-name comparison of two runs flagging regressions.
+//! - Added `TimeLapse::start_epoch_nanos()` giving the start of a measurement as nanoseconds since the Unix epoch.
+//! - Added the `Accumulator` type keeping statistics over recorded durations, with `Accumulator::with_auto_report()` logging a summary every N samples.
+//! - Added the `profile_scope!()` macro and the `elapsed!()` macro, reading a profiler or the innermost scope of a thread-local stack.
+//! - Added `Histogram::to_prometheus()`, rendering a histogram in the Prometheus text exposition format.
+//! - Added the `reporter` module with the `Reporter` trait, `LogReporter`, `PrintReporter` and `MultiReporter`, `profile_end!()` now reporting to the global reporter set with `reporter::set_reporter()`.
+//! - Made the registry reports deterministic: `registry::report()` is sorted by name, and the new `registry::report_by_time()` by descending elapsed time.
+//! - Added the `profile_try!()` macro, timing a `Result` expression, asserting it completed within a limit, and applying `?` to it.
+//! - Added the optional `alloc-count` feature providing `allocs::CountingAllocator`, `TimeLapse::allocs()` and the `profile_end_allocs!()` macro, reporting the heap allocations of a region.
+//! - Added `report::write_report_file()`, `report::read_report_file()` and `report::check_against_file()`, storing a report as JSON and checking a later run against it for regressions.
+//! - Added the optional `cpu-time` feature providing `TimeLapse::cpu_elapsed()` and `TimeLapse::cpu_utilization()`, the ratio of the process CPU time to the wall time of a region.
+//! - Added the `format` module with `format::human()`, and the `elapsed_ms!()` and `elapsed_fmt!()` macros.
+//! - Added `TimeLapse::reset_segment()`, restarting only the current segment, and `TimeLapse::min_lap()` and `TimeLapse::max_lap()`.
+//! - Added `hybrid::HybridClock`, whose `elapsed_including_suspend()` counts the time the system spent suspended from the wall clock.
+//! - Added the `report::Report` builder, rendering named elapsed times as a table, CSV or JSON, with an optional total row and sorting.
+//! - Documented that formatting a `Snapshot` is idempotent, unlike formatting a running `TimeLapse`.
+//! - Added the `recursion` module, accumulating the elapsed time of recursive calls per recursion depth with `recursion::enter()` guards.
+//! - Added `TimeLapse::log_first_n()`, logging only the first occurrences of a named region.
+//! - Added `TimeLapse::pause()`, `TimeLapse::resume()` and `TimeLapse::is_running()`, and the `Stopwatch` struct with the usual stopwatch semantics.
+//! - Added key-value tags to measurements with `TimeLapse::with_tag()`, passed to the reporters by `Reporter::report_tagged()`.
+//! - Added `TimeLapse::deadline_reached()` and `TimeLapse::remaining()` for time-budgeted loops.
+//! - Added `TimeLapse::from_span()` and `Snapshot::from_span_nanos()`, building snapshots from externally recorded timestamps.
+//! - Added exclusive (self) time tracking to `profile_scope!()` scopes, with `scope::stats()` and `scope::report()` showing inclusive and exclusive times.
+//! - Added the `thresholds` module, parsing per-region thresholds from `region = duration` lines, consulted by `TimeLapse::log_if_slow()`, and `TimeLapse::log_if_slower_than()`.
+//! - Added the `Log2Histogram` type, counting durations into power-of-two buckets of nanoseconds without any configuration, and its one-line `report()`.
+//! - Added the optional `indicatif` feature providing `progress_bar::ProgressBarTimer`, showing the live elapsed time as the message of an `indicatif` progress bar and finishing it when dropped.
+//! - Added `TimeLapse::eta()`, estimating the time left to complete a task from the fraction done so far.
+//! - Added the `profile_lines!()` macro, timing and reporting each statement of a block individually, named after its text.
+//! - Added `Accumulator::reset_extremes()`, clearing the minimum and maximum while keeping the count and total, e.g. after a warmup period.
+//! - Added the `chrome` module with the `ChromeTracing` collector, recording begin and end events and rendering them in the Trace Event Format for `chrome://tracing` and Perfetto.
+//! - Added `TimeLapse::set_min_loggable()`, a global floor below which `TimeLapse::log()` skips the elapsed times, zero by default. `TimeLapse::log()`, `Accumulator::log()` and `CostLapse::log()` now return whether they logged.
+//! - Added `registry::with_entries()`, iterating over the registry entries under its lock without cloning them.
+//! - Added the optional `backtrace` feature, making `TimeLapse::log_if_slower_than()` and `TimeLapse::log_if_slow()` log a trimmed backtrace of the caller with the slow measurements.
+//! - Added the `budget` module with `TimeLapse::classify()` and `TimeLapse::classify_with_tolerance()`, returning a `BudgetStatus` carrying the headroom left or the overage.
+//! - Added `reporter::AsyncReporter`, forwarding the reports to another reporter from a background thread through a bounded queue, counting the reports dropped when full, and `reporter::flush()`, also available as `registry::flush()`, with the `Reporter::flush()` method. Only the reports of `TimeLapse::report()` and `profile_end!` go through the queue: `TimeLapse::log()`, the other `log_*` methods and the helpers logging their measurements still log synchronously from the calling thread.
+//! - Added the `assert_faster!()` macro, asserting that a measurement is faster than another one, with an optional tolerance.
+//! - Added `format::parse_duration()`, parsing durations such as `1.5ms`, `200us` or `2m` exactly to the nanosecond, also used by `ThresholdMap`, which now accepts minutes.
+//! - Added the `Reservoir` type, keeping a uniform random sample of fixed capacity of the recorded durations with the algorithm R, seeded deterministically by default.
+//! - Added `TimeLapse::state()` returning a `TimerState` (running, paused or stopped), and `TimeLapse::is_paused()`. Pausing and stopping are now distinct: resuming a stopped timer starts a new measurement, only a paused one continues.
+//! - Added the `cost` module with `CostLapse`, timing a region while summing the cost of its work given by a closure, and reporting the derived rate.
+//! - Added `TimeLapse::log_on_new_max()`, logging an elapsed time only when it is the largest seen so far for its name, across threads.
+//! - Added the optional `tracing` feature providing `tracing_layer::HistogramLayer`, a `tracing_subscriber` layer recording the durations of the spans into a histogram per span name.
+//! - Added the `Clock` trait and `TimeLapse::with_clock()`, with a `testing::VirtualClock` advanced by hand for deterministic tests.
+//! - Added `registry::set_region_enabled()`, `clear_region_enabled()`, `is_region_enabled()` and `set_enabled()` to disable the logging, printing and reporting of named regions at runtime. They apply, as does the floor of `TimeLapse::set_min_loggable()`, to every method and helper logging or reporting a named region.
+//! - Added `format::si()` and `TimeLapse::format_si()`, formatting durations in engineering notation with SI-prefixed seconds (e.g. `4.56 µs`).
+//! - Added the `profile_loop!` macro, running a block a number of times with an optional loop index and logging the total and per-iteration times.
+//! - Added `TimeLapse::log_adaptive()`, logging at the level of the highest elapsed-time threshold reached.
+//! - Added the `profile_into!` macro, storing the elapsed time of a block or expression into a `&mut Duration` instead of logging it.
+//! - Added `registry::hottest()`, returning the entry with the largest total and its fraction of the grand total.
+//! - Added `registry::snapshot()` and `RegistrySnapshot::diff()`, returning the time recorded per name between two snapshots.
+//! - Added `TimeLapse::set_floor_warning()`, off by default, suffixing the logged elapsed times near the calibrated `TimeLapse::measurement_floor()` with `(near measurement floor)`.
+//! - Added the `scoped_registry` module, with `ScopedRegistry` accumulating elapsed times by name separately for each session id, e.g. per request.
+//! - Added `TimeLapse::tick()`, `frames()` and `fps()` for frame loops, the frame rate being computed over the last `FPS_WINDOW` ticks.
+//! - Added `TimeLapse::try_set_log_format()`, rejecting templates with unknown or unclosed placeholders with a `FormatError`; `set_log_format()` now panics on them.
+//! - Added `clock::CoarseClock`, a clock read from an atomic updated periodically by a background thread, stopped when the clock is dropped.
+//! - Added `measure::profile_retry()` and `profile_retry_with_backoff()`, retrying a fallible operation and logging the time of each attempt and the total.
+//! - Added the optional `net` feature providing `net::SocketReporter`, streaming the reports as lines of JSON to a TCP endpoint or a Unix socket from a background thread, with reconnection and a count of the dropped reports.
+//! - Added `Accumulator::with_warmup()`, discarding the first recorded durations from all the statistics.
+//! - Added `try_time()` and `measure::try_time_with_level()`, timing a fallible closure, logging its elapsed time on success and failure alike and returning its `Result` unchanged.
+//! - Added `TimeLapse::sleep_timed()`, sleeping with the timer paused so that the sleep is not counted.
+//! - Added `Histogram::merge()`, summing the buckets of a histogram with the same bounds, and `Log2Histogram::merge()`.
+//! - Added the `timers!` macro, declaring region names as `&'static str` constants checked at compile time.
+//! - Added `format::fixed_width()` and `TimeLapse::format_fixed_width()`, rendering durations in a constant number of characters for live terminal readouts.
+//! - Added `TimeLapse::on_lap()`, streaming each lap to a callback instead of keeping it.
+//! - Added `TimeLapse::set_env_baselines()`, comparing the elapsed times of `log()` to the `TIMELAPSE_BASELINE_<NAME>` environment variables.
+//! - Made the `log` dependency optional, behind the default `log` feature: without it, the logging methods and macros compile but discard their messages, and `timelapse::Level` stands for `log::Level`.
+//! - Added `progress::ProgressTimer`, logging the processed count, elapsed time and ETA of a batch job on demand.
+//! - Added `drop_timed::DropTimed`, a wrapper logging the time taken by the drop of its value.
+//! - Added `report::rank()`, ranking named timings from the fastest to the slowest with their ratio to the fastest.
+//! - Added the `debug_profile!` macro, timing and reporting a block in debug builds only.
+//! - Added `TimeLapse::log_throughput_unit()` and `TimeLapse::log_bandwidth()`, logging the processed count with its unit and the derived rate.
+//! - Added `registry::set_overhead_tracking()` and `registry::profiling_overhead()`, accumulating the time spent in the global reporter.
+//! - Added the `typed_registry` module, with `TypedRegistry` accumulating elapsed times under the variants of an enum declared with `timers! { enum ... }`, and reporting every variant.
+//! - Added `tracing_layer::record_elapsed()`, returning a guard recording its elapsed time as the `elapsed_ms` field of a `tracing` span when dropped.
+//! - Added `bench_stable()`, timing each iteration of a closure and logging the median, mean and standard deviation of the times without the outliers beyond 3 MADs from the median.
+//! - Added `time_err()`, timing a fallible closure and wrapping its error in a `TimedError` carrying the time spent before failing.
+//! - Added `merge_laps()`, summing the laps of several `TimeLapse` instances by label.
+//! - Added `TimeLapse::log_machine()`, logging the elapsed time in a fixed `timelapse name=... elapsed_ns=... ts=...` format independent of the formatting settings.
+//! - Added `measure::try_time_iter()`, collecting an iterator of `Result`s while timing each item, and stopping at the first error, returned as an `IterError` with its index and the elapsed time.
+//!
 //! ### [0.1.3] - 2025-06-28
 //!
 //! - Added two new macros:
@@ -28,6 +131,58 @@
 //!
 //! Initial release
 
+pub mod accumulator;
+pub mod adaptive;
+#[cfg(feature = "alloc-count")]
+pub mod allocs;
+pub mod budget;
+pub mod chrome;
+pub mod clock;
+pub mod cost;
+#[cfg(feature = "cpu-time")]
+pub mod cpu;
+pub mod drop_timed;
+pub mod format;
+pub mod histogram;
+pub mod hybrid;
+mod json;
+mod logging;
+pub mod measure;
+#[cfg(feature = "net")]
+pub mod net;
 pub mod profiler;
+pub mod progress;
+#[cfg(feature = "indicatif")]
+pub mod progress_bar;
+pub mod recursion;
+pub mod registry;
+pub mod report;
+pub mod reporter;
+pub mod reservoir;
+pub mod scope;
+pub mod scoped_registry;
+pub mod shared;
+pub mod snapshot;
+pub mod stopwatch;
+pub mod testing;
+pub mod thresholds;
+pub mod throughput;
+pub mod token;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+#[cfg(feature = "tsc")]
+pub mod tsc;
+pub mod typed_registry;
 
-pub use profiler::TimeLapse;
+pub use accumulator::Accumulator;
+pub use adaptive::AdaptiveReporter;
+pub use histogram::{Histogram, Log2Histogram};
+pub use logging::Level;
+pub use measure::{
+    bench, bench_stable, black_box, profile_retry, time, time_err, try_time, TimedError,
+};
+pub use profiler::{merge_laps, TimeLapse};
+pub use reservoir::Reservoir;
+pub use shared::SharedTimeLapse;
+pub use snapshot::Snapshot;
+pub use stopwatch::Stopwatch;
@@ -0,0 +1,54 @@
+//! The logging backend of the crate.
+//!
+//! With the default `log` feature, the messages go through the `log` crate, and `Level` is
+//! `log::Level`. Without it, the crate has no logging dependency: `Level` is a stand-in enum
+//! with the same variants, and the messages are discarded, so that the logging methods and
+//! macros still compile but do nothing, the measurement being unaffected.
+
+#[cfg(feature = "log")]
+pub use log::Level;
+
+#[cfg(feature = "log")]
+pub(crate) use log::log;
+
+/// The `Level` enum stands for `log::Level` when the `log` feature is disabled.
+#[cfg(not(feature = "log"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// The "error" level.
+    Error = 1,
+    /// The "warn" level.
+    Warn,
+    /// The "info" level.
+    Info,
+    /// The "debug" level.
+    Debug,
+    /// The "trace" level.
+    Trace,
+}
+
+/// Implements the `Display` trait for the `Level` enum, in uppercase like `log::Level`.
+#[cfg(not(feature = "log"))]
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+/// Discards a message, still type-checking its level and format arguments.
+#[cfg(not(feature = "log"))]
+macro_rules! discard {
+    ($level:expr, $($arg:tt)+) => {{
+        let _: $crate::Level = $level;
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+#[cfg(not(feature = "log"))]
+pub(crate) use discard as log;
@@ -0,0 +1,699 @@
+//! Helpers measuring the elapsed time of closures.
+//!
+//! This module provides the `time()` and `bench()` functions, timing a closure once or over
+//! a number of iterations and logging the result, the `bench_stable()` function, discarding
+//! the outlying iterations from the statistics, and the `profile_loop!` macro, timing a loop
+//! body written inline. The `profile_retry()` function times each attempt of a fallible
+//! operation retried until it succeeds, and the `time_err()` function attaches the elapsed
+//! time to the error of a failed operation as a `TimedError`. The `try_time_iter()` function
+//! times each item of an iterator of `Result`s, stopping at the first error.
+//!
+//! The value returned by the measured closure is passed through `black_box()` before the
+//! timer is read. Without it, the compiler is free to delete work whose result is never
+//! used, or to move it after the measurement, giving suspiciously fast timings.
+
+use std::time::Duration;
+
+use crate::logging::Level;
+use crate::profiler::log_gated;
+use crate::TimeLapse;
+
+/// An identity function hinting the compiler to assume the value is used in arbitrary ways.
+/// This prevents the computation of the value from being optimized away.
+/// Re-exported from `std::hint::black_box`, see its documentation for details.
+pub use std::hint::black_box;
+
+/// Times a single call to `f`, logs the elapsed time at the info level with the given name,
+/// and returns the closure's result.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::time;
+///
+/// let sum = time("sum", || (0..1000u64).sum::<u64>());
+/// assert_eq!(sum, 499500);
+/// ```
+pub fn time<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let timer = TimeLapse::new();
+    let result = black_box(f());
+    timer.log(name, Level::Info);
+    result
+}
+
+/// Times a single call to a fallible `f`, logs the elapsed time at the info level with the
+/// given name, whether it succeeded or not, and returns its result unchanged, e.g. to apply
+/// `?` to it. See `try_time_with_level()` to choose the level.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::try_time;
+///
+/// fn load(text: &str) -> Result<u32, std::num::ParseIntError> {
+///     let value = try_time("parse", || text.parse::<u32>())?;
+///     Ok(value + 1)
+/// }
+///
+/// assert_eq!(load("41"), Ok(42));
+/// assert!(load("forty-one").is_err());
+/// ```
+pub fn try_time<T, E>(name: &str, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    try_time_with_level(name, Level::Info, f)
+}
+
+/// Times a single call to a fallible `f` like `try_time()`, logging at the given level.
+pub fn try_time_with_level<T, E>(
+    name: &str,
+    level: Level,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let timer = TimeLapse::new();
+    let result = black_box(f());
+    timer.log(name, level);
+    result
+}
+
+/// Calls a fallible `op` once, timing it, and returns its result, an error being wrapped in a
+/// `TimedError` carrying the given name and the time spent before failing. Nothing is logged,
+/// the error being meant to be reported upstream.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::time_err;
+///
+/// let result = time_err("parse", || "forty-two".parse::<u32>());
+/// let error = result.unwrap_err();
+/// assert_eq!(error.name(), "parse");
+/// // "parse failed after 1.2µs: invalid digit found in string"
+/// println!("{}", error);
+///
+/// assert_eq!(time_err("parse", || "42".parse::<u32>()).unwrap(), 42);
+/// ```
+pub fn time_err<T, E>(
+    name: impl Into<String>,
+    op: impl FnOnce() -> Result<T, E>,
+) -> Result<T, TimedError<E>> {
+    let timer = TimeLapse::new();
+    black_box(op()).map_err(|error| TimedError {
+        error,
+        name: name.into(),
+        elapsed: timer.elapsed(),
+    })
+}
+
+/// The `TimedError` struct wraps the error of an operation timed by `time_err()`, with the
+/// name of the operation and the time elapsed before it failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimedError<E> {
+    error: E,
+    name: String,
+    elapsed: Duration,
+}
+
+impl<E> TimedError<E> {
+    /// Returns the name of the operation.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the time elapsed before the operation failed.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the error of the operation.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the error of the operation, dropping the elapsed time.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for TimedError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} failed after {:?}: {}",
+            self.name, self.elapsed, self.error
+        )
+    }
+}
+
+/// The source of a `TimedError` is the source of the wrapped error, which is already part of
+/// its `Display` message.
+impl<E: std::error::Error> std::error::Error for TimedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Collects the values of an iterator of `Result`s, timing the production of each item, and
+/// stops at the first error, returned as an `IterError` carrying its index and the time
+/// elapsed since the start of the iteration. The remaining items are not produced.
+///
+/// The time of each item, i.e. of the call to `next()` producing it, is logged at the debug
+/// level with its index, then the total time is logged at the info level with the number of
+/// items, or with the index of the failed item. An empty iterator gives an empty `Vec`, and
+/// logs a total of zero items.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::measure::try_time_iter;
+///
+/// let lines = ["1", "2", "three", "4"];
+/// let error = try_time_iter("validate", lines.iter().map(|line| line.parse::<u32>()))
+///     .unwrap_err();
+/// assert_eq!(error.index(), 2);
+///
+/// let values = try_time_iter("validate", lines[..2].iter().map(|line| line.parse::<u32>()));
+/// assert_eq!(values, Ok(vec![1, 2]));
+/// ```
+pub fn try_time_iter<T, E>(
+    name: &str,
+    items: impl IntoIterator<Item = Result<T, E>>,
+) -> Result<Vec<T>, IterError<E>> {
+    let total = TimeLapse::new();
+    let mut items = items.into_iter();
+    let mut values = Vec::new();
+    loop {
+        let timer = TimeLapse::new();
+        let Some(item) = black_box(items.next()) else {
+            break;
+        };
+        let index = values.len();
+        let elapsed = timer.elapsed();
+        log_gated(name, elapsed, Level::Debug, || {
+            format!(
+                "TimeLapse {} - item {}, elapsed time: {:?}",
+                name, index, elapsed
+            )
+        });
+        match item {
+            Ok(value) => values.push(value),
+            Err(error) => {
+                let elapsed = total.elapsed();
+                log_gated(name, elapsed, Level::Info, || {
+                    format!(
+                        "TimeLapse {} - item {} failed, total time: {:?}",
+                        name, index, elapsed
+                    )
+                });
+                return Err(IterError {
+                    error,
+                    index,
+                    elapsed,
+                });
+            }
+        }
+    }
+    let elapsed = total.elapsed();
+    log_gated(name, elapsed, Level::Info, || {
+        format!(
+            "TimeLapse {} - {} items, total time: {:?}",
+            name,
+            values.len(),
+            elapsed
+        )
+    });
+    Ok(values)
+}
+
+/// The `IterError` struct wraps the first error of an iterator timed by `try_time_iter()`,
+/// with the index of its item and the time elapsed since the start of the iteration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IterError<E> {
+    error: E,
+    index: usize,
+    elapsed: Duration,
+}
+
+impl<E> IterError<E> {
+    /// Returns the index of the failed item, from zero.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the time elapsed from the start of the iteration to the failure, included.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the error of the failed item.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the error of the failed item, dropping its index and the elapsed time.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for IterError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "item {} failed after {:?}: {}",
+            self.index, self.elapsed, self.error
+        )
+    }
+}
+
+/// The source of an `IterError` is the source of the wrapped error, which is already part of
+/// its `Display` message.
+impl<E: std::error::Error> std::error::Error for IterError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Calls `f` the given number of times, logs the average time per iteration at the info level
+/// with the given name, and returns that average. Zero iterations give a zero average.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::bench;
+///
+/// let average = bench("sum", 100, || (0..1000u64).sum::<u64>());
+/// assert!(average.as_secs() < 1);
+/// ```
+pub fn bench<T>(name: &str, iterations: u32, mut f: impl FnMut() -> T) -> Duration {
+    let timer = TimeLapse::new();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    let average = timer
+        .elapsed()
+        .checked_div(iterations)
+        .unwrap_or(Duration::ZERO);
+    log_gated(name, average, Level::Info, || {
+        format!(
+            "TimeLapse {} - {} iterations, average time: {:?}",
+            name, iterations, average
+        )
+    });
+    average
+}
+
+/// Calls `op` the given number of times, timing each iteration, logs at the info level with
+/// the given name the median, mean and standard deviation of the iteration times without the
+/// outliers, and the number of outliers discarded, and returns that median.
+///
+/// An iteration time is an outlier when it is more than 3 MADs (median absolute deviations)
+/// away from the median of all the times, e.g. when the thread was preempted. When more than
+/// half the times are equal, the MAD is zero and no time is discarded. Zero iterations give a
+/// zero median.
+///
+/// Each iteration is timed individually, so the times include the overhead of reading the
+/// clock, which matters for operations of a few nanoseconds: `bench()` is better suited to
+/// them.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::bench_stable;
+///
+/// let median = bench_stable("sum", 100, || (0..1000u64).sum::<u64>());
+/// assert!(median.as_secs() < 1);
+/// ```
+pub fn bench_stable<T>(name: &str, iterations: u32, mut op: impl FnMut() -> T) -> Duration {
+    let mut samples: Vec<Duration> = (0..iterations)
+        .map(|_| {
+            let timer = TimeLapse::new();
+            black_box(op());
+            timer.elapsed()
+        })
+        .collect();
+    let outliers = discard_outliers(&mut samples);
+    let median = median(&samples);
+    let nanos: Vec<f64> = samples
+        .iter()
+        .map(|sample| sample.as_nanos() as f64)
+        .collect();
+    let mean = nanos.iter().sum::<f64>() / nanos.len().max(1) as f64;
+    let variance =
+        nanos.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / nanos.len().max(1) as f64;
+    log_gated(name, median, Level::Info, || {
+        format!(
+            "TimeLapse {} - {} iterations, {} outliers discarded, median: {:?}, mean: {:?}, std dev: {:?}",
+            name,
+            iterations,
+            outliers,
+            median,
+            Duration::from_nanos(mean as u64),
+            Duration::from_nanos(variance.sqrt() as u64)
+        )
+    });
+    median
+}
+
+/// Sorts the samples and removes the ones more than 3 MADs away from their median, returning
+/// the number of samples removed.
+fn discard_outliers(samples: &mut Vec<Duration>) -> usize {
+    samples.sort_unstable();
+    let center = median(samples);
+    let mut deviations: Vec<Duration> = samples.iter().map(|x| x.abs_diff(center)).collect();
+    deviations.sort_unstable();
+    let limit = median(&deviations) * 3;
+    if limit.is_zero() {
+        return 0;
+    }
+    let count = samples.len();
+    samples.retain(|x| x.abs_diff(center) <= limit);
+    count - samples.len()
+}
+
+/// Returns the median of sorted durations, the mean of the two middle ones for an even count,
+/// and zero if there are none.
+fn median(sorted: &[Duration]) -> Duration {
+    match sorted.len() {
+        0 => Duration::ZERO,
+        len if len % 2 == 1 => sorted[len / 2],
+        len => (sorted[len / 2 - 1] + sorted[len / 2]) / 2,
+    }
+}
+
+/// Calls `op` with the attempt number, from 1, until it succeeds or `max_attempts` attempts
+/// were made, and returns the result of the last attempt. At least one attempt is made, even
+/// with `max_attempts` zero. Each attempt is logged at the info level with its number and
+/// elapsed time, then the number of attempts and the total time.
+///
+/// Only `op` is timed: nothing is done between the attempts. A backoff sleep made in `op`, e.g.
+/// before retrying, is part of the time of its attempt; `profile_retry_with_backoff()` sleeps
+/// between the attempts instead, outside of their times.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::measure::profile_retry;
+///
+/// let result: Result<u32, &str> = profile_retry("connect", 5, |attempt| {
+///     if attempt < 3 {
+///         Err("connection refused")
+///     } else {
+///         Ok(attempt)
+///     }
+/// });
+/// assert_eq!(result, Ok(3));
+/// ```
+pub fn profile_retry<T, E>(
+    name: &str,
+    max_attempts: u32,
+    op: impl FnMut(u32) -> Result<T, E>,
+) -> Result<T, E> {
+    profile_retry_with_backoff(name, max_attempts, |_| Duration::ZERO, op)
+}
+
+/// Calls `op` like `profile_retry()`, sleeping between a failed attempt and the next one for
+/// the duration given by `backoff` with the number of the failed attempt. The sleeps are not
+/// part of the times of the attempts, but are part of the total time.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::measure::profile_retry_with_backoff;
+///
+/// let backoff = |attempt| Duration::from_millis(1) * 2u32.pow(attempt - 1);
+/// let result: Result<(), &str> =
+///     profile_retry_with_backoff("fetch", 3, backoff, |_| Err("timeout"));
+/// assert_eq!(result, Err("timeout"));
+/// ```
+pub fn profile_retry_with_backoff<T, E>(
+    name: &str,
+    max_attempts: u32,
+    mut backoff: impl FnMut(u32) -> Duration,
+    mut op: impl FnMut(u32) -> Result<T, E>,
+) -> Result<T, E> {
+    let total = TimeLapse::new();
+    let mut attempt = 1;
+    let result = loop {
+        let timer = TimeLapse::new();
+        let result = op(attempt);
+        let elapsed = timer.elapsed();
+        let outcome = if result.is_ok() {
+            "succeeded"
+        } else {
+            "failed"
+        };
+        log_gated(name, elapsed, Level::Info, || {
+            format!(
+                "TimeLapse {} - attempt {} {}, elapsed time: {:?}",
+                name, attempt, outcome, elapsed
+            )
+        });
+        if result.is_ok() || attempt >= max_attempts {
+            break result;
+        }
+        std::thread::sleep(backoff(attempt));
+        attempt += 1;
+    };
+    let elapsed = total.elapsed();
+    log_gated(name, elapsed, Level::Info, || {
+        format!(
+            "TimeLapse {} - {} attempts, total time: {:?}",
+            name, attempt, elapsed
+        )
+    });
+    result
+}
+
+/// The `profile_loop!` macro runs a block the given number of times, logs at the info level
+/// the total elapsed time and the average time per iteration with the profiler's name, and
+/// evaluates to that average. Zero iterations, or a negative count, give a zero average.
+///
+/// The loop index, counting from zero, can be named before the block with `index =>`. The
+/// value of the block is passed through `black_box()`, so that it is not optimized away.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::profile_loop;
+///
+/// let items = [3u64, 1, 4, 1, 5];
+/// let average = profile_loop!(sorting, 100, {
+///     let mut sorted = items;
+///     sorted.sort();
+///     sorted
+/// });
+/// assert!(average.as_secs() < 1);
+///
+/// let mut sum = 0;
+/// profile_loop!(summing, items.len(), i => {
+///     sum += items[i];
+/// });
+/// assert_eq!(sum, 14);
+/// ```
+#[macro_export]
+macro_rules! profile_loop {
+    ($name:ident, $count:expr, $index:ident => $body:block) => {{
+        let count = $count;
+        let profiler = $crate::TimeLapse::new();
+        for $index in 0..count {
+            $crate::black_box($body);
+        }
+        // A negative count runs no iteration.
+        let iterations = <u64 as ::std::convert::TryFrom<_>>::try_from(count).unwrap_or(0);
+        $crate::measure::log_loop(stringify!($name), iterations, profiler.elapsed())
+    }};
+    ($name:ident, $count:expr, $body:block) => {
+        $crate::profile_loop!($name, $count, _index => $body)
+    };
+}
+
+/// Logs the total and average times of `profile_loop!`, and returns the average.
+#[doc(hidden)]
+pub fn log_loop(name: &str, iterations: u64, total: Duration) -> Duration {
+    let average = loop_average(total, iterations);
+    log_gated(name, total, Level::Info, || {
+        format!(
+            "TimeLapse {} - {} iterations, total time: {:?}, average time: {:?}",
+            name, iterations, total, average
+        )
+    });
+    average
+}
+
+/// Returns the average time of an iteration, zero for zero iterations.
+fn loop_average(total: Duration, iterations: u64) -> Duration {
+    match total.as_nanos().checked_div(u128::from(iterations)) {
+        Some(nanos) => Duration::from_nanos(nanos as u64),
+        None => Duration::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time() {
+        let result = time("test_time", || {
+            std::thread::sleep(Duration::from_millis(10));
+            42
+        });
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn test_try_time() {
+        let ok: Result<u32, String> = try_time("test_try_time", || Ok(42));
+        assert_eq!(ok, Ok(42));
+        let err: Result<u32, String> =
+            try_time_with_level("test_try_time", Level::Debug, || Err("failed".to_string()));
+        assert_eq!(err, Err("failed".to_string()));
+
+        let question_mark = || -> Result<u32, std::num::ParseIntError> {
+            let value = try_time("test_try_time", || "x".parse::<u32>())?;
+            Ok(value)
+        };
+        assert!(question_mark().is_err());
+    }
+
+    #[test]
+    fn test_time_err() {
+        let ok: Result<u32, TimedError<String>> = time_err("test_time_err", || Ok(42));
+        assert_eq!(ok, Ok(42));
+
+        let error = time_err("test_time_err", || -> Result<(), String> {
+            std::thread::sleep(Duration::from_millis(2));
+            Err("failed".to_string())
+        })
+        .unwrap_err();
+        assert_eq!(error.name(), "test_time_err");
+        assert!(error.elapsed() >= Duration::from_millis(2));
+        assert_eq!(error.error(), "failed");
+        let message = error.to_string();
+        assert!(message.starts_with("test_time_err failed after "));
+        assert!(message.ends_with("ms: failed"));
+        assert_eq!(error.into_inner(), "failed");
+    }
+
+    #[test]
+    fn test_try_time_iter() {
+        let mut produced = 0;
+        let items = (0..5).map(|i| {
+            produced += 1;
+            std::thread::sleep(Duration::from_millis(1));
+            if i == 2 {
+                Err(format!("invalid {}", i))
+            } else {
+                Ok(i)
+            }
+        });
+        let error = try_time_iter("test_try_time_iter", items).unwrap_err();
+        assert_eq!(produced, 3);
+        assert_eq!(error.index(), 2);
+        assert!(error.elapsed() >= Duration::from_millis(3));
+        assert!(error.to_string().starts_with("item 2 failed after "));
+        assert_eq!(error.into_inner(), "invalid 2");
+
+        let values = try_time_iter("test_try_time_iter", [Ok(1), Ok(2)]);
+        assert_eq!(values, Ok::<_, IterError<()>>(vec![1, 2]));
+        let empty = try_time_iter("test_try_time_iter", Vec::<Result<u32, ()>>::new());
+        assert_eq!(empty, Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_bench() {
+        let mut calls = 0;
+        let average = bench("test_bench", 5, || {
+            calls += 1;
+            std::thread::sleep(Duration::from_millis(2));
+        });
+        assert_eq!(calls, 5);
+        assert!(average >= Duration::from_millis(2));
+        assert_eq!(bench("test_bench", 0, || ()), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_bench_stable() {
+        let mut calls = 0;
+        let median = bench_stable("test_bench_stable", 5, || {
+            calls += 1;
+            std::thread::sleep(Duration::from_millis(2));
+        });
+        assert_eq!(calls, 5);
+        assert!(median >= Duration::from_millis(2));
+        assert_eq!(bench_stable("test_bench_stable", 0, || ()), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_discard_outliers() {
+        let ms = Duration::from_millis;
+        let mut samples = vec![ms(10), ms(11), ms(500), ms(9), ms(10), ms(12), ms(1)];
+        assert_eq!(discard_outliers(&mut samples), 2);
+        assert_eq!(samples, [ms(9), ms(10), ms(10), ms(11), ms(12)]);
+        assert_eq!(median(&samples), ms(10));
+
+        let mut equal = vec![ms(5), ms(5), ms(5), ms(90)];
+        assert_eq!(discard_outliers(&mut equal), 0);
+        assert_eq!(median(&equal), ms(5));
+        assert_eq!(median(&[ms(2), ms(4)]), ms(3));
+    }
+
+    #[test]
+    fn test_profile_retry() {
+        let mut attempts = Vec::new();
+        let result: Result<&str, u32> = profile_retry("test_profile_retry", 5, |attempt| {
+            attempts.push(attempt);
+            if attempt == 3 {
+                Ok("done")
+            } else {
+                Err(attempt)
+            }
+        });
+        assert_eq!(result, Ok("done"));
+        assert_eq!(attempts, [1, 2, 3]);
+
+        let mut calls = 0;
+        let result: Result<(), u32> = profile_retry("test_profile_retry", 0, |attempt| {
+            calls += 1;
+            Err(attempt)
+        });
+        assert_eq!((result, calls), (Err(1), 1));
+
+        let mut backoffs = Vec::new();
+        let total = TimeLapse::new();
+        let result: Result<(), u32> = profile_retry_with_backoff(
+            "test_profile_retry",
+            3,
+            |attempt| {
+                backoffs.push(attempt);
+                Duration::from_millis(5)
+            },
+            Err,
+        );
+        assert_eq!(result, Err(3));
+        assert_eq!(backoffs, [1, 2]);
+        assert!(total.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_profile_loop() {
+        let mut indexes = Vec::new();
+        crate::profile_loop!(test_profile_loop, 4, i => {
+            indexes.push(i);
+        });
+        assert_eq!(indexes, [0, 1, 2, 3]);
+
+        let mut calls = 0;
+        let average = crate::profile_loop!(test_profile_loop, 3u8, {
+            calls += 1;
+        });
+        assert_eq!(calls, 3);
+        assert!(average < Duration::from_secs(1));
+        assert_eq!(
+            crate::profile_loop!(test_profile_loop, 0, {}),
+            Duration::ZERO
+        );
+        assert_eq!(
+            crate::profile_loop!(test_profile_loop, -3i64, {}),
+            Duration::ZERO
+        );
+
+        assert_eq!(
+            loop_average(Duration::from_millis(10), 4),
+            Duration::from_micros(2_500)
+        );
+        assert_eq!(loop_average(Duration::from_millis(10), 0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,320 @@
+//! Streaming of the reported measurements to a collector over a socket.
+//!
+//! This module, available with the `net` feature, provides the `SocketReporter` struct, a
+//! `Reporter` sending each report as a line of JSON to a TCP endpoint, or to a Unix socket on
+//! Unix platforms, e.g. to a central collector profiling live processes:
+//!
+//! ```text
+//! {"name": "db::query", "nanos": 1250000, "tags": {"table": "users"}}
+//! ```
+//!
+//! Like `AsyncReporter`, it queues the reports in a bounded channel, and a background thread
+//! writes them to the socket, so the measured code never waits on the network. The connection
+//! is made on the first report, and made again after a failure, at most once every
+//! `RECONNECT_INTERVAL`. The reports that cannot be sent, the queue being full or the collector
+//! unreachable, are dropped and counted by `dropped()`.
+
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::json;
+use crate::reporter::Reporter;
+
+/// The minimum time between two connection attempts of a `SocketReporter`.
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The timeout of the connections and writes of a `SocketReporter`.
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The address of the collector of a `SocketReporter`.
+#[derive(Clone, Debug)]
+enum Address {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl Address {
+    /// Connects to the collector.
+    fn connect(&self) -> std::io::Result<Box<dyn Write + Send>> {
+        match self {
+            Address::Tcp(address) => {
+                let mut last_error = None;
+                for address in address.to_socket_addrs()? {
+                    match TcpStream::connect_timeout(&address, SOCKET_TIMEOUT) {
+                        Ok(stream) => {
+                            stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+                            return Ok(Box::new(stream));
+                        }
+                        Err(error) => last_error = Some(error),
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "no address to connect to")
+                }))
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                let stream = std::os::unix::net::UnixStream::connect(path)?;
+                stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+/// A message to the background thread of a `SocketReporter`.
+enum Message {
+    Report {
+        name: String,
+        elapsed: Duration,
+        tags: Vec<(String, String)>,
+    },
+    Flush(mpsc::Sender<()>),
+}
+
+/// The `SocketReporter` struct sends the reports, as lines of JSON, to a collector listening
+/// on a socket, from a background thread.
+///
+/// # Usage
+/// ```rust,no_run
+/// use timelapse::net::SocketReporter;
+/// use timelapse::reporter::{self, set_reporter};
+/// use timelapse::{profile_end, profile_start, TimeLapse};
+///
+/// set_reporter(SocketReporter::tcp("collector.local:7070", 1024));
+///
+/// profile_start!(request);
+/// // ... work ...
+/// profile_end!(request); // only queues the report
+///
+/// reporter::flush(); // at shutdown
+/// ```
+pub struct SocketReporter {
+    sender: Option<SyncSender<Message>>,
+    thread: Option<JoinHandle<()>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl SocketReporter {
+    /// Creates a new `SocketReporter` sending to the TCP endpoint at `address` (e.g.
+    /// `127.0.0.1:7070` or `collector.local:7070`), queuing at most `capacity` reports.
+    pub fn tcp(address: impl Into<String>, capacity: usize) -> Self {
+        Self::start(Address::Tcp(address.into()), capacity)
+    }
+
+    /// Creates a new `SocketReporter` sending to the Unix socket at `path`, queuing at most
+    /// `capacity` reports.
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<std::path::PathBuf>, capacity: usize) -> Self {
+        Self::start(Address::Unix(path.into()), capacity)
+    }
+
+    /// Starts the background thread sending to `address`.
+    fn start(address: Address, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let thread = {
+            let dropped = Arc::clone(&dropped);
+            std::thread::spawn(move || send_all(&address, receiver, &dropped))
+        };
+        SocketReporter {
+            sender: Some(sender),
+            thread: Some(thread),
+            dropped,
+        }
+    }
+
+    /// Returns the number of reports dropped because the queue was full or because they could
+    /// not be sent to the collector.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Sends the reports received to the collector, until the channel is closed.
+fn send_all(address: &Address, receiver: Receiver<Message>, dropped: &AtomicU64) {
+    let mut connection: Option<Box<dyn Write + Send>> = None;
+    let mut last_attempt: Option<Instant> = None;
+    for message in receiver {
+        match message {
+            Message::Report {
+                name,
+                elapsed,
+                tags,
+            } => {
+                let retry = last_attempt.is_none_or(|last| last.elapsed() >= RECONNECT_INTERVAL);
+                if connection.is_none() && retry {
+                    last_attempt = Some(Instant::now());
+                    connection = address.connect().ok();
+                }
+                let line = to_json_line(&name, elapsed, &tags);
+                let sent = connection
+                    .as_mut()
+                    .is_some_and(|stream| stream.write_all(line.as_bytes()).is_ok());
+                if !sent {
+                    connection = None;
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Message::Flush(done) => {
+                if let Some(stream) = connection.as_mut() {
+                    if stream.flush().is_err() {
+                        connection = None;
+                    }
+                }
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Returns a report as a line of JSON.
+fn to_json_line(name: &str, elapsed: Duration, tags: &[(String, String)]) -> String {
+    let mut line = String::from("{\"name\": ");
+    json::write_string(&mut line, name);
+    line.push_str(&format!(", \"nanos\": {}", elapsed.as_nanos()));
+    if !tags.is_empty() {
+        line.push_str(", \"tags\": {");
+        for (index, (key, value)) in tags.iter().enumerate() {
+            if index > 0 {
+                line.push_str(", ");
+            }
+            json::write_string(&mut line, key);
+            line.push_str(": ");
+            json::write_string(&mut line, value);
+        }
+        line.push('}');
+    }
+    line.push_str("}\n");
+    line
+}
+
+impl Reporter for SocketReporter {
+    fn report(&self, name: &str, elapsed: Duration) {
+        self.report_tagged(name, elapsed, &[]);
+    }
+
+    fn report_tagged(&self, name: &str, elapsed: Duration, tags: &[(String, String)]) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let message = Message::Report {
+            name: name.to_string(),
+            elapsed,
+            tags: tags.to_vec(),
+        };
+        if let Err(TrySendError::Full(_)) = sender.try_send(message) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let (done, wait) = mpsc::channel();
+        if sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+impl Drop for SocketReporter {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread once the queued reports are sent.
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_to_json_line() {
+        assert_eq!(
+            to_json_line("db::query", Duration::from_micros(1_250), &[]),
+            "{\"name\": \"db::query\", \"nanos\": 1250000}\n"
+        );
+        let tags = [
+            ("table".to_string(), "users".to_string()),
+            ("op".to_string(), "say \"hi\"".to_string()),
+        ];
+        assert_eq!(
+            to_json_line("q", Duration::from_nanos(5), &tags),
+            "{\"name\": \"q\", \"nanos\": 5, \"tags\": {\"table\": \"users\", \"op\": \"say \\\"hi\\\"\"}}\n"
+        );
+    }
+
+    #[test]
+    fn test_socket_reporter_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let reporter = SocketReporter::tcp(listener.local_addr().unwrap().to_string(), 16);
+        reporter.report("first", Duration::from_nanos(1));
+        reporter.report_tagged(
+            "second",
+            Duration::from_nanos(2),
+            &[("k".to_string(), "v".to_string())],
+        );
+        reporter.flush();
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "{\"name\": \"first\", \"nanos\": 1}"
+        );
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "{\"name\": \"second\", \"nanos\": 2, \"tags\": {\"k\": \"v\"}}"
+        );
+        assert_eq!(reporter.dropped(), 0);
+    }
+
+    #[test]
+    fn test_socket_reporter_unreachable() {
+        let address = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        let reporter = SocketReporter::tcp(address, 16);
+        for _ in 0..3 {
+            reporter.report("lost", Duration::from_nanos(1));
+        }
+        reporter.flush();
+        assert_eq!(reporter.dropped(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_reporter_unix() {
+        let path = std::env::temp_dir().join(format!(
+            "timelapse-test-socket-reporter-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let reporter = SocketReporter::unix(&path, 16);
+        reporter.report("unix", Duration::from_nanos(7));
+        drop(reporter);
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "{\"name\": \"unix\", \"nanos\": 7}"
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -6,8 +6,15 @@
 //! See the tesing examples at the end of this source file for usage.
 
 #![allow(unused)]
-use log::{log, Level};
-use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::clock::Clock;
+use crate::logging::{log, Level};
 
 /// The `profile_start!` macro initializes a `TimeLapse` instance to start profiling.
 /// It takes an identifier as an argument, which will be instantiated and used to reference the profiler instance.
@@ -31,8 +38,10 @@ macro_rules! profile_start {
     };
 }
 
-/// The `profile_end!` macro logs at the info level the elapsed time of the profiling instance created by `profile_start!`.
+/// The `profile_end!` macro reports the elapsed time of the profiling instance created by `profile_start!`
+/// to the global reporter (see `reporter::set_reporter()`), which logs it at the info level by default.
 /// These macros are useful for quick profiling without needing to manually create and manage `TimeLapse` instances.
+/// An optional group can be given, the profiler being then reported as `group::name`.
 /// # Usage
 /// ```rust
 /// use std::time::Duration;
@@ -44,11 +53,15 @@ macro_rules! profile_start {
 /// assert!(my_profiler.elapsed().as_millis() >= 100);
 ///
 /// profile_end!(my_profiler);
+/// profile_end!(my_profiler, group: "db");
 /// ```
 #[macro_export]
 macro_rules! profile_end {
     ($name:ident) => {
-        $name.log(stringify!($name), log::Level::Info);
+        $name.report(stringify!($name));
+    };
+    ($name:ident, group: $group:expr) => {
+        $name.report_in($group, stringify!($name));
     };
 }
 
@@ -63,12 +76,16 @@ macro_rules! profile_end {
 /// std::thread::sleep(Duration::from_millis(100));
 /// assert!(my_profiler.elapsed().as_millis() >= 100);
 /// profile_end_print!(my_profiler);
+/// profile_end_print!(my_profiler, group: "db");
 /// ```
 #[macro_export]
 macro_rules! profile_end_print {
     ($name:ident) => {
         $name.print(stringify!($name));
     };
+    ($name:ident, group: $group:expr) => {
+        $name.print_in($group, stringify!($name));
+    };
 }
 
 /// The `profile_end_log!` macro logs the elapsed time of the profiling instance created by `profile_start!`.
@@ -79,51 +96,958 @@ macro_rules! profile_end_print {
 /// ```rust
 /// use std::time::Duration;
 /// use timelapse::{TimeLapse, profile_start, profile_end_log};
-/// use log::Level;
+/// use timelapse::Level;
 ///
 /// profile_start!(my_profiler);
 /// std::thread::sleep(Duration::from_millis(100));
 /// assert!(my_profiler.elapsed().as_millis() >= 100);
 /// profile_end_log!(my_profiler, level: Level::Info);
+/// profile_end_log!(my_profiler, group: "db", level: Level::Debug);
 /// ```
 #[macro_export]
 macro_rules! profile_end_log {
     ($name:ident, level: $level:expr) => {
         $name.log(stringify!($name), $level);
     };
+    ($name:ident, group: $group:expr, level: $level:expr) => {
+        $name.log_in($group, stringify!($name), $level);
+    };
+}
+
+/// The `profile_match!` macro times each arm of a `match` expression, logging at the info level
+/// the elapsed time of the arm taken with its label. Each arm is written `pattern => label => expression`,
+/// guards being supported. The value of the `match` is preserved.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::profile_match;
+///
+/// enum Command {
+///     Get(u32),
+///     Put(u32, u32),
+///     Clear,
+/// }
+///
+/// let command = Command::Put(1, 2);
+/// let result = profile_match!(command, {
+///     Command::Get(key) => "get" => key,
+///     Command::Put(key, value) if key > 0 => "put" => key + value,
+///     Command::Put(_, value) => "put_zero" => value,
+///     Command::Clear => "clear" => 0,
+/// });
+/// assert_eq!(result, 3);
+/// ```
+#[macro_export]
+macro_rules! profile_match {
+    ($scrutinee:expr, { $($pat:pat $(if $guard:expr)? => $label:expr => $body:expr),+ $(,)? }) => {
+        match $scrutinee {
+            $(
+                $pat $(if $guard)? => {
+                    let profiler = $crate::TimeLapse::new();
+                    let result = $body;
+                    profiler.log($label, $crate::Level::Info);
+                    result
+                }
+            )+
+        }
+    };
+}
+
+/// The `profile_try!` macro times a `Result` expression, reports its elapsed time like
+/// `profile_end!`, asserts it completed within a time limit, and then applies `?` to the result.
+/// The elapsed time is reported on the error path too, before the error is returned.
+///
+/// # Panics
+/// Panics if the expression took longer than the limit.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::profile_try;
+///
+/// fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+///     let value = profile_try!(parsing, Duration::from_secs(1), input.parse::<u32>());
+///     Ok(value * 2)
+/// }
+///
+/// assert_eq!(parse("21"), Ok(42));
+/// assert!(parse("twenty-one").is_err());
+/// ```
+#[macro_export]
+macro_rules! profile_try {
+    ($name:ident, $limit:expr, $expr:expr) => {{
+        let $name = $crate::TimeLapse::new();
+        let result = $expr;
+        let elapsed = $name.elapsed();
+        $name.report(stringify!($name));
+        let limit = $limit;
+        assert!(
+            elapsed <= limit,
+            "{} took {:?}, over the limit of {:?}",
+            stringify!($name),
+            elapsed,
+            limit
+        );
+        result?
+    }};
 }
 
+/// The `profile_into!` macro times a block or an expression, stores its elapsed time into the
+/// `&mut Duration` given as destination, and evaluates to its value. Nothing is logged or
+/// reported, for callers aggregating the durations themselves. The profiler is available in
+/// the body under the given name, e.g. to read its elapsed time along the way.
+///
+/// The destination is left untouched if the body returns early, e.g. with `?`.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::profile_into;
+///
+/// let mut parsing = Duration::ZERO;
+/// let value: u32 = profile_into!(parser, &mut parsing, "42".parse().unwrap());
+/// assert_eq!(value, 42);
+///
+/// let mut total = Duration::ZERO;
+/// for input in ["1", "2"] {
+///     let mut elapsed = Duration::ZERO;
+///     profile_into!(parser, &mut elapsed, {
+///         let _ = input.parse::<u32>();
+///     });
+///     total += elapsed;
+/// }
+/// ```
+#[macro_export]
+macro_rules! profile_into {
+    ($name:ident, $dest:expr, $body:expr) => {{
+        let $name = $crate::TimeLapse::new();
+        let result = $body;
+        let dest: &mut ::std::time::Duration = $dest;
+        *dest = $name.elapsed();
+        result
+    }};
+}
+
+/// The `debug_profile!` macro times a block or an expression and reports its elapsed time
+/// under the given name like `profile_end!`, in debug builds only, and evaluates to its value.
+/// In release builds, `cfg!(debug_assertions)` being false, the timer is optimized out and
+/// only the body remains, with no feature flag to manage.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::debug_profile;
+///
+/// let sum: u64 = debug_profile!(summing, { (1..=100).sum() });
+/// assert_eq!(sum, 5050);
+/// ```
+#[macro_export]
+macro_rules! debug_profile {
+    ($name:ident, $body:expr) => {{
+        let profiler = if cfg!(debug_assertions) {
+            ::std::option::Option::Some($crate::TimeLapse::new())
+        } else {
+            ::std::option::Option::None
+        };
+        let result = $body;
+        if let ::std::option::Option::Some(profiler) = profiler {
+            profiler.report(stringify!($name));
+        }
+        result
+    }};
+}
+
+/// The `assert_faster!` macro asserts that a measurement is faster than another one, to check
+/// relative performance invariants without hardcoding absolute times. The measurements can be
+/// `TimeLapse`, `Stopwatch` or `Snapshot` values, or a `Duration` such as the average returned
+/// by `bench()`, which suits timing closures.
+///
+/// An optional tolerance accepts the first measurement being slower by up to that duration,
+/// which avoids flaky failures on near-ties.
+///
+/// # Panics
+/// Panics with both elapsed times if the first one is not less than the second one plus the
+/// tolerance.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::{assert_faster, bench};
+///
+/// let sum = bench("sum", 100, || (0..100u64).sum::<u64>());
+/// let sort = bench("sort", 100, || {
+///     let mut values: Vec<u64> = (0..10_000).rev().collect();
+///     values.sort();
+///     values
+/// });
+/// assert_faster!(sum, sort, tolerance: Duration::from_micros(10));
+/// ```
+#[macro_export]
+macro_rules! assert_faster {
+    ($a:expr, $b:expr $(,)?) => {
+        $crate::assert_faster!($a, $b, tolerance: ::std::time::Duration::ZERO)
+    };
+    ($a:expr, $b:expr, tolerance: $tolerance:expr $(,)?) => {
+        $crate::profiler::check_faster(
+            stringify!($a),
+            $crate::profiler::Measurement::measured(&$a),
+            stringify!($b),
+            $crate::profiler::Measurement::measured(&$b),
+            $tolerance,
+        )
+    };
+}
+
+/// The `profile_lines!` macro times each statement of a block individually, and reports its
+/// elapsed time like `profile_end!`, named after the text of the statement (e.g.
+/// `let rows = load(path)`).
+///
+/// The statements are expanded in place, so the variables bound by a `let` remain visible to
+/// the following statements and after the macro.
+///
+/// # Limitations
+/// - The statements are separated by `;`, block-like ones included (`for x in xs { ... };`),
+///   as a macro cannot tell where a statement without one ends.
+/// - The block cannot end with a tail expression: a last expression is timed as a statement
+///   and its value dropped.
+/// - The statements are not named after their source lines, as `line!()` in a macro gives
+///   the line of the invocation for all of them. Their text tells them apart.
+/// - A statement leaving the block (`return`, `break`, `?` on an error, a panic) is not
+///   reported.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::profile_lines;
+///
+/// profile_lines! {
+///     let values: Vec<u64> = (0..1_000).collect();
+///     let sum: u64 = values.iter().sum();
+///     assert_eq!(sum, 499_500);
+/// }
+/// assert_eq!(values.len(), 1_000);
+/// ```
+#[macro_export]
+macro_rules! profile_lines {
+    ($($stmt:stmt);* $(;)?) => {
+        $(
+            let profile_lines_timer = $crate::TimeLapse::new();
+            $stmt;
+            #[allow(unreachable_code)]
+            profile_lines_timer.report(stringify!($stmt));
+        )*
+    };
+}
+
+/// The `elapsed_ms!` macro expands to the elapsed time of a profiler in whole milliseconds,
+/// i.e. `profiler.elapsed().as_millis()`.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{elapsed_ms, TimeLapse};
+///
+/// let profiler = TimeLapse::new();
+/// println!("done in {}ms", elapsed_ms!(profiler));
+/// ```
+#[macro_export]
+macro_rules! elapsed_ms {
+    ($profiler:expr) => {
+        $profiler.elapsed().as_millis()
+    };
+}
+
+/// The `elapsed_fmt!` macro expands to the elapsed time of a profiler formatted for humans
+/// with `format::human()` (e.g. `12.35ms`), as a `String` to use in `format!` or `write!`.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{elapsed_fmt, TimeLapse};
+///
+/// let profiler = TimeLapse::new();
+/// let message = format!("parsing done in {}", elapsed_fmt!(profiler));
+/// ```
+#[macro_export]
+macro_rules! elapsed_fmt {
+    ($profiler:expr) => {
+        $crate::format::human($profiler.elapsed())
+    };
+}
+
+/// The `TimerState` enum is the lifecycle state of a `TimeLapse`, returned by
+/// `TimeLapse::state()`.
+///
+/// The transitions are:
+///
+/// | Method      | From `Running`         | From `Paused`          | From `Stopped`          |
+/// |-------------|------------------------|------------------------|-------------------------|
+/// | `pause()`   | `Paused`               | `Paused`, unchanged    | `Stopped`, unchanged    |
+/// | `resume()`  | `Running`, unchanged   | `Running`, continuing  | `Running`, from zero    |
+/// | `stop()`    | `Stopped`              | `Stopped`              | `Stopped`, unchanged    |
+/// | `start()`   | `Running`, from zero   | `Running`, from zero   | `Running`, from zero    |
+///
+/// A paused timer is meant to be resumed, its elapsed time continuing from where it was paused.
+/// A stopped timer holds a final measurement: resuming it starts a new measurement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerState {
+    /// The timer is counting.
+    Running,
+    /// The timer was paused with `pause()`, and can be resumed with `resume()`.
+    Paused,
+    /// The timer was stopped with `stop()`, or was created with `uninit()` and not started.
+    Stopped,
+}
+
+/// The callback of `TimeLapse::on_lap()`.
+type LapCallback = Box<dyn FnMut(&str, Duration) + Send + Sync>;
+
 /// The `TimeLapse` struct is used to measure elapsed time in Rust applications.
-/// It provides methods to start, reset, and log the elapsed time.
+/// It provides methods to start, stop, reset, and log the elapsed time.
 /// It can be used to profile code execution and is useful for performance analysis.
 /// It implements the `Display` and `Debug` traits for easy formatting and logging.
 pub struct TimeLapse {
-    start_time: Instant,
+    start_time: Option<Instant>,
+    start_wall: Option<SystemTime>,
+    stopped: Option<Duration>,
+    paused: bool,
+    carried: Duration,
+    total: Duration,
+    laps: Vec<(String, Duration)>,
+    lap_mark: Duration,
+    min_lap: Option<Duration>,
+    max_lap: Option<Duration>,
+    // The timer stays unwind safe: a callback interrupted by a panic has at worst missed a lap.
+    on_lap: Option<AssertUnwindSafe<LapCallback>>,
+    tick_mark: Duration,
+    ticks: VecDeque<Duration>,
+    frames: u64,
+    tags: Vec<(String, String)>,
+    clock: Option<Arc<dyn Clock>>,
+    #[cfg(feature = "alloc-count")]
+    alloc_mark: crate::allocs::AllocStats,
+    #[cfg(feature = "cpu-time")]
+    cpu_mark: Duration,
+    #[cfg(feature = "cpu-time")]
+    cpu_stopped: Option<Duration>,
 }
 
 impl TimeLapse {
     /// Creates a new `TimeLapse` instance, starting the timer immediately.
     pub fn new() -> Self {
+        let mut profiler = Self::uninit();
+        profiler.start();
+        profiler
+    }
+
+    /// Creates a new `TimeLapse` instance without starting the timer, and without reading the
+    /// clock. The timer is started later with `start()`, e.g. only on the rare path of interest.
+    /// Until then, `elapsed()` returns zero.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::uninit();
+    /// assert_eq!(profiler.elapsed(), Duration::ZERO);
+    /// profiler.start();
+    /// assert!(profiler.is_started());
+    /// ```
+    pub fn uninit() -> Self {
         TimeLapse {
-            start_time: Instant::now(),
+            start_time: None,
+            start_wall: None,
+            stopped: None,
+            paused: false,
+            carried: Duration::ZERO,
+            total: Duration::ZERO,
+            laps: Vec::new(),
+            lap_mark: Duration::ZERO,
+            min_lap: None,
+            max_lap: None,
+            on_lap: None,
+            tick_mark: Duration::ZERO,
+            ticks: VecDeque::new(),
+            frames: 0,
+            tags: Vec::new(),
+            clock: None,
+            #[cfg(feature = "alloc-count")]
+            alloc_mark: crate::allocs::AllocStats::default(),
+            #[cfg(feature = "cpu-time")]
+            cpu_mark: Duration::ZERO,
+            #[cfg(feature = "cpu-time")]
+            cpu_stopped: None,
+        }
+    }
+
+    /// Starts the timer from the current time. This is meant for timers created with
+    /// `uninit()`, starting an already started or stopped timer restarts its current
+    /// measurement from the current time.
+    pub fn start(&mut self) {
+        self.start_time = Some(self.now());
+        self.start_wall = Some(SystemTime::now());
+        self.stopped = None;
+        self.paused = false;
+        self.carried = Duration::ZERO;
+        self.lap_mark = Duration::ZERO;
+        self.tick_mark = Duration::ZERO;
+        #[cfg(feature = "alloc-count")]
+        {
+            self.alloc_mark = crate::allocs::current();
+        }
+        #[cfg(feature = "cpu-time")]
+        {
+            self.cpu_mark = crate::cpu::process_cpu_time();
+            self.cpu_stopped = None;
+        }
+    }
+
+    /// Reads the time from the given clock instead of the system clock, e.g. a
+    /// `testing::VirtualClock` in tests. A started timer is restarted from the current time of
+    /// the clock, a timer created with `uninit()` stays unstarted.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use timelapse::testing::VirtualClock;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let clock = Arc::new(VirtualClock::new());
+    /// let profiler = TimeLapse::new().with_clock(clock.clone());
+    /// clock.advance(Duration::from_millis(250));
+    /// assert_eq!(profiler.elapsed(), Duration::from_millis(250));
+    /// ```
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        if self.is_started() {
+            self.start();
+        }
+        self
+    }
+
+    /// Returns the clock injected with `with_clock()`, if any.
+    pub(crate) fn clock(&self) -> Option<Arc<dyn Clock>> {
+        self.clock.clone()
+    }
+
+    /// Returns the current time of the clock of the timer.
+    fn now(&self) -> Instant {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => Instant::now(),
         }
     }
 
+    /// Returns the time elapsed since an instant, on the clock of the timer.
+    fn since(&self, instant: Instant) -> Duration {
+        self.now().saturating_duration_since(instant)
+    }
+
+    /// Returns the allocation counters of the current thread when the timer was started.
+    #[cfg(feature = "alloc-count")]
+    pub(crate) fn alloc_mark(&self) -> crate::allocs::AllocStats {
+        self.alloc_mark
+    }
+
+    /// Returns the process CPU time when the timer was started, and the CPU time elapsed at
+    /// `stop()` if it is stopped.
+    #[cfg(feature = "cpu-time")]
+    pub(crate) fn cpu_marks(&self) -> (Duration, Option<Duration>) {
+        (self.cpu_mark, self.cpu_stopped)
+    }
+
+    /// Returns `true` if the timer has been started, i.e. it was not created with `uninit()` or
+    /// `start()` has been called since.
+    pub fn is_started(&self) -> bool {
+        self.start_time.is_some()
+    }
+
+    /// Returns the start time of the current measurement as nanoseconds since the Unix epoch,
+    /// e.g. to correlate profiler spans with the absolute timestamps of a distributed trace.
+    ///
+    /// The wall clock is read when the timer is started or reset, and not again: the start is
+    /// unaffected by pauses, by adjustments made to the wall clock since, and by the clock
+    /// injected with `with_clock()`, which only measures the elapsed time. Returns zero if the
+    /// timer is not started, or if the start is before the epoch.
+    pub fn start_epoch_nanos(&self) -> u128 {
+        self.start_wall
+            .and_then(|start| start.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since_epoch| since_epoch.as_nanos())
+    }
+
     /// Returns the elapsed time since the `TimeLapse` instance was created or reset.
+    /// Once the timer is stopped, the frozen elapsed time is returned instead.
+    /// A timer created with `uninit()` and not yet started returns zero.
     pub fn elapsed(&self) -> Duration {
-        self.start_time.elapsed()
+        match (self.stopped, self.start_time) {
+            (Some(elapsed), _) => elapsed,
+            (None, Some(start_time)) => self.carried + self.since(start_time),
+            (None, None) => Duration::ZERO,
+        }
     }
 
     /// Resets the timer, starting a new measurement from the current time.
+    /// A stopped timer is running again after a reset.
+    /// The total accumulated by `restart()`, the recorded laps and their minimum and maximum,
+    /// and the frame count of `tick()` are discarded. See `reset_segment()` to keep them.
     pub fn reset(&mut self) {
-        self.start_time = Instant::now();
+        self.start();
+        self.total = Duration::ZERO;
+        self.laps.clear();
+        self.min_lap = None;
+        self.max_lap = None;
+        self.ticks.clear();
+        self.frames = 0;
+    }
+
+    /// Resets only the current segment, starting it again from the current time, and keeps the
+    /// accumulated state. A stopped timer is running again after it.
+    ///
+    /// The state cleared by each kind of reset is:
+    ///
+    /// | Method            | Current segment      | `restart()` total | Laps    | Min/max lap |
+    /// |-------------------|----------------------|-------------------|---------|-------------|
+    /// | `reset_segment()` | discarded, restarted | kept              | kept    | kept        |
+    /// | `restart()`       | added, restarted     | kept              | kept    | kept        |
+    /// | `reset()`         | discarded, restarted | cleared           | cleared | cleared     |
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// profiler.lap("warmup");
+    /// profiler.reset_segment();
+    /// assert_eq!(profiler.laps().len(), 1);
+    /// assert!(profiler.max_lap().is_some());
+    /// ```
+    pub fn reset_segment(&mut self) {
+        self.start();
+    }
+
+    /// Ends the current segment, adds its elapsed time to the running total, and starts a
+    /// fresh segment from the current time. Returns the elapsed time of the ended segment.
+    ///
+    /// Unlike `reset()`, which discards everything measured so far, `restart()` keeps the
+    /// measured segments in the total returned by `grand_total()`. If the timer was stopped,
+    /// the frozen elapsed time is the segment added, so the time between `stop()` and
+    /// `restart()` is not counted.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// for _ in 0..3 {
+    ///     profiler.restart();
+    ///     // ... timed region ...
+    ///     profiler.stop();
+    ///     // ... untimed work ...
+    /// }
+    /// println!("Total time in region: {:?}", profiler.grand_total());
+    /// ```
+    pub fn restart(&mut self) -> Duration {
+        let segment = self.elapsed();
+        self.total += segment;
+        self.start();
+        segment
+    }
+
+    /// Returns the total of the segments ended by `restart()`, plus the elapsed time of the
+    /// current segment.
+    pub fn grand_total(&self) -> Duration {
+        self.total + self.elapsed()
+    }
+
+    /// Records a lap with the given label and returns its duration: the time elapsed since
+    /// the previous lap, or since the start of the timer for the first one.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// // ... parsing ...
+    /// profiler.lap("parse");
+    /// // ... building ...
+    /// profiler.lap("build");
+    /// assert_eq!(profiler.laps().len(), 2);
+    /// ```
+    pub fn lap(&mut self, label: &str) -> Duration {
+        let elapsed = self.elapsed();
+        let lap = elapsed.saturating_sub(self.lap_mark);
+        self.lap_mark = elapsed;
+        match &mut self.on_lap {
+            Some(callback) => (callback.0)(label, lap),
+            None => self.laps.push((label.to_string(), lap)),
+        }
+        self.min_lap = Some(self.min_lap.map_or(lap, |min| min.min(lap)));
+        self.max_lap = Some(self.max_lap.map_or(lap, |max| max.max(lap)));
+        lap
+    }
+
+    /// Streams the laps to a callback instead of keeping them: each `lap()` calls it
+    /// synchronously, on the calling thread, with the label and the duration of the lap, and
+    /// `laps()` stays empty, so that laps can be recorded without end in bounded memory. The
+    /// minimum and maximum laps are still tracked. The callback replaces any previous one and is
+    /// kept across resets.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// profiler.on_lap(|label, lap| println!("{}: {:?}", label, lap));
+    /// for _ in 0..1_000 {
+    ///     // ... handling a message ...
+    ///     profiler.lap("message");
+    /// }
+    /// assert!(profiler.laps().is_empty());
+    /// ```
+    pub fn on_lap(&mut self, callback: impl FnMut(&str, Duration) + Send + Sync + 'static) {
+        self.on_lap = Some(AssertUnwindSafe(Box::new(callback)));
+    }
+
+    /// Ends a frame of a loop and returns its duration: the time elapsed since the previous
+    /// tick, or since the start of the timer for the first one. The next frame starts from
+    /// now. Unlike `lap()`, nothing is kept but the frame count and the durations of the last
+    /// `FPS_WINDOW` frames, used by `fps()`, so it suits endless loops.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// for _ in 0..3 {
+    ///     // ... update and render ...
+    ///     let frame_time = profiler.tick();
+    ///     println!("frame time: {:?}, {:.1} fps", frame_time, profiler.fps().unwrap_or(0.0));
+    /// }
+    /// assert_eq!(profiler.frames(), 3);
+    /// ```
+    pub fn tick(&mut self) -> Duration {
+        let elapsed = self.elapsed();
+        let frame = elapsed.saturating_sub(self.tick_mark);
+        self.tick_mark = elapsed;
+        if self.ticks.len() == FPS_WINDOW {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(frame);
+        self.frames += 1;
+        frame
+    }
+
+    /// Returns the number of ticks since the timer was created or reset.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the frame rate over the last `FPS_WINDOW` ticks, in frames per second, or `None`
+    /// if there was no tick or no time elapsed over them.
+    pub fn fps(&self) -> Option<f64> {
+        let secs = self.ticks.iter().sum::<Duration>().as_secs_f64();
+        (secs > 0.0).then(|| self.ticks.len() as f64 / secs)
+    }
+
+    /// Returns the laps recorded since the timer was created or reset, in order. The laps
+    /// streamed to the callback of `on_lap()` are not kept.
+    pub fn laps(&self) -> &[(String, Duration)] {
+        &self.laps
+    }
+
+    /// Returns the shortest lap recorded since the timer was created or reset, if any.
+    pub fn min_lap(&self) -> Option<Duration> {
+        self.min_lap
+    }
+
+    /// Returns the longest lap recorded since the timer was created or reset, if any.
+    pub fn max_lap(&self) -> Option<Duration> {
+        self.max_lap
+    }
+
+    /// Attaches a key-value tag to the measurement, e.g. an endpoint or a method. The tags are
+    /// passed to the global reporter along with the elapsed time by `report()`, and are kept
+    /// across resets.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let profiler = TimeLapse::new()
+    ///     .with_tag("endpoint", "/users")
+    ///     .with_tag("method", "GET");
+    /// // ... handling the request ...
+    /// profiler.report("request"); // "TimeLapse request - Elapsed time: ... [endpoint=/users, method=GET]"
+    /// ```
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// Returns the tags attached to the measurement, in the order they were added.
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Returns `true` if the elapsed time is within `tolerance` of `expected`, on either side.
+    /// This is meant for timing assertions, sleeps and schedulers being never exact.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let profiler = TimeLapse::new();
+    /// std::thread::sleep(Duration::from_millis(100));
+    /// assert!(profiler.elapsed_approx(Duration::from_millis(100), Duration::from_millis(50)));
+    /// ```
+    pub fn elapsed_approx(&self, expected: Duration, tolerance: Duration) -> bool {
+        self.elapsed().abs_diff(expected) <= tolerance
+    }
+
+    /// Returns the elapsed time as a fraction of a budget, e.g. `0.75` when 75% of a frame
+    /// budget is used. Values over `1.0` indicate a budget overrun.
+    /// A zero budget gives an infinite fraction, or `NaN` if the elapsed time is also zero.
+    pub fn budget_fraction(&self, budget: Duration) -> f64 {
+        self.elapsed().as_secs_f64() / budget.as_secs_f64()
+    }
+
+    /// Returns `true` once the elapsed time reaches a budget, i.e. `elapsed() >= budget`.
+    /// This is meant for time-sliced loops.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let budget = Duration::from_millis(5);
+    /// let profiler = TimeLapse::new();
+    /// let mut processed = 0;
+    /// while !profiler.deadline_reached(budget) {
+    ///     processed += 1; // ... process an item ...
+    /// }
+    /// assert!(profiler.remaining(budget).is_zero());
+    /// ```
+    pub fn deadline_reached(&self, budget: Duration) -> bool {
+        self.elapsed() >= budget
+    }
+
+    /// Returns the time left before the elapsed time reaches a budget, zero once reached.
+    pub fn remaining(&self, budget: Duration) -> Duration {
+        budget.saturating_sub(self.elapsed())
+    }
+
+    /// Returns an estimate of the time left to complete a task, from the elapsed time and the
+    /// fraction of the task done so far (from 0.0 to 1.0): `elapsed / fraction_done - elapsed`.
+    ///
+    /// Returns `Duration::MAX` if nothing is done yet (`fraction_done` zero, negative or NaN),
+    /// as no estimate can be made, and zero once the task is done (`fraction_done` of 1.0 or
+    /// more).
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let profiler = TimeLapse::new();
+    /// let total = 1_000;
+    /// for done in 1..=total {
+    ///     // ... process an item ...
+    ///     if done % 100 == 0 {
+    ///         println!("ETA: {:?}", profiler.eta(done as f64 / total as f64));
+    ///     }
+    /// }
+    /// ```
+    pub fn eta(&self, fraction_done: f64) -> Duration {
+        if fraction_done.is_nan() || fraction_done <= 0.0 {
+            return Duration::MAX;
+        }
+        if fraction_done >= 1.0 {
+            return Duration::ZERO;
+        }
+        let elapsed = self.elapsed().as_secs_f64();
+        Duration::try_from_secs_f64(elapsed / fraction_done - elapsed).unwrap_or(Duration::MAX)
+    }
+
+    /// Stops the timer and returns the elapsed time.
+    /// Subsequent calls to `elapsed()` return this frozen value until `reset()` is called.
+    /// Stopping an already stopped timer keeps the first frozen value, and stopping a paused
+    /// timer keeps the value frozen by `pause()`. See `TimerState` for the transitions.
+    pub fn stop(&mut self) -> Duration {
+        self.paused = false;
+        self.freeze()
+    }
+
+    /// Pauses a running timer and returns the elapsed time, frozen until `resume()` is called.
+    /// Pausing a paused or stopped timer leaves it as is. See `TimerState` for the transitions.
+    pub fn pause(&mut self) -> Duration {
+        if self.is_running() {
+            self.paused = true;
+        }
+        self.freeze()
+    }
+
+    /// Freezes the elapsed time, keeping the value frozen already, if any.
+    fn freeze(&mut self) -> Duration {
+        let elapsed = self.elapsed();
+        self.stopped = Some(elapsed);
+        #[cfg(feature = "cpu-time")]
+        {
+            self.cpu_stopped = Some(self.cpu_elapsed());
+        }
+        elapsed
+    }
+
+    /// Resumes a paused timer: the elapsed time continues from its frozen value, the time spent
+    /// paused not being counted. The laps and the total of `restart()` are kept.
+    /// A stopped timer, or a timer not yet started, is started from zero, as with `start()`.
+    /// A running timer is left as is. See `TimerState` for the transitions.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// // ... timed work ...
+    /// let paused = profiler.pause();
+    /// std::thread::sleep(Duration::from_millis(10)); // not counted
+    /// profiler.resume();
+    /// // ... more timed work ...
+    /// assert!(profiler.elapsed() >= paused);
+    /// ```
+    pub fn resume(&mut self) {
+        match self.state() {
+            TimerState::Paused => {
+                self.start_time = Some(self.now());
+                self.carried = self.stopped.take().unwrap_or_default();
+                self.paused = false;
+                #[cfg(feature = "cpu-time")]
+                {
+                    let cpu_frozen = self.cpu_stopped.take().unwrap_or_default();
+                    self.cpu_mark = crate::cpu::process_cpu_time().saturating_sub(cpu_frozen);
+                }
+            }
+            TimerState::Stopped => self.start(),
+            TimerState::Running => (),
+        }
+    }
+
+    /// Sleeps for the given duration with the timer paused, so the sleep is not counted in the
+    /// elapsed time, e.g. for the deliberate sleeps of a polling loop. A running timer is
+    /// paused for the sleep and resumed after it; a paused or stopped timer is left as is.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// for _ in 0..3 {
+    ///     // ... poll ...
+    ///     profiler.sleep_timed(Duration::from_millis(10)); // not counted
+    /// }
+    /// assert!(profiler.elapsed() < Duration::from_millis(30));
+    /// ```
+    pub fn sleep_timed(&mut self, duration: Duration) {
+        let running = self.is_running();
+        if running {
+            self.pause();
+        }
+        std::thread::sleep(duration);
+        if running {
+            self.resume();
+        }
+    }
+
+    /// Returns the lifecycle state of the timer.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::profiler::TimerState;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let mut profiler = TimeLapse::new();
+    /// assert_eq!(profiler.state(), TimerState::Running);
+    /// profiler.pause();
+    /// assert_eq!(profiler.state(), TimerState::Paused);
+    /// profiler.stop();
+    /// assert_eq!(profiler.state(), TimerState::Stopped);
+    /// ```
+    pub fn state(&self) -> TimerState {
+        if self.paused {
+            TimerState::Paused
+        } else if self.is_started() && self.stopped.is_none() {
+            TimerState::Running
+        } else {
+            TimerState::Stopped
+        }
+    }
+
+    /// Returns `true` if the timer is counting: started and neither paused nor stopped.
+    pub fn is_running(&self) -> bool {
+        self.state() == TimerState::Running
+    }
+
+    /// Returns `true` if the timer is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns `true` if the timer has been stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.is_some() && !self.paused
+    }
+
+    /// Waits until the elapsed time reaches `target`, sleeping while far from it and spinning
+    /// for the last stretch, which is more precise than a single `thread::sleep()`.
+    /// The wait is capped to `target` plus a small margin, measured from the call, to avoid
+    /// hanging if the timer does not advance (e.g. a stopped timer).
+    /// Returns `true` if the target was reached, `false` if the cap was hit first.
+    pub fn wait_until(&self, target: Duration) -> bool {
+        self.wait_until_capped(target, target.saturating_add(WAIT_UNTIL_MARGIN))
+    }
+
+    /// Waits until the elapsed time reaches `target`, for at most `max_wait` from the call.
+    /// Returns `true` if the target was reached, `false` if the cap was hit first.
+    pub fn wait_until_capped(&self, target: Duration, max_wait: Duration) -> bool {
+        let started = Instant::now();
+        loop {
+            let elapsed = self.elapsed();
+            if elapsed >= target {
+                return true;
+            }
+            let waited = started.elapsed();
+            if waited >= max_wait {
+                return false;
+            }
+            let remaining = (target - elapsed).min(max_wait - waited);
+            if remaining > WAIT_UNTIL_SPIN {
+                std::thread::sleep(remaining - WAIT_UNTIL_SPIN);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
     }
 }
 
+/// The number of the last ticks over which `TimeLapse::fps()` computes the frame rate.
+pub const FPS_WINDOW: usize = 60;
+
+/// Margin added to the target of `TimeLapse::wait_until()` to cap the wait.
+const WAIT_UNTIL_MARGIN: Duration = Duration::from_millis(100);
+
+/// Remaining time under which `TimeLapse::wait_until_capped()` spins instead of sleeping.
+const WAIT_UNTIL_SPIN: Duration = Duration::from_millis(1);
+
 /// Implements the `Display` trait for the `TimeLapse` struct.
+/// The alternate flag (`{:#}`) gives a compact form with only the elapsed time (e.g. `10.2ms`).
+/// The elapsed time is read when formatting, so a running timer shows a different value each
+/// time: format a `snapshot()` of it for a stable output.
 impl std::fmt::Display for TimeLapse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Elapsed time: {:?}", self.elapsed())
+        if f.alternate() {
+            write!(f, "{:?}", self.elapsed())
+        } else {
+            write!(f, "Elapsed time: {:?}", self.elapsed())
+        }
     }
 }
 
@@ -143,30 +1067,671 @@ impl std::default::Default for TimeLapse {
 
 impl TimeLapse {
     /// Logs the elapsed time with a given name.
-    pub fn log(&self, name: &str, level: Level) {
-        log!(
-            level,
-            "TimeLapse {} - Elapsed time: {:?}",
-            name,
-            self.elapsed()
-        );
+    /// The message follows the template set with `set_log_format()`.
+    /// Nothing is logged if the elapsed time is below the floor set with `set_min_loggable()`,
+    /// or if the region is disabled (see `registry::set_region_enabled()`).
+    /// Returns `true` if the elapsed time was logged.
+    /// With `set_floor_warning()`, the message of an elapsed time too close to the measurement
+    /// floor to be trusted ends with `(near measurement floor)`.
+    pub fn log(&self, name: &str, level: Level) -> bool {
+        let elapsed = self.elapsed();
+        log_gated(name, elapsed, level, || {
+            let mut message = format_message(name, elapsed);
+            if FLOOR_WARNING.load(Ordering::Relaxed)
+                && is_near_floor(elapsed, Self::measurement_floor())
+            {
+                message.push_str(" (near measurement floor)");
+            }
+            if ENV_BASELINES.load(Ordering::Relaxed) {
+                if let Some(baseline) = env_baseline(name) {
+                    message.push_str(&baseline_suffix(elapsed, baseline));
+                }
+            }
+            message
+        })
+    }
+
+    /// Logs the elapsed time with a given name in a fixed `key=value` format meant for log
+    /// parsers, e.g. for alerting:
+    ///
+    /// `timelapse name=<name> elapsed_ns=<elapsed> ts=<timestamp>`
+    ///
+    /// - `name` is the name, quoted with `"` if it is empty or contains whitespace, `"`, `\`
+    ///   or `=`, the `"` and `\` characters being then escaped with a `\`.
+    /// - `elapsed_ns` is the elapsed time as an integer number of nanoseconds.
+    /// - `ts` is the time of the logging as an integer number of nanoseconds since the Unix
+    ///   epoch, zero if the wall clock is before it.
+    ///
+    /// The fields are always present, in this order, separated by a single space. The message
+    /// does not depend on the template of `set_log_format()` nor on any other formatting
+    /// setting, but, as with `log()`, nothing is logged if the elapsed time is below the floor
+    /// set with `set_min_loggable()`, or if the region is disabled (see
+    /// `registry::set_region_enabled()`).
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::{Level, TimeLapse};
+    ///
+    /// let profiler = TimeLapse::new();
+    /// // ... parsing ...
+    /// profiler.log_machine("parse", Level::Info); // "timelapse name=parse elapsed_ns=123456 ts=..."
+    /// ```
+    pub fn log_machine(&self, name: &str, level: Level) {
+        let elapsed = self.elapsed();
+        log_gated(name, elapsed, level, || {
+            let ts = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_nanos());
+            machine_message(name, elapsed, ts)
+        });
+    }
+
+    /// Enables or disables, for all `TimeLapse` instances, the `(near measurement floor)`
+    /// suffix of `log()` for the elapsed times below `NEAR_FLOOR_MULTIPLE` times the
+    /// measurement floor, which are mostly noise. It is disabled by default.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::{Level, TimeLapse};
+    ///
+    /// TimeLapse::set_floor_warning(true);
+    /// let profiler = TimeLapse::new();
+    /// profiler.log("trivial", Level::Info); // "... (near measurement floor)"
+    /// TimeLapse::set_floor_warning(false);
+    /// ```
+    pub fn set_floor_warning(enabled: bool) {
+        FLOOR_WARNING.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Enables or disables, for all `TimeLapse` instances, the comparison of `log()` against
+    /// the baselines given in the environment: a name has its baseline in the variable
+    /// `TIMELAPSE_BASELINE_<NAME>`, `<NAME>` being the name in uppercase with the characters
+    /// other than letters and digits replaced by `_` (e.g. `TIMELAPSE_BASELINE_DB__QUERY` for
+    /// `db::query`). The value is parsed with `format::parse_duration()`, and the percentage
+    /// difference is appended as by `log_vs_baseline()`. The names without a valid baseline
+    /// are logged as usual. It is disabled by default.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::{Level, TimeLapse};
+    ///
+    /// // Run with `TIMELAPSE_BASELINE_PARSE=3ms`.
+    /// TimeLapse::set_env_baselines(true);
+    /// let profiler = TimeLapse::new();
+    /// // ... parsing ...
+    /// profiler.log("parse", Level::Info); // "... (-12.0% vs baseline 3ms)"
+    /// TimeLapse::set_env_baselines(false);
+    /// ```
+    pub fn set_env_baselines(enabled: bool) {
+        ENV_BASELINES.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the smallest elapsed time that can be told apart from a measurement of nothing:
+    /// the larger of the overhead of starting and reading a timer and of the resolution of the
+    /// clock. It is calibrated on the first call, which takes a few microseconds on usual
+    /// clocks.
+    pub fn measurement_floor() -> Duration {
+        static FLOOR: OnceLock<Duration> = OnceLock::new();
+        *FLOOR.get_or_init(|| {
+            let mut overhead = Duration::MAX;
+            let mut resolution = Duration::MAX;
+            for _ in 0..CALIBRATION_SAMPLES {
+                overhead = overhead.min(TimeLapse::new().elapsed());
+                let start = Instant::now();
+                let tick = loop {
+                    let tick = start.elapsed();
+                    if !tick.is_zero() {
+                        break tick;
+                    }
+                };
+                resolution = resolution.min(tick);
+            }
+            overhead.max(resolution)
+        })
+    }
+
+    /// Sets the floor below which `log()`, `report()`, and the other methods and helpers logging
+    /// a named region, skip the elapsed times of all `TimeLapse` instances, e.g. to filter out
+    /// the regions faster than the clock resolution, logged as `0ns`. The default floor is zero:
+    /// everything is logged.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::{Level, TimeLapse};
+    ///
+    /// TimeLapse::set_min_loggable(Duration::from_micros(1));
+    /// let profiler = TimeLapse::new();
+    /// profiler.log("trivial", Level::Info); // most likely skipped
+    /// TimeLapse::set_min_loggable(Duration::ZERO);
+    /// ```
+    pub fn set_min_loggable(floor: Duration) {
+        MIN_LOGGABLE_NANOS.store(floor_nanos(floor), Ordering::Relaxed);
     }
 
     /// Prints the elapsed time with a given name.
+    /// The message follows the template set with `set_log_format()`.
+    /// Nothing is printed if the region is disabled (see `registry::set_region_enabled()`).
     pub fn print(&self, name: &str) {
-        println!("TimeLapse {} - Elapsed time: {:?}", name, self.elapsed());
+        if crate::registry::is_region_enabled(name) {
+            println!("{}", format_message(name, self.elapsed()));
+        }
+    }
+
+    /// Sets the template of the messages logged and printed by all `TimeLapse` instances.
+    /// The `{name}` and `{elapsed}` placeholders are substituted with the profiler's name and
+    /// elapsed time. The default template is `DEFAULT_LOG_FORMAT`.
+    ///
+    /// # Panics
+    /// Panics if the template is invalid, see `try_set_log_format()`.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// TimeLapse::set_log_format("Finished {name} in {elapsed}");
+    /// let profiler = TimeLapse::new();
+    /// profiler.print("parsing");
+    /// ```
+    pub fn set_log_format(template: &str) {
+        if let Err(error) = Self::try_set_log_format(template) {
+            panic!("invalid log format {:?}: {}", template, error);
+        }
+    }
+
+    /// Sets the template of the messages logged and printed by all `TimeLapse` instances, as
+    /// `set_log_format()` does, if it is valid.
+    ///
+    /// # Errors
+    /// Returns an error, leaving the current template in place, if the template has a
+    /// placeholder other than `{name}` and `{elapsed}`, or a `{` with no closing `}`.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::profiler::FormatError;
+    /// use timelapse::TimeLapse;
+    ///
+    /// assert_eq!(
+    ///     TimeLapse::try_set_log_format("{name} took {elapsd}"),
+    ///     Err(FormatError::UnknownPlaceholder("elapsd".to_string()))
+    /// );
+    /// ```
+    pub fn try_set_log_format(template: &str) -> Result<(), FormatError> {
+        validate_log_format(template)?;
+        *LOG_FORMAT.write().unwrap_or_else(PoisonError::into_inner) = Some(template.to_string());
+        Ok(())
+    }
+
+    /// Logs the elapsed time with a given group and label, formatted as `group::label`.
+    pub fn log_in(&self, group: &str, label: &str, level: Level) {
+        self.log(&qualified_name(group, label), level);
+    }
+
+    /// Prints the elapsed time with a given group and label, formatted as `group::label`.
+    pub fn print_in(&self, group: &str, label: &str) {
+        self.print(&qualified_name(group, label));
+    }
+
+    /// Logs at the info level the elapsed time with a given name, only for the first `n` calls
+    /// with that name across all threads and instances, then stays silent. This is meant for
+    /// regions whose first runs only are of interest, e.g. for cold-cache effects.
+    /// Returns `true` if the elapsed time was logged. A call whose elapsed time is not logged
+    /// because of the floor or of a disabled region still counts as one of the first `n`.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// for _ in 0..10 {
+    ///     let profiler = TimeLapse::new();
+    ///     // ... cached operation ...
+    ///     profiler.log_first_n("doc_cache_lookup", 3);
+    /// }
+    /// ```
+    pub fn log_first_n(&self, name: &str, n: usize) -> bool {
+        let counters = FIRST_N_COUNTERS.get_or_init(RwLock::default);
+        let count = {
+            let read = counters.read().unwrap_or_else(PoisonError::into_inner);
+            read.get(name)
+                .map(|counter| counter.fetch_add(1, Ordering::Relaxed))
+        };
+        let count = count.unwrap_or_else(|| {
+            let mut write = counters.write().unwrap_or_else(PoisonError::into_inner);
+            write
+                .entry(name.to_string())
+                .or_default()
+                .fetch_add(1, Ordering::Relaxed)
+        });
+        count < n && self.log(name, Level::Info)
+    }
+
+    /// Logs at the info level the elapsed time with a given name only if it is the largest
+    /// seen so far for that name, by any thread, e.g. `... (new maximum, previous 12ms)`.
+    /// Returns `true` if the elapsed time was logged. This surfaces a region degrading over
+    /// the life of the program without logging every call. A maximum not logged because of the
+    /// floor or of a disabled region is still recorded.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// for _ in 0..10 {
+    ///     let profiler = TimeLapse::new();
+    ///     // ... request handling ...
+    ///     profiler.log_on_new_max("doc_request");
+    /// }
+    /// ```
+    pub fn log_on_new_max(&self, name: &str) -> bool {
+        let elapsed = self.elapsed();
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let maxima = MAX_NANOS.get_or_init(RwLock::default);
+        let recorded = {
+            let read = maxima.read().unwrap_or_else(PoisonError::into_inner);
+            read.get(name)
+                .map(|max| max.fetch_max(nanos, Ordering::Relaxed))
+        };
+        let previous = recorded.or_else(|| {
+            let mut write = maxima.write().unwrap_or_else(PoisonError::into_inner);
+            match write.get(name) {
+                Some(max) => Some(max.fetch_max(nanos, Ordering::Relaxed)),
+                None => {
+                    write.insert(name.to_string(), AtomicU64::new(nanos));
+                    None
+                }
+            }
+        });
+        let suffix = match previous {
+            None => " (new maximum)".to_string(),
+            Some(previous) if nanos > previous => format!(
+                " (new maximum, previous {:?})",
+                Duration::from_nanos(previous)
+            ),
+            Some(_) => return false,
+        };
+        log_gated(name, elapsed, Level::Info, || {
+            format_message(name, elapsed) + &suffix
+        })
+    }
+
+    /// Logs at the info level the elapsed time with a given name, along with the signed
+    /// percentage difference from a baseline duration (e.g. `+12.0% vs baseline 10ms`).
+    /// A run faster than the baseline shows a negative percentage. A zero baseline
+    /// cannot be compared against and is reported as such.
+    pub fn log_vs_baseline(&self, name: &str, baseline: Duration) {
+        let elapsed = self.elapsed();
+        log_gated(name, elapsed, Level::Info, || {
+            format_message(name, elapsed) + &baseline_suffix(elapsed, baseline)
+        });
+    }
+
+    /// Logs the elapsed time with a given name, at a level depending on it: the level of the
+    /// highest threshold reached, i.e. the last one with `elapsed() >= threshold`, the
+    /// thresholds being expected in ascending order of duration. If none is reached, or the
+    /// slice is empty, `DEFAULT_ADAPTIVE_LEVEL` is used.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::Level;
+    /// use std::time::Duration;
+    /// use timelapse::TimeLapse;
+    ///
+    /// let profiler = TimeLapse::new();
+    /// // ... handling the request ...
+    /// profiler.log_adaptive(
+    ///     "request",
+    ///     &[
+    ///         (Duration::ZERO, Level::Trace),
+    ///         (Duration::from_millis(10), Level::Debug),
+    ///         (Duration::from_millis(500), Level::Warn),
+    ///     ],
+    /// );
+    /// ```
+    pub fn log_adaptive(&self, name: &str, thresholds: &[(Duration, Level)]) {
+        self.log(name, adaptive_level(self.elapsed(), thresholds));
+    }
+}
+
+/// The level of `TimeLapse::log_adaptive()` when no threshold is reached.
+pub const DEFAULT_ADAPTIVE_LEVEL: Level = Level::Info;
+
+/// Returns the level of the last threshold reached by an elapsed time, see
+/// `TimeLapse::log_adaptive()`.
+fn adaptive_level(elapsed: Duration, thresholds: &[(Duration, Level)]) -> Level {
+    thresholds
+        .iter()
+        .rev()
+        .find(|(threshold, _)| elapsed >= *threshold)
+        .map_or(DEFAULT_ADAPTIVE_LEVEL, |(_, level)| *level)
+}
+
+/// The default template of the messages logged and printed by `TimeLapse` instances.
+pub const DEFAULT_LOG_FORMAT: &str = "TimeLapse {name} - Elapsed time: {elapsed}";
+
+/// The per-name call counters of `TimeLapse::log_first_n()`.
+static FIRST_N_COUNTERS: OnceLock<RwLock<HashMap<String, AtomicUsize>>> = OnceLock::new();
+
+/// The `Measurement` trait is implemented by the values compared by `assert_faster!`.
+#[doc(hidden)]
+pub trait Measurement {
+    /// Returns the measured elapsed time.
+    fn measured(&self) -> Duration;
+}
+
+impl Measurement for TimeLapse {
+    fn measured(&self) -> Duration {
+        self.elapsed()
+    }
+}
+
+impl Measurement for crate::Stopwatch {
+    fn measured(&self) -> Duration {
+        self.elapsed()
+    }
+}
+
+impl Measurement for crate::Snapshot<'_> {
+    fn measured(&self) -> Duration {
+        self.elapsed()
+    }
+}
+
+impl Measurement for Duration {
+    fn measured(&self) -> Duration {
+        *self
+    }
+}
+
+/// Panics unless `a` is less than `b` plus `tolerance`. See `assert_faster!`.
+#[doc(hidden)]
+#[track_caller]
+pub fn check_faster(a_name: &str, a: Duration, b_name: &str, b: Duration, tolerance: Duration) {
+    if a >= b.saturating_add(tolerance) {
+        let mut message = format!(
+            "assertion failed: `{}` ({:?}) is not faster than `{}` ({:?})",
+            a_name, a, b_name, b
+        );
+        if !tolerance.is_zero() {
+            let _ = write!(message, " with a tolerance of {:?}", tolerance);
+        }
+        panic!("{}", message);
+    }
+}
+
+/// Sums the laps of several timers by label, e.g. of the shards of a job timing the same
+/// phases, and returns the totals in the order the labels first appear, going through the
+/// timers in order. A label recorded by only some of the timers is still reported, and a label
+/// recorded several times by a timer has all its laps summed.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{merge_laps, TimeLapse};
+///
+/// let mut shards = [TimeLapse::new(), TimeLapse::new()];
+/// for shard in &mut shards {
+///     // ... loading ...
+///     shard.lap("load");
+///     // ... processing ...
+///     shard.lap("process");
+/// }
+/// let totals = merge_laps(&[&shards[0], &shards[1]]);
+/// assert_eq!(totals.len(), 2);
+/// assert_eq!(totals[0].0, "load");
+/// ```
+pub fn merge_laps(timers: &[&TimeLapse]) -> Vec<(String, Duration)> {
+    let mut totals: Vec<(String, Duration)> = Vec::new();
+    for (label, lap) in timers.iter().flat_map(|timer| timer.laps()) {
+        match totals.iter_mut().find(|(name, _)| name == label) {
+            Some((_, total)) => *total += *lap,
+            None => totals.push((label.clone(), *lap)),
+        }
+    }
+    totals
+}
+
+/// The multiple of the measurement floor under which `TimeLapse::log()` warns, with
+/// `TimeLapse::set_floor_warning()`, that the elapsed time is near it.
+pub const NEAR_FLOOR_MULTIPLE: u32 = 5;
+
+/// The number of samples of the calibration of `TimeLapse::measurement_floor()`.
+const CALIBRATION_SAMPLES: usize = 16;
+
+/// Whether `TimeLapse::log()` warns of the elapsed times near the measurement floor.
+static FLOOR_WARNING: AtomicBool = AtomicBool::new(false);
+
+/// The prefix of the environment variables holding the baselines of
+/// `TimeLapse::set_env_baselines()`.
+pub const BASELINE_ENV_PREFIX: &str = "TIMELAPSE_BASELINE_";
+
+/// Whether `TimeLapse::log()` compares the elapsed times to the baselines of the environment.
+static ENV_BASELINES: AtomicBool = AtomicBool::new(false);
+
+/// Returns the environment variable holding the baseline of a name, e.g.
+/// `TIMELAPSE_BASELINE_DB__QUERY` for `db::query`.
+fn baseline_env_var(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", BASELINE_ENV_PREFIX, name)
+}
+
+/// Returns the baseline of a name given in the environment, if any is set and valid.
+fn env_baseline(name: &str) -> Option<Duration> {
+    let value = std::env::var(baseline_env_var(name)).ok()?;
+    crate::format::parse_duration(&value).ok()
+}
+
+/// Returns the comparison of an elapsed time to a baseline appended to the log messages, e.g.
+/// ` (+12.0% vs baseline 10ms)`, or ` (zero baseline)`.
+fn baseline_suffix(elapsed: Duration, baseline: Duration) -> String {
+    match baseline_delta_pct(elapsed, baseline) {
+        Some(pct) => format!(" ({:+.1}% vs baseline {:?})", pct, baseline),
+        None => " (zero baseline)".to_string(),
+    }
+}
+
+/// Returns `true` if an elapsed time is below `NEAR_FLOOR_MULTIPLE` times the measurement
+/// floor.
+fn is_near_floor(elapsed: Duration, floor: Duration) -> bool {
+    elapsed < floor.saturating_mul(NEAR_FLOOR_MULTIPLE)
+}
+
+/// The floor set with `TimeLapse::set_min_loggable()`, in nanoseconds.
+static MIN_LOGGABLE_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a floor in nanoseconds, saturated to `u64::MAX`.
+fn floor_nanos(floor: Duration) -> u64 {
+    u64::try_from(floor.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Returns `true` if an elapsed time reaches a floor in nanoseconds.
+fn reaches_floor(elapsed: Duration, floor_nanos: u64) -> bool {
+    elapsed.as_nanos() >= u128::from(floor_nanos)
+}
+
+/// Returns `true` if an elapsed time reaches the floor set with `TimeLapse::set_min_loggable()`.
+fn is_loggable(elapsed: Duration) -> bool {
+    reaches_floor(elapsed, MIN_LOGGABLE_NANOS.load(Ordering::Relaxed))
+}
+
+/// Returns `true` if the elapsed time of a named region is to be logged or reported: it
+/// reaches the floor set with `TimeLapse::set_min_loggable()` and the region is enabled (see
+/// `registry::set_region_enabled()`).
+pub(crate) fn passes_gate(name: &str, elapsed: Duration) -> bool {
+    is_loggable(elapsed) && crate::registry::is_region_enabled(name)
+}
+
+/// Logs the message built by `message` about the elapsed time of a named region if it passes
+/// `passes_gate()`, and returns whether it was logged. All the logging of named regions goes
+/// through it, so that the floor and the region settings apply everywhere.
+pub(crate) fn log_gated(
+    name: &str,
+    elapsed: Duration,
+    level: Level,
+    message: impl FnOnce() -> String,
+) -> bool {
+    let logged = passes_gate(name, elapsed);
+    if logged {
+        log!(level, "{}", message());
     }
+    logged
+}
+
+/// The per-name largest elapsed times of `TimeLapse::log_on_new_max()`, in nanoseconds.
+static MAX_NANOS: OnceLock<RwLock<HashMap<String, AtomicU64>>> = OnceLock::new();
+
+/// The template set with `TimeLapse::set_log_format()`, `None` for the default one.
+static LOG_FORMAT: RwLock<Option<String>> = RwLock::new(None);
+
+/// Returns the message for a profiler's name and elapsed time, following the current template.
+pub(crate) fn format_message(name: &str, elapsed: Duration) -> String {
+    let template = LOG_FORMAT.read().unwrap_or_else(PoisonError::into_inner);
+    render_log_format(
+        template.as_deref().unwrap_or(DEFAULT_LOG_FORMAT),
+        name,
+        elapsed,
+    )
+}
+
+/// The placeholders of the templates of `TimeLapse::set_log_format()`.
+const LOG_FORMAT_PLACEHOLDERS: [&str; 2] = ["name", "elapsed"];
+
+/// The error returned by `TimeLapse::try_set_log_format()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// The template has a placeholder other than `{name}` and `{elapsed}`, given without its
+    /// braces.
+    UnknownPlaceholder(String),
+    /// The template has a `{` with no closing `}`.
+    UnclosedPlaceholder,
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::UnknownPlaceholder(placeholder) => write!(
+                f,
+                "unknown placeholder {{{}}}, expected {{name}} or {{elapsed}}",
+                placeholder
+            ),
+            FormatError::UnclosedPlaceholder => f.write_str("unclosed placeholder"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Checks that every placeholder of a template is known.
+fn validate_log_format(template: &str) -> Result<(), FormatError> {
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            return Err(FormatError::UnclosedPlaceholder);
+        };
+        let placeholder = &rest[open + 1..open + close];
+        if !LOG_FORMAT_PLACEHOLDERS.contains(&placeholder) {
+            return Err(FormatError::UnknownPlaceholder(placeholder.to_string()));
+        }
+        rest = &rest[open + close + 1..];
+    }
+    Ok(())
+}
+
+/// Substitutes the `{name}` and `{elapsed}` placeholders of a template.
+/// Unknown placeholders are left verbatim.
+fn render_log_format(template: &str, name: &str, elapsed: Duration) -> String {
+    let mut message = String::with_capacity(template.len() + name.len() + 16);
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        message.push_str(&rest[..open]);
+        rest = &rest[open..];
+        if let Some(tail) = rest.strip_prefix("{name}") {
+            message.push_str(name);
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("{elapsed}") {
+            let _ = write!(message, "{:?}", elapsed);
+            rest = tail;
+        } else {
+            message.push('{');
+            rest = &rest[1..];
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+/// Returns the message of `TimeLapse::log_machine()`, `ts` being the time of the logging in
+/// nanoseconds since the Unix epoch.
+fn machine_message(name: &str, elapsed: Duration, ts: u128) -> String {
+    let mut message = String::from("timelapse name=");
+    if name.is_empty()
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\\' | '='))
+    {
+        message.push('"');
+        for c in name.chars() {
+            if matches!(c, '"' | '\\') {
+                message.push('\\');
+            }
+            message.push(c);
+        }
+        message.push('"');
+    } else {
+        message.push_str(name);
+    }
+    let _ = write!(message, " elapsed_ns={} ts={}", elapsed.as_nanos(), ts);
+    message
+}
+
+/// Returns the name under which a label of a group is reported: `group::label`.
+pub fn qualified_name(group: &str, label: &str) -> String {
+    format!("{}::{}", group, label)
+}
+
+/// Returns the signed percentage difference of `elapsed` relative to `baseline`,
+/// or `None` if the baseline is zero.
+fn baseline_delta_pct(elapsed: Duration, baseline: Duration) -> Option<f64> {
+    if baseline.is_zero() {
+        return None;
+    }
+    let baseline = baseline.as_secs_f64();
+    Some((elapsed.as_secs_f64() - baseline) / baseline * 100.0)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::VirtualClock;
+
+    /// Tolerance of the timing assertions around sleeps.
+    const TOLERANCE: Duration = Duration::from_millis(50);
+
+    /// Returns a stopped profiler frozen at the given elapsed time.
+    fn frozen(elapsed: Duration) -> TimeLapse {
+        let mut profiler = TimeLapse::new();
+        profiler.stopped = Some(elapsed);
+        profiler
+    }
+
+    /// Returns a virtual clock and a profiler started on it.
+    fn virtual_timer() -> (Arc<VirtualClock>, TimeLapse) {
+        let clock = Arc::new(VirtualClock::new());
+        let profiler = TimeLapse::new().with_clock(clock.clone());
+        (clock, profiler)
+    }
 
     #[test]
     fn test_profiler() {
-        let profiler = TimeLapse::new();
-        std::thread::sleep(Duration::from_millis(100));
-        assert!(profiler.elapsed().as_millis() >= 100);
+        let (clock, profiler) = virtual_timer();
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(profiler.elapsed(), Duration::from_millis(100));
         profiler.print("test");
     }
 
@@ -174,15 +1739,713 @@ mod tests {
     fn test_profiler_macros() {
         profile_start!(the_profile);
         std::thread::sleep(Duration::from_millis(100));
-        assert!(the_profile.elapsed().as_millis() >= 100);
+        assert!(the_profile.elapsed_approx(Duration::from_millis(100), TOLERANCE));
         profile_end_print!(the_profile);
     }
 
     #[test]
     fn test_profiler_reset() {
+        let (clock, mut profiler) = virtual_timer();
+        clock.advance(Duration::from_millis(100));
+        profiler.reset();
+        assert_eq!(profiler.elapsed(), Duration::ZERO);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(profiler.elapsed(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_profiler_stop() {
+        let (clock, mut profiler) = virtual_timer();
+        clock.advance(Duration::from_millis(10));
+        let stopped = profiler.stop();
+        assert_eq!(stopped, Duration::from_millis(10));
+        assert!(profiler.is_stopped());
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(profiler.elapsed(), stopped);
+        assert_eq!(profiler.stop(), stopped);
+        profiler.reset();
+        assert!(!profiler.is_stopped());
+        assert_eq!(profiler.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_profiler_match() {
+        let classify = |value: Option<i32>| {
+            profile_match!(value, {
+                Some(0) => "zero" => "zero",
+                Some(n) if n < 0 => "negative" => "negative",
+                Some(_) => "positive" => {
+                    std::thread::sleep(Duration::from_millis(1));
+                    "positive"
+                },
+                None => "none" => "none"
+            })
+        };
+        assert_eq!(classify(Some(0)), "zero");
+        assert_eq!(classify(Some(-3)), "negative");
+        assert_eq!(classify(Some(3)), "positive");
+        assert_eq!(classify(None), "none");
+    }
+
+    #[test]
+    fn test_profiler_group() {
+        assert_eq!(qualified_name("db", "query"), "db::query");
+        profile_start!(the_profile);
+        profile_end_print!(the_profile, group: "test");
+        profile_end_log!(the_profile, group: "test", level: Level::Debug);
+    }
+
+    #[test]
+    fn test_profiler_uninit() {
+        let clock = Arc::new(VirtualClock::new());
+        let mut profiler = TimeLapse::uninit().with_clock(clock.clone());
+        assert!(!profiler.is_started());
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(profiler.elapsed(), Duration::ZERO);
+
+        profiler.start();
+        assert!(profiler.is_started());
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(profiler.elapsed(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_profiler_start_epoch_nanos() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let (clock, mut profiler) = virtual_timer();
+        clock.advance(Duration::from_millis(10));
+        profiler.stop();
+        clock.advance(Duration::from_millis(10));
+        let start = Duration::from_nanos(profiler.start_epoch_nanos() as u64);
+        assert!(start.abs_diff(before) < TOLERANCE);
+        assert_eq!(TimeLapse::uninit().start_epoch_nanos(), 0);
+    }
+
+    #[test]
+    fn test_profiler_start_epoch_nanos_pause() {
+        let (clock, mut profiler) = virtual_timer();
+        let start = profiler.start_epoch_nanos();
+        assert_ne!(start, 0);
+        clock.advance(Duration::from_secs(60));
+        profiler.pause();
+        clock.advance(Duration::from_secs(3600));
+        profiler.resume();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(profiler.start_epoch_nanos(), start);
+
+        profiler.reset();
+        assert!(profiler.start_epoch_nanos() >= start);
+    }
+
+    #[test]
+    fn test_profiler_restart() {
+        let (clock, mut profiler) = virtual_timer();
+        clock.advance(Duration::from_millis(10));
+        let first = profiler.stop();
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(profiler.restart(), first);
+        clock.advance(Duration::from_millis(15));
+        assert_eq!(profiler.stop(), Duration::from_millis(15));
+        assert_eq!(profiler.grand_total(), Duration::from_millis(25));
+
+        profiler.reset();
+        assert_eq!(profiler.grand_total(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_profiler_laps() {
+        let (clock, mut profiler) = virtual_timer();
+        clock.advance(Duration::from_millis(10));
+        let first = profiler.lap("first");
+        clock.advance(Duration::from_millis(20));
+        let second = profiler.lap("second");
+        assert_eq!(first, Duration::from_millis(10));
+        assert_eq!(second, Duration::from_millis(20));
+        assert_eq!(profiler.elapsed(), first + second);
+        assert_eq!(
+            profiler.laps(),
+            &[("first".to_string(), first), ("second".to_string(), second)]
+        );
+
+        profiler.reset();
+        assert!(profiler.laps().is_empty());
+    }
+
+    #[test]
+    fn test_machine_message() {
+        let elapsed = Duration::from_micros(123_456);
+        assert_eq!(
+            machine_message("db::query", elapsed, 42),
+            "timelapse name=db::query elapsed_ns=123456000 ts=42"
+        );
+        assert_eq!(
+            machine_message("load \"a=b\"", elapsed, 0),
+            r#"timelapse name="load \"a=b\"" elapsed_ns=123456000 ts=0"#
+        );
+        assert_eq!(
+            machine_message("", Duration::ZERO, 1),
+            r#"timelapse name="" elapsed_ns=0 ts=1"#
+        );
+        TimeLapse::new().log_machine("test_machine_message", Level::Debug);
+    }
+
+    #[test]
+    fn test_log_gated() {
+        let elapsed = Duration::from_millis(1);
+        assert!(log_gated("test_log_gated", elapsed, Level::Debug, || {
+            "logged".to_string()
+        }));
+        crate::registry::set_region_enabled("test_log_gated_disabled", false);
+        assert!(!log_gated(
+            "test_log_gated_disabled",
+            elapsed,
+            Level::Debug,
+            || unreachable!()
+        ));
+    }
+
+    #[test]
+    fn test_merge_laps() {
+        let (clock, mut first) = virtual_timer();
+        let mut second = TimeLapse::new().with_clock(clock.clone());
+        clock.advance(Duration::from_millis(10));
+        first.lap("load");
+        second.lap("load");
+        clock.advance(Duration::from_millis(5));
+        second.lap("sort");
+        first.lap("load");
+        first.lap("emit");
+
+        assert_eq!(
+            merge_laps(&[&first, &second]),
+            [
+                ("load".to_string(), Duration::from_millis(25)),
+                ("emit".to_string(), Duration::ZERO),
+                ("sort".to_string(), Duration::from_millis(5)),
+            ]
+        );
+        assert!(merge_laps(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_profiler_on_lap() {
+        let (clock, mut profiler) = virtual_timer();
+        let streamed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&streamed);
+        profiler.on_lap(move |label, lap| sink.lock().unwrap().push((label.to_string(), lap)));
+        clock.advance(Duration::from_millis(10));
+        profiler.lap("first");
+        clock.advance(Duration::from_millis(5));
+        profiler.lap("second");
+        assert_eq!(
+            *streamed.lock().unwrap(),
+            [
+                ("first".to_string(), Duration::from_millis(10)),
+                ("second".to_string(), Duration::from_millis(5))
+            ]
+        );
+        assert!(profiler.laps().is_empty());
+        assert_eq!(profiler.min_lap(), Some(Duration::from_millis(5)));
+
+        profiler.reset();
+        profiler.lap("after reset");
+        assert_eq!(streamed.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_profiler_tick() {
+        let (clock, mut profiler) = virtual_timer();
+        assert_eq!(profiler.fps(), None);
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(profiler.tick(), Duration::from_millis(20));
+        assert_eq!(profiler.fps(), Some(50.0));
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(profiler.tick(), Duration::from_millis(30));
+        assert_eq!(profiler.fps(), Some(40.0));
+        assert!(profiler.laps().is_empty());
+
+        for _ in 0..FPS_WINDOW {
+            clock.advance(Duration::from_millis(10));
+            profiler.tick();
+        }
+        assert_eq!(profiler.frames(), FPS_WINDOW as u64 + 2);
+        assert!((profiler.fps().unwrap() - 100.0).abs() < 1e-9);
+
+        profiler.reset_segment();
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(profiler.tick(), Duration::from_millis(5));
+        profiler.reset();
+        assert_eq!((profiler.frames(), profiler.fps()), (0, None));
+    }
+
+    #[test]
+    fn test_profiler_reset_segment() {
+        let mut profiler = frozen(Duration::from_millis(30));
+        profiler.lap("long");
+        profiler.restart();
+        profiler.stop();
+        profiler.lap("short");
+        assert!(profiler.min_lap().unwrap() < Duration::from_millis(30));
+        assert_eq!(profiler.max_lap(), Some(Duration::from_millis(30)));
+
+        profiler.reset_segment();
+        assert!(!profiler.is_stopped());
+        assert_eq!(profiler.laps().len(), 2);
+        assert_eq!(profiler.max_lap(), Some(Duration::from_millis(30)));
+        assert!(profiler.grand_total() >= Duration::from_millis(30));
+
+        profiler.reset();
+        assert_eq!((profiler.min_lap(), profiler.max_lap()), (None, None));
+        assert!(profiler.grand_total() < Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_profiler_elapsed_approx() {
+        let profiler = frozen(Duration::from_millis(100));
+        assert!(profiler.elapsed_approx(Duration::from_millis(100), Duration::ZERO));
+        assert!(profiler.elapsed_approx(Duration::from_millis(90), Duration::from_millis(10)));
+        assert!(profiler.elapsed_approx(Duration::from_millis(110), Duration::from_millis(10)));
+        assert!(!profiler.elapsed_approx(Duration::from_millis(111), Duration::from_millis(10)));
+        assert!(!profiler.elapsed_approx(Duration::from_millis(89), Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_profiler_budget_fraction() {
+        let budget = Duration::from_micros(16_667);
+        let profiler = frozen(Duration::from_micros(12_500));
+        assert!((profiler.budget_fraction(budget) - 0.75).abs() < 1e-3);
+        assert!(frozen(Duration::from_millis(20)).budget_fraction(budget) > 1.0);
+        assert_eq!(profiler.budget_fraction(Duration::ZERO), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_profiler_wait_until() {
         let mut profiler = TimeLapse::new();
-        std::thread::sleep(Duration::from_millis(100));
+        assert!(profiler.wait_until(Duration::from_millis(20)));
+        assert!(profiler.elapsed() >= Duration::from_millis(20));
+
+        profiler.stop();
+        let waiting = TimeLapse::new();
+        assert!(!profiler.wait_until_capped(Duration::from_secs(1), Duration::from_millis(10)));
+        assert!(waiting.elapsed() >= Duration::from_millis(10));
+        assert!(waiting.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_profiler_display() {
+        let (clock, mut profiler) = virtual_timer();
+        clock.advance(Duration::from_micros(10_200));
+        profiler.stop();
+        assert_eq!(format!("{}", profiler), "Elapsed time: 10.2ms");
+        assert_eq!(format!("{:#}", profiler), "10.2ms");
+    }
+
+    #[test]
+    fn test_validate_log_format() {
+        assert_eq!(validate_log_format(DEFAULT_LOG_FORMAT), Ok(()));
+        assert_eq!(validate_log_format("no placeholder"), Ok(()));
+        assert_eq!(validate_log_format("{elapsed}{name}"), Ok(()));
+        assert_eq!(
+            validate_log_format("{name} took {duration}"),
+            Err(FormatError::UnknownPlaceholder("duration".to_string()))
+        );
+        assert_eq!(
+            validate_log_format("{}"),
+            Err(FormatError::UnknownPlaceholder(String::new()))
+        );
+        assert_eq!(
+            validate_log_format("{name} {elapsed"),
+            Err(FormatError::UnclosedPlaceholder)
+        );
+        assert_eq!(
+            FormatError::UnknownPlaceholder("n".to_string()).to_string(),
+            "unknown placeholder {n}, expected {name} or {elapsed}"
+        );
+
+        assert!(TimeLapse::try_set_log_format("{name} {oops}").is_err());
+        let panic = std::panic::catch_unwind(|| TimeLapse::set_log_format("{oops}"));
+        assert!(panic.is_err());
+        let template = LOG_FORMAT.read().unwrap_or_else(PoisonError::into_inner);
+        assert!(!template
+            .as_deref()
+            .is_some_and(|template| template.contains("oops")));
+    }
+
+    #[test]
+    fn test_render_log_format() {
+        let elapsed = Duration::from_millis(12);
+        assert_eq!(
+            render_log_format(DEFAULT_LOG_FORMAT, "test", elapsed),
+            "TimeLapse test - Elapsed time: 12ms"
+        );
+        assert_eq!(
+            render_log_format("Finished {name} in {elapsed}", "test", elapsed),
+            "Finished test in 12ms"
+        );
+        assert_eq!(
+            render_log_format("{name} {unknown} {{elapsed}} {", "test", elapsed),
+            "test {unknown} {12ms} {"
+        );
+    }
+
+    #[test]
+    fn test_baseline_delta_pct() {
+        let baseline = Duration::from_millis(100);
+        let slower = baseline_delta_pct(Duration::from_millis(112), baseline).unwrap();
+        let faster = baseline_delta_pct(Duration::from_millis(75), baseline).unwrap();
+        assert!((slower - 12.0).abs() < 1e-9);
+        assert!((faster + 25.0).abs() < 1e-9);
+        assert_eq!(baseline_delta_pct(baseline, Duration::ZERO), None);
+
+        let profiler = TimeLapse::new();
+        profiler.log_vs_baseline("test", baseline);
+        profiler.log_vs_baseline("test", Duration::ZERO);
+    }
+
+    #[test]
+    fn test_env_baselines() {
+        assert_eq!(baseline_env_var("parse"), "TIMELAPSE_BASELINE_PARSE");
+        assert_eq!(
+            baseline_env_var("db::query-1"),
+            "TIMELAPSE_BASELINE_DB__QUERY_1"
+        );
+        assert_eq!(
+            baseline_suffix(Duration::from_millis(11), Duration::from_millis(10)),
+            " (+10.0% vs baseline 10ms)"
+        );
+        assert_eq!(
+            baseline_suffix(Duration::from_millis(1), Duration::ZERO),
+            " (zero baseline)"
+        );
+
+        std::env::set_var("TIMELAPSE_BASELINE_TEST_ENV_BASELINES", "1.5ms");
+        std::env::set_var("TIMELAPSE_BASELINE_TEST_ENV_BASELINES_INVALID", "fast");
+        assert_eq!(
+            env_baseline("test_env_baselines"),
+            Some(Duration::from_micros(1_500))
+        );
+        assert_eq!(env_baseline("test_env_baselines_invalid"), None);
+        assert_eq!(env_baseline("test_env_baselines_unset"), None);
+    }
+
+    #[test]
+    fn test_profile_try() {
+        fn halve(value: u32, limit: Duration) -> Result<u32, String> {
+            let even = profile_try!(
+                halving,
+                limit,
+                match value % 2 {
+                    0 => Ok(value),
+                    _ => Err(format!("{} is odd", value)),
+                }
+            );
+            Ok(even / 2)
+        }
+
+        assert_eq!(halve(4, Duration::from_secs(1)), Ok(2));
+        assert_eq!(
+            halve(3, Duration::from_secs(1)),
+            Err("3 is odd".to_string())
+        );
+        assert!(std::panic::catch_unwind(|| halve(4, Duration::ZERO)).is_err());
+    }
+
+    #[test]
+    fn test_assert_faster() {
+        let fast = frozen(Duration::from_millis(10));
+        let slow = crate::Snapshot::new(None, Duration::from_millis(12));
+        assert_faster!(fast, slow);
+        assert_faster!(slow, fast, tolerance: Duration::from_millis(3));
+        assert_faster!(Duration::ZERO, crate::Stopwatch::start_new());
+
+        let failure = std::panic::catch_unwind(|| assert_faster!(slow, fast)).unwrap_err();
+        assert_eq!(
+            failure.downcast_ref::<String>().unwrap(),
+            "assertion failed: `slow` (12ms) is not faster than `fast` (10ms)"
+        );
+        let failure =
+            std::panic::catch_unwind(|| assert_faster!(fast, fast, tolerance: Duration::ZERO))
+                .unwrap_err();
+        assert!(failure
+            .downcast_ref::<String>()
+            .unwrap()
+            .ends_with("is not faster than `fast` (10ms)"));
+        let failure = std::panic::catch_unwind(
+            || assert_faster!(slow, fast, tolerance: Duration::from_millis(1)),
+        )
+        .unwrap_err();
+        assert!(failure
+            .downcast_ref::<String>()
+            .unwrap()
+            .ends_with(" with a tolerance of 1ms"));
+    }
+
+    #[test]
+    fn test_profile_lines() {
+        fn first_even(values: &[u32]) -> Option<u32> {
+            profile_lines! {
+                let mut found = None;
+                for value in values {
+                    if value % 2 == 0 {
+                        found = Some(*value);
+                        break;
+                    }
+                };
+                let even = found?;
+            }
+            Some(even)
+        }
+
+        assert_eq!(first_even(&[1, 3, 4, 6]), Some(4));
+        assert_eq!(first_even(&[1, 3]), None);
+    }
+
+    #[test]
+    fn test_elapsed_macros() {
+        let profiler = frozen(Duration::from_micros(12_345));
+        assert_eq!(elapsed_ms!(profiler), 12);
+        assert_eq!(format!("took {}", elapsed_fmt!(profiler)), "took 12.35ms");
+        assert_eq!(
+            format!("{} / {}", elapsed_ms!(&profiler), elapsed_fmt!(&profiler)),
+            "12 / 12.35ms"
+        );
+    }
+
+    #[test]
+    fn test_log_first_n() {
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    (0..25)
+                        .filter(|_| TimeLapse::new().log_first_n("test_log_first_n", 10))
+                        .count()
+                })
+            })
+            .collect();
+        let logged: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+        assert_eq!(logged, 10);
+        assert!(!TimeLapse::new().log_first_n("test_log_first_n", 10));
+        assert!(!TimeLapse::new().log_first_n("test_log_first_n_zero", 0));
+        crate::registry::set_region_enabled("test_log_first_n_disabled", false);
+        assert!(!TimeLapse::new().log_first_n("test_log_first_n_disabled", 1));
+    }
+
+    #[test]
+    fn test_debug_profile() {
+        let value = debug_profile!(test_debug_profile, {
+            let profiler = "shadowed";
+            profiler.len()
+        });
+        assert_eq!(value, 8);
+        let text = debug_profile!(test_debug_profile, "expression".to_uppercase());
+        assert_eq!(text, "EXPRESSION");
+
+        let parse = |input: &str| -> Result<u32, std::num::ParseIntError> {
+            Ok(debug_profile!(test_debug_profile, input.parse::<u32>()?))
+        };
+        assert_eq!(parse("7"), Ok(7));
+        assert!(parse("nan").is_err());
+    }
+
+    #[test]
+    fn test_profile_into() {
+        let mut elapsed = Duration::MAX;
+        let mut inside = Duration::MAX;
+        let value = profile_into!(profiler, &mut elapsed, {
+            inside = profiler.elapsed();
+            6 * 7
+        });
+        assert_eq!(value, 42);
+        assert!(inside <= elapsed);
+        assert!(elapsed < Duration::from_secs(1));
+
+        let mut durations = [Duration::MAX; 2];
+        let text = profile_into!(profiler, &mut durations[1], "expression".to_uppercase());
+        assert_eq!(text, "EXPRESSION");
+        assert_eq!(durations[0], Duration::MAX);
+        assert!(durations[1] < Duration::from_secs(1));
+
+        let parse = |input: &str, elapsed: &mut Duration| -> Result<u32, std::num::ParseIntError> {
+            Ok(profile_into!(profiler, elapsed, input.parse::<u32>()?))
+        };
+        let mut elapsed = Duration::MAX;
+        assert!(parse("nan", &mut elapsed).is_err());
+        assert_eq!(elapsed, Duration::MAX);
+    }
+
+    #[test]
+    fn test_measurement_floor() {
+        let floor = TimeLapse::measurement_floor();
+        assert!(!floor.is_zero());
+        assert!(floor < Duration::from_millis(100));
+        assert_eq!(TimeLapse::measurement_floor(), floor);
+
+        let floor = Duration::from_nanos(40);
+        assert!(is_near_floor(Duration::ZERO, floor));
+        assert!(is_near_floor(Duration::from_nanos(199), floor));
+        assert!(!is_near_floor(Duration::from_nanos(200), floor));
+        assert!(!is_near_floor(Duration::from_millis(1), floor));
+    }
+
+    #[test]
+    fn test_adaptive_level() {
+        let thresholds = [
+            (Duration::ZERO, Level::Trace),
+            (Duration::from_millis(10), Level::Debug),
+            (Duration::from_millis(500), Level::Warn),
+        ];
+        let level = |ms| adaptive_level(Duration::from_millis(ms), &thresholds);
+        assert_eq!(level(0), Level::Trace);
+        assert_eq!(level(9), Level::Trace);
+        assert_eq!(level(10), Level::Debug);
+        assert_eq!(level(499), Level::Debug);
+        assert_eq!(level(2_000), Level::Warn);
+        assert_eq!(
+            adaptive_level(Duration::from_millis(5), &thresholds[1..]),
+            DEFAULT_ADAPTIVE_LEVEL
+        );
+        assert_eq!(
+            adaptive_level(Duration::from_secs(1), &[]),
+            DEFAULT_ADAPTIVE_LEVEL
+        );
+        TimeLapse::new().log_adaptive("test_log_adaptive", &thresholds);
+    }
+
+    #[test]
+    fn test_profiler_pause_resume() {
+        let (clock, mut profiler) = virtual_timer();
+        clock.advance(Duration::from_millis(30));
+        profiler.stop();
+        assert!(!profiler.is_running());
+        profiler.resume();
+        assert!(profiler.is_running());
+        assert_eq!(profiler.elapsed(), Duration::ZERO);
+        clock.advance(Duration::from_millis(5));
+        let paused = profiler.pause();
+        assert_eq!(paused, Duration::from_millis(5));
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(profiler.elapsed(), paused);
+        profiler.resume();
+        assert_eq!(profiler.elapsed(), paused);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(profiler.elapsed(), Duration::from_millis(10));
+        profiler.resume();
+        assert!(profiler.is_running());
+
+        let mut profiler = TimeLapse::uninit();
+        profiler.resume();
+        assert!(profiler.is_running());
+        profiler.start();
+        assert!(profiler.elapsed() < Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_profiler_sleep_timed() {
+        let mut profiler = TimeLapse::new();
+        profiler.sleep_timed(Duration::from_millis(100));
+        assert!(profiler.is_running());
+        assert!(profiler.elapsed() < TOLERANCE);
+
+        let paused = profiler.pause();
+        profiler.sleep_timed(Duration::from_millis(1));
+        assert!(profiler.is_paused());
+        assert_eq!(profiler.elapsed(), paused);
+    }
+
+    #[test]
+    fn test_profiler_state() {
+        let mut profiler = TimeLapse::uninit();
+        assert_eq!(profiler.state(), TimerState::Stopped);
+        assert!(!profiler.is_stopped());
+        profiler.start();
+        assert_eq!(profiler.state(), TimerState::Running);
+
+        let paused = profiler.pause();
+        assert_eq!(profiler.state(), TimerState::Paused);
+        assert!(profiler.is_paused() && !profiler.is_running() && !profiler.is_stopped());
+        assert_eq!(profiler.pause(), paused);
+        assert_eq!(profiler.stop(), paused);
+        assert_eq!(profiler.state(), TimerState::Stopped);
+        assert!(profiler.is_stopped() && !profiler.is_paused());
+        profiler.pause();
+        assert_eq!(profiler.state(), TimerState::Stopped);
+
+        let mut profiler = frozen(Duration::from_secs(10));
+        profiler.resume();
+        assert_eq!(profiler.state(), TimerState::Running);
+        assert!(profiler.elapsed() < Duration::from_secs(10));
+        profiler.pause();
         profiler.reset();
-        assert!(profiler.elapsed().as_millis() < 50);
+        assert_eq!(profiler.state(), TimerState::Running);
+    }
+
+    #[test]
+    fn test_profiler_deadline() {
+        let budget = Duration::from_millis(10);
+        let before = frozen(Duration::from_micros(9_999));
+        assert!(!before.deadline_reached(budget));
+        assert_eq!(before.remaining(budget), Duration::from_micros(1));
+        let at = frozen(budget);
+        assert!(at.deadline_reached(budget));
+        assert_eq!(at.remaining(budget), Duration::ZERO);
+        let after = frozen(Duration::from_millis(11));
+        assert!(after.deadline_reached(budget));
+        assert_eq!(after.remaining(budget), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_profiler_eta() {
+        let profiler = frozen(Duration::from_secs(10));
+        assert_eq!(profiler.eta(0.25), Duration::from_secs(30));
+        assert_eq!(profiler.eta(0.5), Duration::from_secs(10));
+        assert_eq!(profiler.eta(1.0), Duration::ZERO);
+        assert_eq!(profiler.eta(1.5), Duration::ZERO);
+        assert_eq!(profiler.eta(0.0), Duration::MAX);
+        assert_eq!(profiler.eta(-0.5), Duration::MAX);
+        assert_eq!(profiler.eta(f64::NAN), Duration::MAX);
+        assert_eq!(profiler.eta(f64::MIN_POSITIVE), Duration::MAX);
+    }
+
+    #[test]
+    fn test_min_loggable() {
+        // The global floor is left untouched, as the other tests expect everything to be logged.
+        assert!(is_loggable(Duration::ZERO));
+        let floor = floor_nanos(Duration::from_micros(1));
+        assert!(!reaches_floor(Duration::from_nanos(999), floor));
+        assert!(reaches_floor(Duration::from_micros(1), floor));
+        let floor = floor_nanos(Duration::MAX);
+        assert_eq!(floor, u64::MAX);
+        assert!(!reaches_floor(Duration::from_secs(3600), floor));
+        assert!(reaches_floor(Duration::ZERO, floor_nanos(Duration::ZERO)));
+    }
+
+    #[test]
+    fn test_log_on_new_max() {
+        let name = "test_log_on_new_max";
+        assert!(frozen(Duration::from_millis(10)).log_on_new_max(name));
+        assert!(!frozen(Duration::from_millis(5)).log_on_new_max(name));
+        assert!(!frozen(Duration::from_millis(10)).log_on_new_max(name));
+        assert!(frozen(Duration::from_millis(11)).log_on_new_max(name));
+        assert!(frozen(Duration::from_millis(5)).log_on_new_max("test_log_on_new_max_other"));
+
+        let workers: Vec<_> = (1..=8)
+            .map(|ms| {
+                std::thread::spawn(move || {
+                    frozen(Duration::from_millis(100 + ms)).log_on_new_max(name);
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert!(!frozen(Duration::from_millis(108)).log_on_new_max(name));
+        assert!(frozen(Duration::from_millis(109)).log_on_new_max(name));
+
+        let disabled = "test_log_on_new_max_disabled";
+        crate::registry::set_region_enabled(disabled, false);
+        assert!(!frozen(Duration::from_millis(10)).log_on_new_max(disabled));
+        crate::registry::set_region_enabled(disabled, true);
+        assert!(!frozen(Duration::from_millis(10)).log_on_new_max(disabled));
+        assert!(frozen(Duration::from_millis(11)).log_on_new_max(disabled));
     }
 }
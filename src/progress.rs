@@ -0,0 +1,292 @@
+//! Live elapsed time feedback for long profiled regions.
+//!
+//! This module provides the `ProgressGuard` struct. While it is alive, a background thread
+//! rewrites a single line on the standard error with the label and the time elapsed so far
+//! (e.g. `loading: still working... 3.2s`). The line is cleared when the guard is dropped.
+//!
+//! The guard only engages when the standard error is a terminal. Otherwise it does nothing,
+//! so logs and CI outputs are not cluttered with carriage returns.
+//!
+//! For headless jobs, such as cron or CI ones, the `ProgressTimer` struct logs the progress
+//! of a batch instead, as lines with the count of processed items, the elapsed time and the
+//! estimated time left, whenever asked to.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::format::human;
+use crate::logging::{log, Level};
+use crate::TimeLapse;
+
+/// Default interval between two updates of the progress line.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The `ProgressGuard` struct displays a live elapsed time counter until dropped.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::progress::ProgressGuard;
+///
+/// {
+///     let _progress = ProgressGuard::new("loading");
+///     // ... long task ...
+/// } // the progress line is cleared here
+/// ```
+#[must_use = "the progress line is cleared when the guard is dropped"]
+pub struct ProgressGuard {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProgressGuard {
+    /// Creates a new `ProgressGuard` updating the progress line every 100ms.
+    pub fn new(label: impl Into<String>) -> Self {
+        Self::with_interval(label, DEFAULT_INTERVAL)
+    }
+
+    /// Creates a new `ProgressGuard` updating the progress line at the given interval.
+    pub fn with_interval(label: impl Into<String>, interval: Duration) -> Self {
+        if std::io::stderr().is_terminal() {
+            Self::spawn(label.into(), interval, Box::new(std::io::stderr()))
+        } else {
+            ProgressGuard {
+                stop: Arc::default(),
+                thread: None,
+            }
+        }
+    }
+
+    /// Starts the thread writing the progress line to `output`.
+    fn spawn(label: String, interval: Duration, mut output: Box<dyn Write + Send>) -> Self {
+        let stop: Arc<(Mutex<bool>, Condvar)> = Arc::default();
+        let thread = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let timer = TimeLapse::new();
+                let (stopped, wakeup) = &*stop;
+                let mut stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+                while !*stopped {
+                    let _ = write!(
+                        output,
+                        "\r{}: still working... {:.1}s",
+                        label,
+                        timer.elapsed().as_secs_f64()
+                    );
+                    let _ = output.flush();
+                    stopped = wakeup
+                        .wait_timeout(stopped, interval)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+                let _ = write!(output, "\r\x1b[2K");
+                let _ = output.flush();
+            })
+        };
+        ProgressGuard {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns `true` if the progress line is displayed, i.e. the standard error is a terminal.
+    pub fn is_active(&self) -> bool {
+        self.thread.is_some()
+    }
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let (stopped, wakeup) = &*self.stop;
+            *stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+            wakeup.notify_one();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The `ProgressTimer` struct tracks the progress of a batch of items, and logs it with its
+/// estimated time left (see `TimeLapse::eta()`). The processed count can be advanced from
+/// several threads through a shared reference.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::progress::ProgressTimer;
+///
+/// let progress = ProgressTimer::new(10_000);
+/// for item in 0..10_000 {
+///     // ... process the item ...
+///     progress.inc(1);
+///     if item % 1_000 == 0 {
+///         progress.report_progress(); // "processed 4200/10000 (42%), elapsed 30.00s, ETA 41.43s"
+///     }
+/// }
+/// assert_eq!(progress.processed(), 10_000);
+/// ```
+pub struct ProgressTimer {
+    total: u64,
+    processed: AtomicU64,
+    timer: TimeLapse,
+}
+
+impl ProgressTimer {
+    /// Creates a new `ProgressTimer` for the given total number of items, started at the
+    /// current time.
+    pub fn new(total: u64) -> Self {
+        ProgressTimer {
+            total,
+            processed: AtomicU64::new(0),
+            timer: TimeLapse::new(),
+        }
+    }
+
+    /// Adds `count` items to the processed count.
+    pub fn inc(&self, count: u64) {
+        self.processed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Sets the processed count.
+    pub fn set_processed(&self, processed: u64) {
+        self.processed.store(processed, Ordering::Relaxed);
+    }
+
+    /// Returns the number of processed items.
+    pub fn processed(&self) -> u64 {
+        self.processed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total number of items.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the elapsed time since the creation of the `ProgressTimer`.
+    pub fn elapsed(&self) -> Duration {
+        self.timer.elapsed()
+    }
+
+    /// Returns the progress line, e.g.
+    /// `processed 4200/10000 (42%), elapsed 30.00s, ETA 41.43s`. The ETA is `unknown` until an
+    /// item is processed. An empty batch is complete.
+    pub fn message(&self) -> String {
+        let processed = self.processed();
+        let fraction = if self.total == 0 {
+            1.0
+        } else {
+            processed as f64 / self.total as f64
+        };
+        render(
+            processed,
+            self.total,
+            self.elapsed(),
+            self.timer.eta(fraction),
+        )
+    }
+
+    /// Logs the progress line at the info level.
+    pub fn report_progress(&self) {
+        self.report_progress_with_level(Level::Info);
+    }
+
+    /// Logs the progress line at the given level. Having no name, the progress line is not
+    /// subject to the floor of `TimeLapse::set_min_loggable()` nor to the region settings.
+    pub fn report_progress_with_level(&self, level: Level) {
+        log!(level, "{}", self.message());
+    }
+}
+
+/// Renders the progress line of a `ProgressTimer`.
+fn render(processed: u64, total: u64, elapsed: Duration, eta: Duration) -> String {
+    let percent = match total {
+        0 => 100,
+        _ => u128::from(processed) * 100 / u128::from(total),
+    };
+    let eta = match eta {
+        Duration::MAX => "unknown".to_string(),
+        eta => human(eta),
+    };
+    format!(
+        "processed {}/{} ({}%), elapsed {}, ETA {}",
+        processed,
+        total,
+        percent,
+        human(elapsed),
+        eta
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer appending to a shared buffer.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_progress_output() {
+        let buffer = Buffer::default();
+        let guard = ProgressGuard::spawn(
+            "task".to_string(),
+            Duration::from_millis(5),
+            Box::new(buffer.clone()),
+        );
+        assert!(guard.is_active());
+        std::thread::sleep(Duration::from_millis(30));
+        drop(guard);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(output.starts_with("\rtask: still working... 0.0s"));
+        assert!(output.matches("still working").count() > 1);
+        assert!(output.ends_with("\r\x1b[2K"));
+    }
+
+    #[test]
+    fn test_progress_timer() {
+        assert_eq!(
+            render(
+                4_200,
+                10_000,
+                Duration::from_secs(30),
+                Duration::from_secs(41)
+            ),
+            "processed 4200/10000 (42%), elapsed 30.00s, ETA 41.00s"
+        );
+        assert_eq!(
+            render(0, 10, Duration::from_secs(1), Duration::MAX),
+            "processed 0/10 (0%), elapsed 1.00s, ETA unknown"
+        );
+        assert_eq!(
+            render(0, 0, Duration::ZERO, Duration::ZERO),
+            "processed 0/0 (100%), elapsed 0ns, ETA 0ns"
+        );
+
+        let progress = ProgressTimer::new(8);
+        assert!(progress.message().ends_with("ETA unknown"));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| progress.inc(1));
+            }
+        });
+        assert_eq!(progress.processed(), 4);
+        assert!(progress.message().starts_with("processed 4/8 (50%)"));
+        progress.set_processed(8);
+        assert!(progress.message().ends_with("ETA 0ns"));
+        progress.report_progress();
+        assert_eq!(ProgressTimer::new(0).total(), 0);
+    }
+}
@@ -0,0 +1,116 @@
+//! Live elapsed time on an `indicatif` progress bar.
+//!
+//! This module, available with the `indicatif` feature, provides the `ProgressBarTimer`
+//! struct. While it is alive, a background thread sets the message of a given `ProgressBar`
+//! to the time elapsed so far. The bar is finished, with the final elapsed time as message,
+//! when the timer is dropped.
+
+use indicatif::ProgressBar;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::format::human;
+use crate::TimeLapse;
+
+/// Default interval between two updates of the progress bar message.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The `ProgressBarTimer` struct shows the live elapsed time on a progress bar until dropped.
+///
+/// # Usage
+/// ```rust
+/// use indicatif::ProgressBar;
+/// use timelapse::progress_bar::ProgressBarTimer;
+///
+/// let bar = ProgressBar::new(100);
+/// {
+///     let _timer = ProgressBarTimer::new(bar.clone());
+///     for _ in 0..100 {
+///         // ... step of the long task ...
+///         bar.inc(1);
+///     }
+/// } // the bar is finished here
+/// assert!(bar.is_finished());
+/// ```
+#[must_use = "the progress bar is finished when the timer is dropped"]
+pub struct ProgressBarTimer {
+    bar: ProgressBar,
+    timer: TimeLapse,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProgressBarTimer {
+    /// Creates a new `ProgressBarTimer` updating the message of the bar every 100ms.
+    pub fn new(bar: ProgressBar) -> Self {
+        Self::with_interval(bar, DEFAULT_INTERVAL)
+    }
+
+    /// Creates a new `ProgressBarTimer` updating the message of the bar at the given interval.
+    pub fn with_interval(bar: ProgressBar, interval: Duration) -> Self {
+        let timer = TimeLapse::new();
+        let stop: Arc<(Mutex<bool>, Condvar)> = Arc::default();
+        let thread = {
+            let bar = bar.clone();
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let timer = TimeLapse::new();
+                let (stopped, wakeup) = &*stop;
+                let mut stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+                while !*stopped {
+                    bar.set_message(human(timer.elapsed()));
+                    stopped = wakeup
+                        .wait_timeout(stopped, interval)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            })
+        };
+        ProgressBarTimer {
+            bar,
+            timer,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the progress bar.
+    pub fn bar(&self) -> &ProgressBar {
+        &self.bar
+    }
+
+    /// Returns the elapsed time since the creation of the timer.
+    pub fn elapsed(&self) -> Duration {
+        self.timer.elapsed()
+    }
+}
+
+impl Drop for ProgressBarTimer {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let (stopped, wakeup) = &*self.stop;
+            *stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+            wakeup.notify_one();
+            let _ = thread.join();
+        }
+        self.bar.finish_with_message(human(self.timer.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_bar_timer() {
+        let bar = ProgressBar::hidden();
+        let timer = ProgressBarTimer::with_interval(bar.clone(), Duration::from_millis(5));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!bar.message().is_empty());
+        assert!(!bar.is_finished());
+        drop(timer);
+        assert!(bar.is_finished());
+        assert!(bar.message().ends_with("ms"));
+    }
+}
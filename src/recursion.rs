@@ -0,0 +1,205 @@
+//! Profiling of recursive functions by recursion depth.
+//!
+//! This module provides the `enter()` function, returning a guard to take at the start of a
+//! recursive function. Each thread keeps the current recursion depth of every name: a guard
+//! increments it when created and decrements it when dropped, including when unwinding from a
+//! panic. The elapsed time of each call is accumulated per name and per depth, in a
+//! process-wide table fed by all threads.
+//!
+//! The time of a call includes the time of the nested calls, so the total at depth 0 is the
+//! total time spent in the function from its outermost calls.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::marker::PhantomData;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static DEPTHS: RefCell<HashMap<&'static str, usize>> = RefCell::new(HashMap::new());
+}
+
+static STATS: OnceLock<Mutex<HashMap<&'static str, Vec<DepthStats>>>> = OnceLock::new();
+
+/// Locks the per-depth statistics.
+fn stats_table() -> MutexGuard<'static, HashMap<&'static str, Vec<DepthStats>>> {
+    STATS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The `DepthStats` struct holds the accumulated elapsed time and number of calls at a depth.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepthStats {
+    total: Duration,
+    calls: u64,
+}
+
+impl DepthStats {
+    /// Returns the total elapsed time of the calls, nested calls included.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the number of calls.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+}
+
+/// Returns a guard recording the elapsed time of the current call of a recursive function
+/// under the given name, at the current recursion depth of that name on this thread.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::recursion;
+///
+/// fn fib(n: u64) -> u64 {
+///     let _guard = recursion::enter("doc_fib");
+///     if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+/// }
+///
+/// fib(5);
+/// assert_eq!(recursion::max_depth("doc_fib"), Some(4));
+/// println!("{}", recursion::report("doc_fib")); // depth 0: 15µs, depth 1: 12µs (×2 calls), ...
+/// ```
+pub fn enter(name: &'static str) -> RecursionGuard {
+    let depth = DEPTHS.with(|depths| {
+        let mut depths = depths.borrow_mut();
+        let depth = depths.entry(name).or_default();
+        *depth += 1;
+        *depth - 1
+    });
+    RecursionGuard {
+        name,
+        depth,
+        start: Instant::now(),
+        _not_send: PhantomData,
+    }
+}
+
+/// Returns the current recursion depth of a name on this thread: the number of its guards
+/// alive.
+pub fn depth(name: &str) -> usize {
+    DEPTHS.with(|depths| depths.borrow().get(name).copied().unwrap_or(0))
+}
+
+/// Returns the statistics of a name for each depth reached, from depth 0.
+pub fn stats(name: &str) -> Vec<DepthStats> {
+    stats_table().get(name).cloned().unwrap_or_default()
+}
+
+/// Returns the maximum recursion depth reached by a name, 0 being a call without recursion,
+/// or `None` if nothing was recorded under it.
+pub fn max_depth(name: &str) -> Option<usize> {
+    stats_table().get(name).map(|depths| depths.len() - 1)
+}
+
+/// Returns a one-line report of the statistics of a name, e.g.
+/// `depth 0: 10ms, depth 1: 40ms (×8 calls)`.
+pub fn report(name: &str) -> String {
+    let mut report = String::new();
+    for (depth, stats) in stats(name).iter().enumerate() {
+        if depth > 0 {
+            report.push_str(", ");
+        }
+        let _ = write!(report, "depth {}: {:?}", depth, stats.total);
+        if stats.calls > 1 {
+            let _ = write!(report, " (×{} calls)", stats.calls);
+        }
+    }
+    report
+}
+
+/// Removes the statistics of all names.
+pub fn clear() {
+    stats_table().clear();
+}
+
+/// The `RecursionGuard` struct records the elapsed time of a call at its recursion depth when
+/// dropped. It is created by `enter()`.
+#[must_use = "the call is recorded when the guard is dropped"]
+pub struct RecursionGuard {
+    name: &'static str,
+    depth: usize,
+    start: Instant,
+    // The guard is tied to the depth counters of the thread that created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl RecursionGuard {
+    /// Returns the recursion depth of the call, 0 for the outermost one.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl Drop for RecursionGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        DEPTHS.with(|depths| {
+            depths.borrow_mut().insert(self.name, self.depth);
+        });
+        let mut table = stats_table();
+        let depths = table.entry(self.name).or_default();
+        if depths.len() <= self.depth {
+            depths.resize(self.depth + 1, DepthStats::default());
+        }
+        let stats = &mut depths[self.depth];
+        stats.total += elapsed;
+        stats.calls += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn countdown(name: &'static str, n: u32) {
+        let guard = enter(name);
+        assert_eq!(guard.depth(), depth(name) - 1);
+        if n > 0 {
+            countdown(name, n - 1);
+            countdown(name, n - 1);
+        }
+    }
+
+    #[test]
+    fn test_recursion_depths() {
+        assert_eq!(max_depth("test_recursion_depths"), None);
+        countdown("test_recursion_depths", 3);
+        assert_eq!(depth("test_recursion_depths"), 0);
+        assert_eq!(max_depth("test_recursion_depths"), Some(3));
+
+        let stats = stats("test_recursion_depths");
+        let calls: Vec<u64> = stats.iter().map(DepthStats::calls).collect();
+        assert_eq!(calls, [1, 2, 4, 8]);
+        assert!(stats[0].total() >= stats[1].total());
+
+        let report = report("test_recursion_depths");
+        assert!(report.starts_with("depth 0: "));
+        assert!(report.contains(" (×8 calls)"));
+        assert!(!report.contains("(×1 calls)"));
+    }
+
+    #[test]
+    fn test_recursion_panic() {
+        fn failing(n: u32) {
+            let _guard = enter("test_recursion_panic");
+            if n == 0 {
+                panic!("bottom reached");
+            }
+            failing(n - 1);
+        }
+
+        assert!(std::panic::catch_unwind(|| failing(2)).is_err());
+        assert_eq!(depth("test_recursion_panic"), 0);
+        let calls: Vec<u64> = stats("test_recursion_panic")
+            .iter()
+            .map(DepthStats::calls)
+            .collect();
+        assert_eq!(calls, [1, 1, 1]);
+    }
+}
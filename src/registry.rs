@@ -0,0 +1,833 @@
+//! A global registry accumulating elapsed times by name.
+//!
+//! This module provides a process-wide registry where elapsed times are added under a name,
+//! keeping the total time and the number of recordings for each name. It is thread-safe and
+//! can be fed from anywhere in the program.
+//!
+//! Names can be split into a group and a label with the `_in` variants, the entries being
+//! then stored as `group::label`. The `group_report()` function rolls the entries up by group.
+//!
+//! Reports are deterministic: `report()` and `group_report()` are sorted by name, and
+//! `report_by_time()` by descending elapsed time.
+//!
+//! The `scope()` function returns a guard recording its elapsed time into the registry when
+//! dropped, including when unwinding from a panic.
+//!
+//! The `snapshot()` function freezes the registry into a `RegistrySnapshot`, and two of them
+//! can be diffed to get the time accumulated between them, e.g. during one phase of the
+//! program, while the registry keeps being fed by everything else.
+//!
+//! Regions can be disabled and enabled again at runtime by name with `set_region_enabled()`,
+//! e.g. from an admin command, to zoom into the timings of one subsystem: the logging,
+//! printing and reporting of `TimeLapse` and of the macros skip the disabled regions.
+//!
+//! With `set_overhead_tracking()`, the time spent in the global reporter is accumulated, and
+//! returned by `profiling_overhead()`, to tell whether the instrumentation is too heavy.
+
+use std::collections::{btree_map, BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::profiler::qualified_name;
+use crate::TimeLapse;
+
+static REGISTRY: OnceLock<Mutex<BTreeMap<String, Entry>>> = OnceLock::new();
+
+/// Locks the registry. A registry poisoned by a panic is still usable, as entries are only
+/// updated once the new values are fully computed.
+fn entries() -> MutexGuard<'static, BTreeMap<String, Entry>> {
+    REGISTRY
+        .get_or_init(|| Mutex::new(BTreeMap::new()))
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The `Entry` struct holds the accumulated elapsed time and recording count of a name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    total: Duration,
+    count: u64,
+    group_len: Option<usize>,
+}
+
+impl Entry {
+    /// Returns the total elapsed time recorded.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the number of recordings.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Adds a recording of an elapsed time. The total and count saturate instead of
+    /// overflowing.
+    pub(crate) fn add(&mut self, elapsed: Duration) {
+        self.total = self.total.saturating_add(elapsed);
+        self.count = self.count.saturating_add(1);
+    }
+}
+
+/// Adds an elapsed time to an entry, `group_len` being the length of the group prefix in
+/// `name` for entries recorded with a group.
+fn record_entry(name: &str, group_len: Option<usize>, elapsed: Duration) {
+    let mut entries = entries();
+    let entry = match entries.get_mut(name) {
+        Some(entry) => entry,
+        None => entries.entry(name.to_string()).or_default(),
+    };
+    entry.add(elapsed);
+    entry.group_len = entry.group_len.or(group_len);
+}
+
+/// The `timers!` macro declares a set of region names as `&'static str` constants, so that
+/// the names used with the registry and `TimeLapse` are checked at compile time instead of
+/// being repeated as string literals.
+///
+/// Each constant holds its own identifier as written, e.g. `PARSE` is `"PARSE"` and shows as
+/// `PARSE` in the reports. A different name can be given with `= "name"`, e.g. to follow the
+/// `group::label` convention. Attributes, such as doc comments, and a visibility can precede
+/// each name.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::{registry, timers};
+///
+/// timers! {
+///     PARSE,
+///     /// The code generation.
+///     pub BUILD,
+///     EMIT = "codegen::emit",
+/// }
+///
+/// assert_eq!(PARSE, "PARSE");
+/// registry::record(BUILD, Duration::from_millis(5));
+/// registry::record(EMIT, Duration::from_millis(2));
+/// assert_eq!(registry::get("BUILD").unwrap().count(), 1);
+/// assert_eq!(registry::get_in("codegen", "emit").unwrap().count(), 1);
+/// ```
+///
+/// With `enum Name { ... }`, the macro instead declares a unit enum with the given variants,
+/// implementing the `TimerKey` trait for the `TypedRegistry` struct of the `typed_registry`
+/// module, each variant being named after its identifier.
+#[macro_export]
+macro_rules! timers {
+    ($(#[$attr:meta])* $vis:vis enum $name:ident {
+        $($(#[$variant_attr:meta])* $variant:ident),* $(,)?
+    }) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $($(#[$variant_attr])* $variant),*
+        }
+
+        impl $crate::typed_registry::TimerKey for $name {
+            const ALL: &'static [Self] = &[$($name::$variant),*];
+
+            fn index(self) -> usize {
+                self as usize
+            }
+
+            fn name(self) -> &'static str {
+                match self {
+                    $($name::$variant => stringify!($variant)),*
+                }
+            }
+        }
+    };
+    (@name $name:ident) => {
+        stringify!($name)
+    };
+    (@name $name:ident = $value:expr) => {
+        $value
+    };
+    ($($(#[$attr:meta])* $vis:vis $name:ident $(= $value:expr)?),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            $vis const $name: &str = $crate::timers!(@name $name $(= $value)?);
+        )*
+    };
+}
+
+/// Adds an elapsed time to the registry under the given name.
+pub fn record(name: &str, elapsed: Duration) {
+    record_entry(name, None, elapsed);
+}
+
+/// Adds an elapsed time to the registry under the given group and label, stored as
+/// `group::label`.
+pub fn record_in(group: &str, label: &str, elapsed: Duration) {
+    record_entry(&qualified_name(group, label), Some(group.len()), elapsed);
+}
+
+/// Returns the accumulated entry for the given name, if anything was recorded under it.
+pub fn get(name: &str) -> Option<Entry> {
+    entries().get(name).copied()
+}
+
+/// Returns the accumulated entry for the given group and label, if anything was recorded
+/// under it.
+pub fn get_in(group: &str, label: &str) -> Option<Entry> {
+    get(&qualified_name(group, label))
+}
+
+/// Returns the total elapsed time of every name in the registry, sorted by name.
+pub fn report() -> Vec<(String, Duration)> {
+    entries()
+        .iter()
+        .map(|(name, entry)| (name.clone(), entry.total))
+        .collect()
+}
+
+/// Calls a function with an iterator over the entries of the registry, sorted by name, and
+/// returns its result. The entries are borrowed from the registry, so nothing is cloned or
+/// allocated, unlike `report()`.
+///
+/// The registry stays locked while the function runs: the recordings of the other threads wait
+/// for it to return, so it should be short. The function must not call back into the registry,
+/// including by dropping a `ScopeGuard`, which would deadlock.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::registry;
+///
+/// registry::record("doc_with_entries", Duration::from_millis(3));
+/// let slowest = registry::with_entries(|entries| {
+///     entries.map(|(_, entry)| entry.total()).max()
+/// });
+/// assert!(slowest >= Some(Duration::from_millis(3)));
+/// ```
+pub fn with_entries<R>(f: impl FnOnce(Entries<'_>) -> R) -> R {
+    let entries = entries();
+    f(Entries(entries.iter()))
+}
+
+/// The `Entries` struct is an iterator over the names and entries of the registry, sorted by
+/// name. It is given by `with_entries()`.
+pub struct Entries<'a>(btree_map::Iter<'a, String, Entry>);
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = (&'a str, &'a Entry);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Returns the total elapsed time of every name in the registry, sorted by descending elapsed
+/// time, ties being broken by name.
+pub fn report_by_time() -> Vec<(String, Duration)> {
+    let mut report = report();
+    report.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    report
+}
+
+/// Returns the name and total elapsed time of the entry with the largest total, with its
+/// fraction of the grand total of all entries (from 0.0 to 1.0, zero if the grand total is
+/// zero), or `None` if the registry is empty. Ties are broken by name.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::registry;
+///
+/// registry::record("doc_hottest", Duration::from_secs(3600));
+/// if let Some((name, total, fraction)) = registry::hottest() {
+///     let calls = registry::get(&name).map_or(0, |entry| entry.count());
+///     println!(
+///         "hottest: {} took {:.0}% of total ({:?} across {} calls)",
+///         name,
+///         fraction * 100.0,
+///         total,
+///         calls
+///     );
+/// }
+/// ```
+pub fn hottest() -> Option<(String, Duration, f64)> {
+    let entries = entries();
+    let grand_total: Duration = entries.values().map(|entry| entry.total).sum();
+    let (name, entry) = entries.iter().rev().max_by_key(|(_, entry)| entry.total)?;
+    let fraction = if grand_total.is_zero() {
+        0.0
+    } else {
+        entry.total.as_secs_f64() / grand_total.as_secs_f64()
+    };
+    Some((name.clone(), entry.total, fraction))
+}
+
+/// Returns a copy of all the entries of the registry, to diff with a later one.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::registry;
+///
+/// registry::record("doc_snapshot", Duration::from_millis(10));
+/// let before = registry::snapshot();
+/// // ... the phase of interest ...
+/// registry::record("doc_snapshot", Duration::from_millis(5));
+/// let phase = before.diff(&registry::snapshot());
+/// assert!(phase.contains(&("doc_snapshot".to_string(), Duration::from_millis(5))));
+/// ```
+pub fn snapshot() -> RegistrySnapshot {
+    RegistrySnapshot(entries().clone())
+}
+
+/// The `RegistrySnapshot` struct holds the entries of the registry at the time of `snapshot()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistrySnapshot(BTreeMap<String, Entry>);
+
+impl RegistrySnapshot {
+    /// Returns the entry for the given name at the time of the snapshot, if any.
+    pub fn get(&self, name: &str) -> Option<Entry> {
+        self.0.get(name).copied()
+    }
+
+    /// Returns the number of names in the snapshot.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the registry was empty at the time of the snapshot.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the elapsed time recorded under every name between this snapshot and a later
+    /// one, sorted by name. Only the names recorded in between are returned: the names found
+    /// only in the later snapshot come with their full total.
+    ///
+    /// A name with fewer recordings or a smaller total in the later snapshot was cleared in
+    /// between, and also comes with its full later total.
+    pub fn diff(&self, later: &RegistrySnapshot) -> Vec<(String, Duration)> {
+        later
+            .0
+            .iter()
+            .filter_map(|(name, entry)| {
+                let delta = match self.0.get(name) {
+                    Some(earlier) if earlier == entry => return None,
+                    Some(earlier)
+                        if earlier.count <= entry.count && earlier.total <= entry.total =>
+                    {
+                        entry.total - earlier.total
+                    }
+                    _ => entry.total,
+                };
+                Some((name.clone(), delta))
+            })
+            .collect()
+    }
+}
+
+/// Returns the total elapsed time of every group in the registry, sorted by group. Only the entries recorded
+/// with a group (`record_in()` and `scope_in()`) are rolled up.
+pub fn group_report() -> Vec<(String, Duration)> {
+    let mut groups: BTreeMap<&str, Duration> = BTreeMap::new();
+    let entries = entries();
+    for (name, entry) in entries.iter() {
+        if let Some(len) = entry.group_len {
+            *groups.entry(&name[..len]).or_default() += entry.total;
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(group, total)| (group.to_string(), total))
+        .collect()
+}
+
+/// Returns the total elapsed time of every name in the registry, sorted by name, and removes
+/// all entries, in a single locked operation. Nothing recorded concurrently is lost: a
+/// recording lands either in the returned report or in the registry for the next one.
+pub fn drain_report() -> Vec<(String, Duration)> {
+    std::mem::take(&mut *entries())
+        .into_iter()
+        .map(|(name, entry)| (name, entry.total))
+        .collect()
+}
+
+/// Returns once the measurements reported so far are delivered by the global reporter, e.g.
+/// the reports still queued by a `reporter::AsyncReporter`, to call at shutdown. This is
+/// `reporter::flush()`, and covers the measurements going through the global reporter, i.e.
+/// `TimeLapse::report()` and `profile_end!`: `TimeLapse::log()` logs from the calling thread.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::reporter::{set_reporter, AsyncReporter, LogReporter};
+/// use timelapse::{registry, TimeLapse};
+///
+/// set_reporter(AsyncReporter::new(LogReporter::default(), 1024));
+/// TimeLapse::new().report("doc_flush"); // only queues the report
+/// registry::flush();
+/// ```
+pub fn flush() {
+    crate::reporter::flush();
+}
+
+/// Removes all entries from the registry.
+pub fn clear() {
+    entries().clear();
+}
+
+/// The setting of the regions with no setting of their own, see `set_enabled()`.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// The settings of the regions set with `set_region_enabled()`.
+static REGIONS: OnceLock<RwLock<HashMap<String, bool>>> = OnceLock::new();
+
+/// Enables or disables all the regions with no setting of their own. They are enabled by
+/// default.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Enables or disables the region with the given name, overriding the setting of
+/// `set_enabled()`. The setting of a group (e.g. `db`) applies to the regions of the group
+/// (e.g. `db::query`) with no setting of their own.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{registry, Level, TimeLapse};
+///
+/// registry::set_region_enabled("doc_render", false);
+/// let profiler = TimeLapse::new();
+/// // ... rendering ...
+/// profiler.log("doc_render", Level::Info); // skipped
+/// assert!(!registry::is_region_enabled("doc_render"));
+/// ```
+pub fn set_region_enabled(name: &str, enabled: bool) {
+    REGIONS
+        .get_or_init(|| RwLock::new(HashMap::new()))
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name.to_string(), enabled);
+}
+
+/// Removes the setting of the region with the given name, which follows again the setting of
+/// its group or of `set_enabled()`.
+pub fn clear_region_enabled(name: &str) {
+    if let Some(regions) = REGIONS.get() {
+        regions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(name);
+    }
+}
+
+/// Returns `true` if the region with the given name is enabled: by its own setting, or else
+/// by the setting of its closest group, or else by the setting of `set_enabled()`.
+pub fn is_region_enabled(name: &str) -> bool {
+    REGIONS
+        .get()
+        .and_then(|regions| {
+            region_setting(
+                &regions.read().unwrap_or_else(PoisonError::into_inner),
+                name,
+            )
+        })
+        .unwrap_or_else(|| ENABLED.load(Ordering::Relaxed))
+}
+
+/// Returns the setting of a region, or else of its closest group, if any.
+fn region_setting(regions: &HashMap<String, bool>, mut name: &str) -> Option<bool> {
+    loop {
+        if let Some(enabled) = regions.get(name) {
+            return Some(*enabled);
+        }
+        name = &name[..name.rfind("::")?];
+    }
+}
+
+/// Whether the time spent in the global reporter is accumulated, see
+/// `set_overhead_tracking()`.
+static OVERHEAD_TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// The time spent in the global reporter, in nanoseconds, and its number of calls.
+static OVERHEAD_NANOS: AtomicU64 = AtomicU64::new(0);
+static OVERHEAD_CALLS: AtomicU64 = AtomicU64::new(0);
+
+/// Enables or disables the accumulation of the time spent in the global reporter by
+/// `TimeLapse::report()` and `profile_end!`. It is disabled by default, and then costs a
+/// single atomic load per report. Once enabled, each report also reads the clock twice.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{registry, TimeLapse};
+///
+/// registry::set_overhead_tracking(true);
+/// for _ in 0..100 {
+///     TimeLapse::new().report("doc_overhead");
+/// }
+/// let overhead = registry::profiling_overhead();
+/// println!("{:?} in {} reports", overhead.total(), overhead.count());
+/// registry::set_overhead_tracking(false);
+/// ```
+pub fn set_overhead_tracking(enabled: bool) {
+    OVERHEAD_TRACKING.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns the time spent in the global reporter while `set_overhead_tracking()` was enabled,
+/// and the number of reports, since the start of the program.
+pub fn profiling_overhead() -> Entry {
+    Entry {
+        total: Duration::from_nanos(OVERHEAD_NANOS.load(Ordering::Relaxed)),
+        count: OVERHEAD_CALLS.load(Ordering::Relaxed),
+        group_len: None,
+    }
+}
+
+/// Runs `f`, the dispatch of a report, adding its duration to the profiling overhead if
+/// tracked.
+pub(crate) fn track_overhead<R>(f: impl FnOnce() -> R) -> R {
+    if !OVERHEAD_TRACKING.load(Ordering::Relaxed) {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+    OVERHEAD_NANOS.fetch_add(nanos, Ordering::Relaxed);
+    OVERHEAD_CALLS.fetch_add(1, Ordering::Relaxed);
+    result
+}
+
+/// Returns a guard that records its elapsed time into the registry under the given name
+/// when dropped.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::registry;
+///
+/// fn parse() {
+///     let _guard = registry::scope("doc_parse");
+///     // ... work ...
+/// }
+///
+/// parse();
+/// parse();
+/// assert_eq!(registry::get("doc_parse").unwrap().count(), 2);
+/// ```
+pub fn scope(name: impl Into<String>) -> ScopeGuard {
+    ScopeGuard {
+        name: name.into(),
+        group_len: None,
+        timer: TimeLapse::new(),
+    }
+}
+
+/// Returns a guard that records its elapsed time into the registry under the given group and
+/// label when dropped.
+pub fn scope_in(group: &str, label: &str) -> ScopeGuard {
+    ScopeGuard {
+        name: qualified_name(group, label),
+        group_len: Some(group.len()),
+        timer: TimeLapse::new(),
+    }
+}
+
+/// The `ScopeGuard` struct records the time elapsed since its creation into the registry when
+/// dropped. It is created by `scope()` or `scope_in()`.
+#[must_use = "the elapsed time is recorded when the guard is dropped"]
+pub struct ScopeGuard {
+    name: String,
+    group_len: Option<usize>,
+    timer: TimeLapse,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        record_entry(&self.name, self.group_len, self.timer.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serializes the tests, as `drain_report()` empties the registry shared by all of them.
+    fn serial() -> MutexGuard<'static, ()> {
+        static SERIAL: Mutex<()> = Mutex::new(());
+        SERIAL.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[test]
+    fn test_registry_record() {
+        let _serial = serial();
+        record("test_registry_record", Duration::from_millis(10));
+        record("test_registry_record", Duration::from_millis(5));
+        let entry = get("test_registry_record").unwrap();
+        assert_eq!(entry.total(), Duration::from_millis(15));
+        assert_eq!(entry.count(), 2);
+        assert!(report().contains(&("test_registry_record".to_string(), entry.total())));
+        assert_eq!(get("test_registry_unknown"), None);
+    }
+
+    #[test]
+    fn test_entry_saturation() {
+        let mut entry = Entry {
+            total: Duration::MAX,
+            count: u64::MAX,
+            group_len: None,
+        };
+        entry.add(Duration::from_secs(1));
+        assert_eq!(entry.total(), Duration::MAX);
+        assert_eq!(entry.count(), u64::MAX);
+    }
+
+    #[test]
+    fn test_profiling_overhead() {
+        let _serial = serial();
+        let before = profiling_overhead();
+        set_overhead_tracking(true);
+        for _ in 0..3 {
+            TimeLapse::new().report("test_profiling_overhead");
+        }
+        set_overhead_tracking(false);
+        let after = profiling_overhead();
+        assert!(after.count() >= before.count() + 3);
+        assert!(after.total() > before.total());
+        assert_eq!(track_overhead(|| 42), 42);
+    }
+
+    #[test]
+    fn test_registry_timers() {
+        let _serial = serial();
+        timers! {
+            TEST_REGISTRY_TIMERS_PARSE,
+            TEST_REGISTRY_TIMERS_EMIT = "test_registry_timers::emit",
+        }
+        assert_eq!(TEST_REGISTRY_TIMERS_PARSE, "TEST_REGISTRY_TIMERS_PARSE");
+        record(TEST_REGISTRY_TIMERS_PARSE, Duration::from_millis(1));
+        drop(scope(TEST_REGISTRY_TIMERS_EMIT));
+        assert!(get("TEST_REGISTRY_TIMERS_PARSE").is_some());
+        assert!(get_in("test_registry_timers", "emit").is_some());
+    }
+
+    #[test]
+    fn test_registry_groups() {
+        let _serial = serial();
+        record_in("test_registry_groups", "parse", Duration::from_millis(10));
+        record_in("test_registry_groups", "build", Duration::from_millis(20));
+        drop(scope_in("test_registry_groups", "emit"));
+        record("test_registry_groups::ungrouped", Duration::from_millis(40));
+
+        assert_eq!(get_in("test_registry_groups", "parse").unwrap().count(), 1);
+        assert!(get("test_registry_groups::build").is_some());
+
+        let (_, total) = group_report()
+            .into_iter()
+            .find(|(group, _)| group == "test_registry_groups")
+            .unwrap();
+        assert!(total >= Duration::from_millis(30));
+        assert!(total < Duration::from_millis(70));
+    }
+
+    #[test]
+    fn test_registry_scope_threads() {
+        let _serial = serial();
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10 {
+                        let _guard = scope("test_registry_scope_threads");
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let entry = get("test_registry_scope_threads").unwrap();
+        assert_eq!(entry.count(), 80);
+        assert!(entry.total() >= Duration::from_millis(80));
+    }
+
+    #[test]
+    fn test_registry_scope_panic() {
+        let _serial = serial();
+        let result = std::panic::catch_unwind(|| {
+            let _guard = scope("test_registry_scope_panic");
+            panic!("region failed");
+        });
+        assert!(result.is_err());
+        assert_eq!(get("test_registry_scope_panic").unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_registry_report_order() {
+        let _serial = serial();
+        for (name, ms) in [("b", 10), ("c", 20), ("a", 10)] {
+            record(
+                &qualified_name("test_registry_report_order", name),
+                Duration::from_millis(ms),
+            );
+        }
+        let names = |report: Vec<(String, Duration)>| -> Vec<String> {
+            report
+                .into_iter()
+                .filter_map(|(name, _)| {
+                    name.strip_prefix("test_registry_report_order::")
+                        .map(str::to_string)
+                })
+                .collect()
+        };
+        assert_eq!(names(report()), ["a", "b", "c"]);
+        assert_eq!(names(report_by_time()), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_registry_with_entries() {
+        let _serial = serial();
+        record("test_registry_with_entries::b", Duration::from_millis(2));
+        record("test_registry_with_entries::a", Duration::from_millis(1));
+        record("test_registry_with_entries::a", Duration::from_millis(1));
+        let found: Vec<(String, u64)> = with_entries(|entries| {
+            entries
+                .filter(|(name, _)| name.starts_with("test_registry_with_entries::"))
+                .map(|(name, entry)| (name.to_string(), entry.count()))
+                .collect()
+        });
+        assert_eq!(
+            found,
+            [
+                ("test_registry_with_entries::a".to_string(), 2),
+                ("test_registry_with_entries::b".to_string(), 1),
+            ]
+        );
+        assert_eq!(with_entries(|entries| entries.count()), report().len());
+    }
+
+    #[test]
+    fn test_registry_region_setting() {
+        let regions: HashMap<String, bool> = [("db", false), ("db::query", true), ("ui", true)]
+            .into_iter()
+            .map(|(name, enabled)| (name.to_string(), enabled))
+            .collect();
+        assert_eq!(region_setting(&regions, "db"), Some(false));
+        assert_eq!(region_setting(&regions, "db::query"), Some(true));
+        assert_eq!(region_setting(&regions, "db::insert"), Some(false));
+        assert_eq!(region_setting(&regions, "db::insert::batch"), Some(false));
+        assert_eq!(region_setting(&regions, "ui::paint"), Some(true));
+        assert_eq!(region_setting(&regions, "net"), None);
+        assert_eq!(region_setting(&regions, "net::db"), None);
+    }
+
+    #[test]
+    fn test_registry_region_enabled() {
+        assert!(is_region_enabled("test_registry_region_enabled"));
+        set_region_enabled("test_registry_region_enabled", false);
+        assert!(!is_region_enabled("test_registry_region_enabled"));
+        assert!(!is_region_enabled("test_registry_region_enabled::child"));
+        set_region_enabled("test_registry_region_enabled::child", true);
+        assert!(is_region_enabled("test_registry_region_enabled::child"));
+        clear_region_enabled("test_registry_region_enabled");
+        assert!(is_region_enabled("test_registry_region_enabled"));
+    }
+
+    #[test]
+    fn test_registry_hottest() {
+        let _serial = serial();
+        clear();
+        assert_eq!(hottest(), None);
+
+        record("test_registry_hottest::b", Duration::from_millis(30));
+        assert_eq!(
+            hottest(),
+            Some((
+                "test_registry_hottest::b".to_string(),
+                Duration::from_millis(30),
+                1.0
+            ))
+        );
+        record("test_registry_hottest::a", Duration::from_millis(30));
+        record("test_registry_hottest::c", Duration::from_millis(20));
+        record("test_registry_hottest::c", Duration::from_millis(20));
+        let (name, total, fraction) = hottest().unwrap();
+        assert_eq!(name, "test_registry_hottest::c");
+        assert_eq!(total, Duration::from_millis(40));
+        assert!((fraction - 0.4).abs() < 1e-9);
+
+        record("test_registry_hottest::a", Duration::from_millis(10));
+        assert_eq!(hottest().unwrap().0, "test_registry_hottest::a");
+
+        clear();
+        record("test_registry_hottest::zero", Duration::ZERO);
+        assert_eq!(hottest().unwrap().2, 0.0);
+        clear();
+    }
+
+    #[test]
+    fn test_registry_snapshot_diff() {
+        let _serial = serial();
+        let name = |label| qualified_name("test_registry_snapshot_diff", label);
+        record(&name("kept"), Duration::from_millis(10));
+        record(&name("idle"), Duration::from_millis(10));
+        record(&name("cleared"), Duration::from_millis(50));
+        let before = snapshot();
+        assert_eq!(before.get(&name("kept")).unwrap().count(), 1);
+        assert!(!before.is_empty());
+
+        record(&name("kept"), Duration::from_millis(5));
+        record(&name("new"), Duration::from_millis(7));
+        entries().remove(&name("cleared"));
+        record(&name("cleared"), Duration::from_millis(20));
+        let after = snapshot();
+
+        let phase: Vec<(String, Duration)> = before
+            .diff(&after)
+            .into_iter()
+            .filter(|(region, _)| region.starts_with("test_registry_snapshot_diff::"))
+            .collect();
+        assert_eq!(
+            phase,
+            [
+                (name("cleared"), Duration::from_millis(20)),
+                (name("kept"), Duration::from_millis(5)),
+                (name("new"), Duration::from_millis(7)),
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+        assert_eq!(RegistrySnapshot::default().diff(&after).len(), after.len());
+    }
+
+    #[test]
+    fn test_registry_drain_report() {
+        let _serial = serial();
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..1000 {
+                        record("test_registry_drain_report", Duration::from_nanos(1));
+                    }
+                })
+            })
+            .collect();
+
+        let mut drained = Duration::ZERO;
+        let mut collect = |report: Vec<(String, Duration)>| {
+            for (name, total) in report {
+                if name == "test_registry_drain_report" {
+                    drained += total;
+                }
+            }
+        };
+        while !writers.iter().all(|writer| writer.is_finished()) {
+            collect(drain_report());
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        collect(drain_report());
+
+        assert_eq!(drained, Duration::from_nanos(4000));
+        assert_eq!(get("test_registry_drain_report"), None);
+    }
+}
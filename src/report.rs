@@ -0,0 +1,683 @@
+//! Reports built from named elapsed times.
+//!
+//! This module provides functions working on reports, lists of `(name, elapsed time)` pairs
+//! such as the ones returned by `registry::report()`.
+//!
+//! `compare_reports()` matches the names of two reports, e.g. an A/B performance run, and
+//! renders the per-name deltas with the regressions flagged.
+//!
+//! `write_report_file()` and `check_against_file()` support a CI gate: a run stores its report
+//! in a JSON file, and the next run checks its own report against it for regressions.
+//!
+//! `rank()` orders named timings from the fastest to the slowest, with their ratio to the
+//! fastest, e.g. to compare several candidate implementations at once.
+//!
+//! The `Report` builder composes a report with chained calls and renders it as a table, CSV or
+//! JSON.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::json::{self, Value};
+
+/// The percentage above which `compare_reports()` flags a slowdown as a regression.
+pub const DEFAULT_REGRESSION_THRESHOLD_PCT: f64 = 10.0;
+
+/// Compares a candidate report against a baseline report, flagging the names slower by more
+/// than `DEFAULT_REGRESSION_THRESHOLD_PCT` percent as regressions.
+/// See `compare_reports_with_threshold()`.
+pub fn compare_reports(
+    baseline: &[(String, Duration)],
+    candidate: &[(String, Duration)],
+) -> String {
+    compare_reports_with_threshold(baseline, candidate, DEFAULT_REGRESSION_THRESHOLD_PCT)
+}
+
+/// Compares a candidate report against a baseline report, and renders a table with, for each
+/// name present in both, the two elapsed times, the delta and the percentage change. The names
+/// slower by more than `threshold_pct` percent are flagged as `REGRESSION`.
+///
+/// The rows are sorted by largest regression first, i.e. by descending percentage change, ties
+/// being broken by name. The names present in only one of the reports are listed after the
+/// table. Names appearing several times in a report have their elapsed times summed.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::report::compare_reports;
+///
+/// let baseline = vec![
+///     ("parse".to_string(), Duration::from_millis(10)),
+///     ("build".to_string(), Duration::from_millis(20)),
+/// ];
+/// let candidate = vec![
+///     ("parse".to_string(), Duration::from_millis(15)),
+///     ("build".to_string(), Duration::from_millis(18)),
+///     ("emit".to_string(), Duration::from_millis(5)),
+/// ];
+/// let report = compare_reports(&baseline, &candidate);
+/// assert!(report.lines().nth(1).unwrap().starts_with("parse"));
+/// assert!(report.contains("REGRESSION"));
+/// assert!(report.contains("Only in candidate: emit"));
+/// ```
+pub fn compare_reports_with_threshold(
+    baseline: &[(String, Duration)],
+    candidate: &[(String, Duration)],
+    threshold_pct: f64,
+) -> String {
+    let baseline = totals(baseline);
+    let candidate = totals(candidate);
+
+    let mut rows: Vec<(&str, Duration, Duration, f64)> = baseline
+        .iter()
+        .filter_map(|(name, before)| {
+            let after = *candidate.get(name)?;
+            Some((*name, *before, after, change_pct(*before, after)))
+        })
+        .collect();
+    rows.sort_by(|a, b| b.3.total_cmp(&a.3).then_with(|| a.0.cmp(b.0)));
+
+    let width = rows
+        .iter()
+        .map(|row| row.0.len())
+        .max()
+        .unwrap_or(0)
+        .max("Name".len());
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{:<width$} {:>12} {:>12} {:>12} {:>9}",
+        "Name", "Baseline", "Candidate", "Delta", "Change"
+    );
+    for (name, before, after, pct) in &rows {
+        let delta = if after >= before {
+            format!("+{:?}", *after - *before)
+        } else {
+            format!("-{:?}", *before - *after)
+        };
+        let _ = write!(
+            report,
+            "{:<width$} {:>12} {:>12} {:>12} {:>+8.1}%",
+            name,
+            format!("{:?}", before),
+            format!("{:?}", after),
+            delta,
+            pct
+        );
+        if *pct > threshold_pct {
+            report.push_str("  REGRESSION");
+        }
+        report.push('\n');
+    }
+
+    for (label, names) in [
+        ("Only in baseline", only_in(&baseline, &candidate)),
+        ("Only in candidate", only_in(&candidate, &baseline)),
+    ] {
+        if !names.is_empty() {
+            let _ = writeln!(report, "{}: {}", label, names.join(", "));
+        }
+    }
+    report
+}
+
+/// Ranks named timings from the fastest to the slowest, ties being broken by name, along with
+/// their ratio to the fastest one (1.0 for the fastest). If the fastest timing is zero, the
+/// other zero timings have a ratio of 1.0 and the nonzero ones an infinite ratio.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::report::rank;
+///
+/// let ranking = rank(&[
+///     ("naive", Duration::from_millis(30)),
+///     ("simd", Duration::from_millis(5)),
+///     ("lookup", Duration::from_millis(10)),
+/// ]);
+/// assert_eq!(ranking[0], ("simd".to_string(), Duration::from_millis(5), 1.0));
+/// assert_eq!(ranking[2].0, "naive");
+/// assert_eq!(ranking[2].2, 6.0);
+/// ```
+pub fn rank(timers: &[(&str, Duration)]) -> Vec<(String, Duration, f64)> {
+    let mut sorted = timers.to_vec();
+    sorted.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+    let fastest = sorted
+        .first()
+        .map_or(Duration::ZERO, |(_, elapsed)| *elapsed);
+    sorted
+        .into_iter()
+        .map(|(name, elapsed)| {
+            let ratio = if elapsed == fastest {
+                1.0
+            } else {
+                elapsed.as_secs_f64() / fastest.as_secs_f64()
+            };
+            (name.to_string(), elapsed, ratio)
+        })
+        .collect()
+}
+
+/// The `Regression` struct describes a name slower in a report than in its baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Regression {
+    name: String,
+    baseline: Duration,
+    current: Duration,
+    change_pct: f64,
+}
+
+impl Regression {
+    /// Returns the regressed name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the elapsed time of the baseline.
+    pub fn baseline(&self) -> Duration {
+        self.baseline
+    }
+
+    /// Returns the elapsed time of the current report.
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// Returns the percentage change from the baseline, infinite for a zero baseline.
+    pub fn change_pct(&self) -> f64 {
+        self.change_pct
+    }
+}
+
+/// Implements the `Display` trait for the `Regression` struct (e.g. `parse: 10ms -> 15ms (+50.0%)`).
+impl std::fmt::Display for Regression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {:?} -> {:?} ({:+.1}%)",
+            self.name, self.baseline, self.current, self.change_pct
+        )
+    }
+}
+
+/// The error returned by `check_against_file()`.
+#[derive(Debug)]
+pub enum CheckError {
+    /// The stored report could not be read. A missing file gives an error of kind `NotFound`,
+    /// e.g. on the first run.
+    Io(std::io::Error),
+    /// The stored report is not a valid report file.
+    Malformed,
+    /// Some names regressed beyond the tolerance, sorted by largest regression first.
+    Regressions(Vec<Regression>),
+}
+
+impl std::fmt::Display for CheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckError::Io(error) => write!(f, "cannot read the stored report: {}", error),
+            CheckError::Malformed => write!(f, "malformed stored report"),
+            CheckError::Regressions(regressions) => {
+                write!(f, "{} regression(s)", regressions.len())?;
+                for regression in regressions {
+                    write!(f, "\n  {}", regression)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CheckError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Writes a report to a JSON file, as an object mapping each name to its elapsed time in
+/// nanoseconds. Names appearing several times have their elapsed times summed.
+pub fn write_report_file(
+    path: impl AsRef<Path>,
+    report: &[(String, Duration)],
+) -> std::io::Result<()> {
+    let totals: BTreeMap<&str, Duration> = totals(report).into_iter().collect();
+    let mut text = String::from("{");
+    for (index, (name, elapsed)) in totals.iter().enumerate() {
+        text.push_str(if index == 0 { "\n  " } else { ",\n  " });
+        json::write_string(&mut text, name);
+        let _ = write!(text, ": {}", elapsed.as_nanos());
+    }
+    text.push_str("\n}\n");
+    std::fs::write(path, text)
+}
+
+/// Reads a report written by `write_report_file()`, sorted by name.
+pub fn read_report_file(path: impl AsRef<Path>) -> Result<Vec<(String, Duration)>, CheckError> {
+    let text = std::fs::read_to_string(path).map_err(CheckError::Io)?;
+    json::parse(&text)
+        .as_ref()
+        .and_then(Value::as_object)
+        .ok_or(CheckError::Malformed)?
+        .iter()
+        .map(|(name, nanos)| {
+            let nanos = nanos.as_u64().ok_or(CheckError::Malformed)?;
+            Ok((name.clone(), Duration::from_nanos(nanos)))
+        })
+        .collect()
+}
+
+/// Checks a report against the one stored in a file by `write_report_file()`, returning the
+/// names slower by more than `tolerance_pct` percent than in the stored report. The names
+/// present in only one of the reports are not checked.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::report::{check_against_file, write_report_file, CheckError};
+///
+/// let path = std::env::temp_dir().join("timelapse_doc_check.json");
+/// let previous = vec![("parse".to_string(), Duration::from_millis(10))];
+/// write_report_file(&path, &previous).unwrap();
+///
+/// let current = vec![("parse".to_string(), Duration::from_millis(15))];
+/// match check_against_file(&path, &current, 10.0) {
+///     Err(CheckError::Regressions(regressions)) => assert_eq!(regressions[0].name(), "parse"),
+///     _ => unreachable!(),
+/// }
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn check_against_file(
+    path: impl AsRef<Path>,
+    report: &[(String, Duration)],
+    tolerance_pct: f64,
+) -> Result<(), CheckError> {
+    let stored = read_report_file(path)?;
+    let baseline = totals(&stored);
+    let mut regressions: Vec<Regression> = totals(report)
+        .into_iter()
+        .filter_map(|(name, current)| {
+            let baseline = *baseline.get(name)?;
+            let change_pct = change_pct(baseline, current);
+            (change_pct > tolerance_pct).then(|| Regression {
+                name: name.to_string(),
+                baseline,
+                current,
+                change_pct,
+            })
+        })
+        .collect();
+    if regressions.is_empty() {
+        return Ok(());
+    }
+    regressions.sort_by(|a, b| {
+        b.change_pct
+            .total_cmp(&a.change_pct)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Err(CheckError::Regressions(regressions))
+}
+
+/// The formats `Report::render()` can produce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// An aligned text table with a header line.
+    #[default]
+    Table,
+    /// Comma-separated values with a `name,nanos` header line.
+    Csv,
+    /// A JSON object with an `entries` array of `name` and `nanos` objects, and a `total` in
+    /// nanoseconds if requested.
+    Json,
+}
+
+/// The orders in which `Report::render()` lists the entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// In the order the entries were added.
+    #[default]
+    Insertion,
+    /// By name.
+    Name,
+    /// By descending elapsed time, ties being broken by name.
+    Time,
+}
+
+/// The `Report` struct builds a report of named elapsed times with chained calls, and renders
+/// it as a table, CSV or JSON.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::report::{Format, Report, SortOrder};
+///
+/// let output = Report::new()
+///     .add("parse", Duration::from_millis(10))
+///     .add("build", Duration::from_millis(30))
+///     .with_total()
+///     .sorted_by(SortOrder::Time)
+///     .render();
+/// assert!(output.lines().nth(1).unwrap().starts_with("build"));
+/// assert!(output.lines().last().unwrap().starts_with("Total"));
+///
+/// let csv = Report::new()
+///     .add("parse", Duration::from_millis(10))
+///     .with_format(Format::Csv)
+///     .render();
+/// assert_eq!(csv, "name,nanos\nparse,10000000\n");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    entries: Vec<(String, Duration)>,
+    total: bool,
+    order: SortOrder,
+    format: Format,
+}
+
+impl Report {
+    /// Creates a new empty `Report`, rendered as a table in insertion order without a total.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a named elapsed time.
+    pub fn add(mut self, name: impl Into<String>, elapsed: Duration) -> Self {
+        self.entries.push((name.into(), elapsed));
+        self
+    }
+
+    /// Adds all the named elapsed times of a report, e.g. from `registry::report()`.
+    pub fn add_all(mut self, report: &[(String, Duration)]) -> Self {
+        self.entries.extend_from_slice(report);
+        self
+    }
+
+    /// Includes the total of the elapsed times after the entries.
+    pub fn with_total(mut self) -> Self {
+        self.total = true;
+        self
+    }
+
+    /// Sets the order of the entries.
+    pub fn sorted_by(mut self, order: SortOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sets the format of the rendered report.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Returns the entries added, in insertion order.
+    pub fn entries(&self) -> &[(String, Duration)] {
+        &self.entries
+    }
+
+    /// Returns the total of the elapsed times.
+    pub fn total(&self) -> Duration {
+        self.entries.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+
+    /// Renders the report in the chosen format and order.
+    pub fn render(&self) -> String {
+        let mut entries: Vec<&(String, Duration)> = self.entries.iter().collect();
+        match self.order {
+            SortOrder::Insertion => (),
+            SortOrder::Name => entries.sort_by(|a, b| a.0.cmp(&b.0)),
+            SortOrder::Time => entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0))),
+        }
+        let total = self.total.then(|| self.total());
+
+        let mut output = String::new();
+        match self.format {
+            Format::Table => {
+                let width = entries
+                    .iter()
+                    .map(|(name, _)| name.len())
+                    .chain(total.map(|_| "Total".len()))
+                    .max()
+                    .unwrap_or(0)
+                    .max("Name".len());
+                let _ = writeln!(output, "{:<width$} {:>12}", "Name", "Elapsed");
+                for (name, elapsed) in entries {
+                    let _ = writeln!(output, "{:<width$} {:>12}", name, format!("{:?}", elapsed));
+                }
+                if let Some(total) = total {
+                    let _ = writeln!(output, "{:<width$} {:>12}", "Total", format!("{:?}", total));
+                }
+            }
+            Format::Csv => {
+                output.push_str("name,nanos\n");
+                for (name, elapsed) in entries {
+                    let _ = writeln!(output, "{},{}", csv_field(name), elapsed.as_nanos());
+                }
+                if let Some(total) = total {
+                    let _ = writeln!(output, "Total,{}", total.as_nanos());
+                }
+            }
+            Format::Json => {
+                output.push_str("{\"entries\": [");
+                for (index, (name, elapsed)) in entries.iter().enumerate() {
+                    output.push_str(if index == 0 {
+                        "\n  {\"name\": "
+                    } else {
+                        ",\n  {\"name\": "
+                    });
+                    json::write_string(&mut output, name);
+                    let _ = write!(output, ", \"nanos\": {}}}", elapsed.as_nanos());
+                }
+                output.push_str("\n]");
+                if let Some(total) = total {
+                    let _ = write!(output, ", \"total\": {}", total.as_nanos());
+                }
+                output.push_str("}\n");
+            }
+        }
+        output
+    }
+}
+
+/// Implements the `Display` trait for the `Report` struct, writing the rendered report.
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render())
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// Sums the elapsed times of a report by name.
+fn totals(report: &[(String, Duration)]) -> HashMap<&str, Duration> {
+    let mut totals = HashMap::new();
+    for (name, elapsed) in report {
+        *totals.entry(name.as_str()).or_default() += *elapsed;
+    }
+    totals
+}
+
+/// Returns the sorted names of `report` absent from `other`.
+fn only_in<'a>(
+    report: &HashMap<&'a str, Duration>,
+    other: &HashMap<&str, Duration>,
+) -> Vec<&'a str> {
+    let mut names: Vec<&str> = report
+        .keys()
+        .filter(|name| !other.contains_key(*name))
+        .copied()
+        .collect();
+    names.sort();
+    names
+}
+
+/// Returns the percentage change from `before` to `after`. A change from zero is infinite,
+/// unless `after` is also zero.
+fn change_pct(before: Duration, after: Duration) -> f64 {
+    if before.is_zero() {
+        return if after.is_zero() { 0.0 } else { f64::INFINITY };
+    }
+    let before = before.as_nanos() as f64;
+    (after.as_nanos() as f64 - before) / before * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(entries: &[(&str, u64)]) -> Vec<(String, Duration)> {
+        entries
+            .iter()
+            .map(|(name, ms)| (name.to_string(), Duration::from_millis(*ms)))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_reports() {
+        let baseline = report(&[("a", 100), ("b", 100), ("c", 100), ("gone", 5)]);
+        let candidate = report(&[("a", 105), ("b", 150), ("c", 50), ("new", 5), ("new", 5)]);
+        let output = compare_reports(&baseline, &candidate);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines[0].starts_with("Name"));
+        assert!(lines[1].starts_with("b "));
+        assert!(lines[1].contains("+50ms"));
+        assert!(lines[1].contains("+50.0%"));
+        assert!(lines[1].ends_with("REGRESSION"));
+        assert!(lines[2].starts_with("a "));
+        assert!(!lines[2].contains("REGRESSION"));
+        assert!(lines[3].starts_with("c "));
+        assert!(lines[3].contains("-50ms"));
+        assert_eq!(lines[4], "Only in baseline: gone");
+        assert_eq!(lines[5], "Only in candidate: new");
+
+        let strict = compare_reports_with_threshold(&baseline, &candidate, 1.0);
+        assert_eq!(strict.matches("REGRESSION").count(), 2);
+    }
+
+    #[test]
+    fn test_rank() {
+        let ms = Duration::from_millis;
+        assert_eq!(
+            rank(&[("c", ms(40)), ("b", ms(10)), ("a", ms(10)), ("d", ms(25))]),
+            [
+                ("a".to_string(), ms(10), 1.0),
+                ("b".to_string(), ms(10), 1.0),
+                ("d".to_string(), ms(25), 2.5),
+                ("c".to_string(), ms(40), 4.0),
+            ]
+        );
+        assert_eq!(
+            rank(&[("slow", ms(1)), ("free", ms(0)), ("noop", ms(0))]),
+            [
+                ("free".to_string(), ms(0), 1.0),
+                ("noop".to_string(), ms(0), 1.0),
+                ("slow".to_string(), ms(1), f64::INFINITY),
+            ]
+        );
+        assert!(rank(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_change_pct() {
+        let ms = Duration::from_millis;
+        assert_eq!(change_pct(ms(100), ms(150)), 50.0);
+        assert_eq!(change_pct(ms(100), ms(50)), -50.0);
+        assert_eq!(change_pct(ms(0), ms(0)), 0.0);
+        assert_eq!(change_pct(ms(0), ms(1)), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_report_file() {
+        let path = std::env::temp_dir().join(format!(
+            "timelapse_test_report_file_{}.json",
+            std::process::id()
+        ));
+        let stored = report(&[("b \"quoted\"", 100), ("a", 100), ("a", 100), ("gone", 5)]);
+        write_report_file(&path, &stored).unwrap();
+        assert_eq!(
+            read_report_file(&path).unwrap(),
+            report(&[("a", 200), ("b \"quoted\"", 100), ("gone", 5)])
+        );
+
+        let current = report(&[("a", 210), ("b \"quoted\"", 150), ("new", 5)]);
+        assert!(check_against_file(&path, &current, 60.0).is_ok());
+        match check_against_file(&path, &current, 1.0) {
+            Err(CheckError::Regressions(regressions)) => {
+                let names: Vec<&str> = regressions.iter().map(Regression::name).collect();
+                assert_eq!(names, ["b \"quoted\"", "a"]);
+                assert_eq!(regressions[1].to_string(), "a: 200ms -> 210ms (+5.0%)");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        std::fs::write(&path, "[1, 2]").unwrap();
+        assert!(matches!(
+            check_against_file(&path, &current, 1.0),
+            Err(CheckError::Malformed)
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            check_against_file(&path, &current, 1.0),
+            Err(CheckError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_report_builder() {
+        let ms = Duration::from_millis;
+        let builder = Report::new()
+            .add("b", ms(10))
+            .add_all(&report(&[("c", 30), ("a, \"x\"", 10)]))
+            .with_total();
+        assert_eq!(builder.total(), ms(50));
+
+        let table = builder.clone().render();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "Name        Elapsed");
+        assert_eq!(lines[1], "b              10ms");
+        assert_eq!(lines[4], "Total          50ms");
+
+        let csv = builder
+            .clone()
+            .sorted_by(SortOrder::Time)
+            .with_format(Format::Csv)
+            .render();
+        assert_eq!(
+            csv,
+            "name,nanos\nc,30000000\n\"a, \"\"x\"\"\",10000000\nb,10000000\nTotal,50000000\n"
+        );
+
+        let json = builder
+            .sorted_by(SortOrder::Name)
+            .with_format(Format::Json)
+            .render();
+        let value = json::parse(&json).unwrap();
+        let members = value.as_object().unwrap();
+        assert_eq!(
+            members[1],
+            ("total".to_string(), Value::Number("50000000".into()))
+        );
+        match &members[0].1 {
+            Value::Array(entries) => {
+                assert_eq!(entries.len(), 3);
+                assert_eq!(
+                    entries[0].as_object().unwrap()[0].1,
+                    Value::String("a, \"x\"".into())
+                );
+            }
+            other => panic!("unexpected entries: {:?}", other),
+        }
+    }
+}
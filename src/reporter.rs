@@ -0,0 +1,557 @@
+//! Pluggable destinations for the elapsed times reported by `profile_end!`.
+//!
+//! This module provides the `Reporter` trait. The global reporter, set with `set_reporter()`,
+//! receives every elapsed time reported with `TimeLapse::report()`, and thus every
+//! `profile_end!`. By default, it is a `LogReporter` logging at the info level.
+//!
+//! `MultiReporter` fans out each report to several reporters, e.g. a log and a metrics backend.
+//!
+//! The key-value tags attached to a measurement with `TimeLapse::with_tag()` are passed to
+//! `Reporter::report_tagged()`. The log and print reporters append them to the message.
+//!
+//! `AsyncReporter` moves the reporting off the measured thread: the reports are queued in a
+//! bounded channel and forwarded to another reporter by a background thread, so a slow log
+//! sink does not distort the timings. Call `flush()` before exiting to forward the reports
+//! still queued.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::logging::Level;
+use crate::profiler::{format_message, log_gated, passes_gate, qualified_name};
+use crate::TimeLapse;
+
+/// The `Reporter` trait is implemented by the destinations of reported elapsed times.
+pub trait Reporter: Send + Sync {
+    /// Reports the elapsed time of the profiler with the given name.
+    fn report(&self, name: &str, elapsed: Duration);
+
+    /// Reports the elapsed time of the profiler with the given name and key-value tags (see
+    /// `TimeLapse::with_tag()`). Reporters emitting structured records or metrics should
+    /// export the tags as fields or labels. By default, the tags are ignored.
+    fn report_tagged(&self, name: &str, elapsed: Duration, tags: &[(String, String)]) {
+        let _ = tags;
+        self.report(name, elapsed);
+    }
+
+    /// Returns once the reports received so far are delivered, for reporters delivering them
+    /// later. By default, does nothing.
+    fn flush(&self) {}
+}
+
+/// The `LogReporter` struct logs the reported elapsed times at a given level.
+/// The message follows the template set with `TimeLapse::set_log_format()`.
+/// Nothing is logged below the floor set with `TimeLapse::set_min_loggable()` or for a
+/// disabled region (see `registry::set_region_enabled()`).
+#[derive(Clone, Copy, Debug)]
+pub struct LogReporter {
+    level: Level,
+}
+
+impl LogReporter {
+    /// Creates a new `LogReporter` logging at the given level.
+    pub fn new(level: Level) -> Self {
+        LogReporter { level }
+    }
+}
+
+/// Implements the `Default` trait for the `LogReporter` struct, logging at the info level.
+impl std::default::Default for LogReporter {
+    fn default() -> Self {
+        LogReporter::new(Level::Info)
+    }
+}
+
+impl Reporter for LogReporter {
+    fn report(&self, name: &str, elapsed: Duration) {
+        log_gated(name, elapsed, self.level, || format_message(name, elapsed));
+    }
+
+    fn report_tagged(&self, name: &str, elapsed: Duration, tags: &[(String, String)]) {
+        log_gated(name, elapsed, self.level, || {
+            tagged_message(name, elapsed, tags)
+        });
+    }
+}
+
+/// The `PrintReporter` struct prints the reported elapsed times on the standard output.
+/// The message follows the template set with `TimeLapse::set_log_format()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PrintReporter;
+
+impl Reporter for PrintReporter {
+    fn report(&self, name: &str, elapsed: Duration) {
+        println!("{}", format_message(name, elapsed));
+    }
+
+    fn report_tagged(&self, name: &str, elapsed: Duration, tags: &[(String, String)]) {
+        println!("{}", tagged_message(name, elapsed, tags));
+    }
+}
+
+/// Returns the message for a profiler's name and elapsed time, followed by its tags, if any
+/// (e.g. `... [endpoint=/users, method=GET]`).
+fn tagged_message(name: &str, elapsed: Duration, tags: &[(String, String)]) -> String {
+    let mut message = format_message(name, elapsed);
+    for (index, (key, value)) in tags.iter().enumerate() {
+        message.push_str(if index == 0 { " [" } else { ", " });
+        message.push_str(key);
+        message.push('=');
+        message.push_str(value);
+    }
+    if !tags.is_empty() {
+        message.push(']');
+    }
+    message
+}
+
+/// The `MultiReporter` struct forwards each report to all of its reporters, in order.
+/// A reporter panicking does not prevent the following ones from receiving the report.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::reporter::{set_reporter, LogReporter, MultiReporter, PrintReporter};
+///
+/// set_reporter(MultiReporter::new(vec![
+///     Box::new(LogReporter::default()),
+///     Box::new(PrintReporter),
+/// ]));
+/// ```
+#[derive(Default)]
+pub struct MultiReporter {
+    reporters: Vec<Box<dyn Reporter>>,
+}
+
+impl MultiReporter {
+    /// Creates a new `MultiReporter` forwarding to the given reporters.
+    pub fn new(reporters: Vec<Box<dyn Reporter>>) -> Self {
+        MultiReporter { reporters }
+    }
+
+    /// Adds a reporter to forward to.
+    pub fn with(mut self, reporter: impl Reporter + 'static) -> Self {
+        self.reporters.push(Box::new(reporter));
+        self
+    }
+}
+
+impl Reporter for MultiReporter {
+    fn report(&self, name: &str, elapsed: Duration) {
+        self.report_tagged(name, elapsed, &[]);
+    }
+
+    fn report_tagged(&self, name: &str, elapsed: Duration, tags: &[(String, String)]) {
+        for reporter in &self.reporters {
+            let _ = catch_unwind(AssertUnwindSafe(|| {
+                reporter.report_tagged(name, elapsed, tags)
+            }));
+        }
+    }
+
+    fn flush(&self) {
+        for reporter in &self.reporters {
+            let _ = catch_unwind(AssertUnwindSafe(|| reporter.flush()));
+        }
+    }
+}
+
+/// A message to the background thread of an `AsyncReporter`.
+enum Message {
+    Report {
+        name: String,
+        elapsed: Duration,
+        tags: Vec<(String, String)>,
+    },
+    Flush(mpsc::Sender<()>),
+}
+
+/// The `AsyncReporter` struct forwards the reports to another reporter from a background
+/// thread, so the reporting thread never waits on the destination.
+///
+/// The reports are queued in a channel of bounded capacity. When it is full, the reports are
+/// dropped rather than blocking the measured code, `dropped()` counting them. `flush()` waits
+/// for the queued reports to be forwarded; dropping the reporter forwards them too.
+///
+/// Only the reports, i.e. `TimeLapse::report()` and `profile_end!`, go through it. Logging does
+/// not: `TimeLapse::log()`, the other `log_*` methods and the helpers logging their measurements
+/// (e.g. `time()` or `profile_end_log!`) still log synchronously from the calling thread, so the
+/// measurements of the hot paths should be reported rather than logged.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::reporter::{self, set_reporter, AsyncReporter, LogReporter};
+/// use timelapse::{profile_end, profile_start, TimeLapse};
+///
+/// set_reporter(AsyncReporter::new(LogReporter::default(), 1024));
+///
+/// profile_start!(request);
+/// // ... work ...
+/// profile_end!(request); // only queues the report
+///
+/// reporter::flush(); // at shutdown
+/// ```
+pub struct AsyncReporter {
+    sender: Option<SyncSender<Message>>,
+    thread: Option<JoinHandle<()>>,
+    dropped: AtomicU64,
+}
+
+impl AsyncReporter {
+    /// Creates a new `AsyncReporter` forwarding to `reporter`, queuing at most `capacity`
+    /// reports. A `capacity` of zero drops every report the background thread is not ready to
+    /// take at once.
+    pub fn new(reporter: impl Reporter + 'static, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let thread = std::thread::spawn(move || {
+            for message in receiver {
+                match message {
+                    Message::Report {
+                        name,
+                        elapsed,
+                        tags,
+                    } => {
+                        let _ = catch_unwind(AssertUnwindSafe(|| {
+                            reporter.report_tagged(&name, elapsed, &tags)
+                        }));
+                    }
+                    Message::Flush(done) => {
+                        reporter.flush();
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        AsyncReporter {
+            sender: Some(sender),
+            thread: Some(thread),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the number of reports dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Reporter for AsyncReporter {
+    fn report(&self, name: &str, elapsed: Duration) {
+        self.report_tagged(name, elapsed, &[]);
+    }
+
+    fn report_tagged(&self, name: &str, elapsed: Duration, tags: &[(String, String)]) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let message = Message::Report {
+            name: name.to_string(),
+            elapsed,
+            tags: tags.to_vec(),
+        };
+        if let Err(TrySendError::Full(_)) = sender.try_send(message) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let (done, wait) = mpsc::channel();
+        if sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+impl Drop for AsyncReporter {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread once the queued reports are forwarded.
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The global reporter, `None` for the default `LogReporter`.
+static REPORTER: RwLock<Option<Arc<dyn Reporter>>> = RwLock::new(None);
+
+/// Sets the global reporter, receiving the elapsed times reported by all `TimeLapse` instances.
+/// The reports in progress with the previous reporter complete with it.
+pub fn set_reporter(reporter: impl Reporter + 'static) {
+    *REPORTER.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(reporter));
+}
+
+/// Returns the global reporter, `None` for the default `LogReporter`. The lock is released on
+/// return, so that a slow reporter does not hold up `set_reporter()`.
+fn global_reporter() -> Option<Arc<dyn Reporter>> {
+    REPORTER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Returns once the reports received so far by the global reporter are delivered, e.g. the
+/// reports queued by an `AsyncReporter`. To call before exiting, as the global reporter is
+/// never dropped. Also available as `registry::flush()`.
+pub fn flush() {
+    if let Some(reporter) = global_reporter() {
+        reporter.flush();
+    }
+}
+
+/// Forwards an elapsed time and its tags to the global reporter.
+/// The time spent is part of the profiling overhead, see `registry::set_overhead_tracking()`.
+pub(crate) fn dispatch(name: &str, elapsed: Duration, tags: &[(String, String)]) {
+    crate::registry::track_overhead(|| match global_reporter() {
+        Some(reporter) => reporter.report_tagged(name, elapsed, tags),
+        None => LogReporter::default().report_tagged(name, elapsed, tags),
+    });
+}
+
+impl TimeLapse {
+    /// Reports the elapsed time with a given name, and the tags of the measurement, to the
+    /// global reporter. This is what `profile_end!` does.
+    /// Nothing is reported if the elapsed time is below the floor set with `set_min_loggable()`,
+    /// or if the region is disabled (see `registry::set_region_enabled()`).
+    pub fn report(&self, name: &str) {
+        let elapsed = self.elapsed();
+        if passes_gate(name, elapsed) {
+            dispatch(name, elapsed, self.tags());
+        }
+    }
+
+    /// Reports the elapsed time with a given group and label, formatted as `group::label`,
+    /// to the global reporter.
+    pub fn report_in(&self, group: &str, label: &str) {
+        self.report(&qualified_name(group, label));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A reporter keeping the reported names starting with a prefix.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        prefix: &'static str,
+        names: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Reporter for Recorder {
+        fn report(&self, name: &str, _elapsed: Duration) {
+            if name.starts_with(self.prefix) {
+                self.names.lock().unwrap().push(name.to_string());
+            }
+        }
+    }
+
+    /// A reporter keeping the reported tags.
+    #[derive(Clone, Default)]
+    struct TagRecorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl Reporter for TagRecorder {
+        fn report(&self, _name: &str, _elapsed: Duration) {}
+
+        fn report_tagged(&self, _name: &str, _elapsed: Duration, tags: &[(String, String)]) {
+            self.0.lock().unwrap().extend_from_slice(tags);
+        }
+    }
+
+    /// The messages logged by the tests once `capture_logs()` is called.
+    #[cfg(feature = "log")]
+    static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    /// A logger keeping the messages in `LOGGED`.
+    #[cfg(feature = "log")]
+    struct Capture;
+
+    #[cfg(feature = "log")]
+    impl log::Log for Capture {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            LOGGED.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Installs the `Capture` logger, once for all the tests.
+    #[cfg(feature = "log")]
+    fn capture_logs() {
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&Capture).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    /// Returns `true` if a captured message contains the given text.
+    #[cfg(feature = "log")]
+    fn was_logged(text: &str) -> bool {
+        LOGGED
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|message| message.contains(text))
+    }
+
+    struct Panicking;
+
+    impl Reporter for Panicking {
+        fn report(&self, _name: &str, _elapsed: Duration) {
+            panic!("reporter failure");
+        }
+    }
+
+    struct PanickingFlush;
+
+    impl Reporter for PanickingFlush {
+        fn report(&self, _name: &str, _elapsed: Duration) {}
+
+        fn flush(&self) {
+            panic!("flush failure");
+        }
+    }
+
+    #[test]
+    fn test_multi_reporter_flush() {
+        let recorder = Recorder::default();
+        let multi = MultiReporter::new(vec![Box::new(PanickingFlush)])
+            .with(AsyncReporter::new(recorder.clone(), 4));
+        multi.report("task", Duration::from_millis(1));
+        multi.flush();
+        assert_eq!(*recorder.names.lock().unwrap(), ["task"]);
+    }
+
+    #[test]
+    fn test_multi_reporter() {
+        let first = Recorder::default();
+        let second = Recorder::default();
+        let multi = MultiReporter::new(vec![Box::new(first.clone()), Box::new(Panicking)])
+            .with(second.clone());
+        multi.report("task", Duration::from_millis(1));
+        assert_eq!(*first.names.lock().unwrap(), ["task"]);
+        assert_eq!(*second.names.lock().unwrap(), ["task"]);
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_reporter_disabled() {
+        capture_logs();
+        crate::registry::set_region_enabled("test_log_reporter_disabled", false);
+        let reporter = LogReporter::default();
+        let tags = [("key".to_string(), "value".to_string())];
+        reporter.report("test_log_reporter_enabled", Duration::from_millis(1));
+        reporter.report("test_log_reporter_disabled", Duration::from_millis(1));
+        reporter.report_tagged(
+            "test_log_reporter_disabled",
+            Duration::from_millis(1),
+            &tags,
+        );
+        assert!(was_logged("test_log_reporter_enabled"));
+        assert!(!was_logged("test_log_reporter_disabled"));
+    }
+
+    #[test]
+    fn test_global_reporter() {
+        let recorder = Recorder {
+            prefix: "reporter_test",
+            names: Arc::default(),
+        };
+        set_reporter(recorder.clone());
+        let reporter_test = TimeLapse::new();
+        crate::profile_end!(reporter_test);
+        crate::profile_end!(reporter_test, group: "reporter_test_group");
+        crate::registry::set_region_enabled("reporter_test_disabled", false);
+        let reporter_test_disabled = TimeLapse::new();
+        crate::profile_end!(reporter_test_disabled);
+        crate::profile_end!(reporter_test_disabled, group: "reporter_test_disabled");
+        set_reporter(LogReporter::default());
+
+        assert_eq!(
+            *recorder.names.lock().unwrap(),
+            ["reporter_test", "reporter_test_group::reporter_test"]
+        );
+    }
+
+    #[test]
+    fn test_tagged_reports() {
+        let tags = vec![
+            ("endpoint".to_string(), "/users".to_string()),
+            ("method".to_string(), "GET".to_string()),
+        ];
+        assert_eq!(
+            tagged_message("request", Duration::from_millis(5), &tags),
+            "TimeLapse request - Elapsed time: 5ms [endpoint=/users, method=GET]"
+        );
+        assert_eq!(
+            tagged_message("request", Duration::from_millis(5), &[]),
+            "TimeLapse request - Elapsed time: 5ms"
+        );
+
+        let recorder = TagRecorder::default();
+        let first = Recorder::default();
+        let multi = MultiReporter::new(vec![Box::new(first.clone())]).with(recorder.clone());
+        multi.report_tagged("request", Duration::from_millis(5), &tags);
+        assert_eq!(*recorder.0.lock().unwrap(), tags);
+        assert_eq!(*first.names.lock().unwrap(), ["request"]);
+
+        let profiler = TimeLapse::new().with_tag("endpoint", "/users");
+        assert_eq!(profiler.tags(), &tags[..1]);
+    }
+
+    /// A reporter waiting for a gate to open before keeping the reported names.
+    #[derive(Clone, Default)]
+    struct Gated {
+        gate: Arc<Mutex<()>>,
+        names: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Reporter for Gated {
+        fn report(&self, name: &str, _elapsed: Duration) {
+            let _open = self.gate.lock().unwrap();
+            self.names.lock().unwrap().push(name.to_string());
+        }
+    }
+
+    #[test]
+    fn test_async_reporter() {
+        let recorder = Recorder::default();
+        let reporter = AsyncReporter::new(recorder.clone(), 16);
+        reporter.report("first", Duration::from_millis(1));
+        reporter.report_tagged("second", Duration::from_millis(1), &[]);
+        reporter.flush();
+        assert_eq!(*recorder.names.lock().unwrap(), ["first", "second"]);
+
+        reporter.report("third", Duration::from_millis(1));
+        drop(reporter);
+        assert_eq!(recorder.names.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_async_reporter_full() {
+        let gated = Gated::default();
+        let closed = gated.gate.lock().unwrap();
+        let reporter = AsyncReporter::new(gated.clone(), 1);
+        for _ in 0..10 {
+            reporter.report("task", Duration::from_millis(1));
+        }
+        // At most one report is being forwarded and one is queued.
+        assert!(reporter.dropped() >= 8);
+        drop(closed);
+        reporter.flush();
+        let delivered = gated.names.lock().unwrap().len() as u64;
+        assert_eq!(delivered + reporter.dropped(), 10);
+    }
+}
@@ -0,0 +1,161 @@
+//! A uniform random sample of recorded durations.
+//!
+//! This module provides the `Reservoir` struct, keeping a fixed number of the recorded
+//! durations with Vitter's algorithm R: after any number of recordings, every recorded duration
+//! has the same probability of being in the sample. It suits unbounded streams, where the
+//! individual measurements are wanted for a later distribution analysis but cannot all be kept.
+//!
+//! The sample is drawn with a small internal pseudo-random generator, seeded with a fixed
+//! value by default so that runs are reproducible.
+
+use std::time::Duration;
+
+/// The seed of the generator of a `Reservoir` created with `new()`.
+const DEFAULT_SEED: u64 = 0x5EED_0F71_AE1A_9500;
+
+/// The `Reservoir` struct keeps a uniform random sample of fixed capacity of the recorded
+/// durations.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::Reservoir;
+///
+/// let mut reservoir = Reservoir::new(100);
+/// for us in 0..10_000 {
+///     reservoir.record(Duration::from_micros(us));
+/// }
+/// assert_eq!(reservoir.count(), 10_000);
+/// assert_eq!(reservoir.samples().len(), 100);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Reservoir {
+    samples: Vec<Duration>,
+    capacity: usize,
+    count: u64,
+    state: u64,
+}
+
+impl Reservoir {
+    /// Creates a new empty `Reservoir` keeping at most `capacity` samples, with the default
+    /// seed.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_seed(capacity, DEFAULT_SEED)
+    }
+
+    /// Creates a new empty `Reservoir` keeping at most `capacity` samples, drawing them with
+    /// a generator seeded with `seed`.
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        Reservoir {
+            samples: Vec::with_capacity(capacity),
+            capacity,
+            count: 0,
+            state: seed,
+        }
+    }
+
+    /// Records a duration: it is kept while the reservoir is not full, and then replaces a
+    /// random sample with a probability of `capacity / count`.
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.samples.len() < self.capacity {
+            self.samples.push(elapsed);
+        } else {
+            let index = self.next_below(self.count + 1);
+            if let Some(sample) = self.samples.get_mut(index as usize) {
+                *sample = elapsed;
+            }
+        }
+        self.count += 1;
+    }
+
+    /// Returns the samples, in no particular order.
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    /// Returns the maximum number of samples.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of recorded durations, sampled or not.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Removes the samples and resets the count, the generator going on from its current
+    /// state.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.count = 0;
+    }
+
+    /// Returns a pseudo-random number lower than `bound`, with the SplitMix64 generator.
+    fn next_below(&mut self, bound: u64) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        ((u128::from(z) * u128::from(bound)) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservoir_fill() {
+        let mut reservoir = Reservoir::new(3);
+        for ms in [1, 2] {
+            reservoir.record(Duration::from_millis(ms));
+        }
+        assert_eq!(
+            reservoir.samples(),
+            &[Duration::from_millis(1), Duration::from_millis(2)]
+        );
+        for ms in 3..100 {
+            reservoir.record(Duration::from_millis(ms));
+        }
+        assert_eq!(reservoir.samples().len(), 3);
+        assert_eq!(reservoir.count(), 99);
+        assert_eq!(reservoir.capacity(), 3);
+
+        reservoir.clear();
+        assert!(reservoir.samples().is_empty());
+        assert_eq!(reservoir.count(), 0);
+
+        let mut empty = Reservoir::new(0);
+        empty.record(Duration::from_millis(1));
+        assert!(empty.samples().is_empty());
+        assert_eq!(empty.count(), 1);
+    }
+
+    #[test]
+    fn test_reservoir_sampling() {
+        let sample = |seed| {
+            let mut reservoir = Reservoir::with_seed(1_000, seed);
+            for ns in 0..100_000 {
+                reservoir.record(Duration::from_nanos(ns));
+            }
+            reservoir.samples().to_vec()
+        };
+        let samples = sample(DEFAULT_SEED);
+        assert_eq!(samples, sample(DEFAULT_SEED));
+        assert_ne!(samples, sample(1));
+
+        // A uniform sample of 0..100_000 has a mean close to 50_000, and covers each tenth.
+        let mean = samples.iter().sum::<Duration>() / samples.len() as u32;
+        assert!(mean > Duration::from_nanos(45_000) && mean < Duration::from_nanos(55_000));
+        for tenth in 0..10 {
+            let range =
+                Duration::from_nanos(tenth * 10_000)..Duration::from_nanos((tenth + 1) * 10_000);
+            let count = samples
+                .iter()
+                .filter(|sample| range.contains(sample))
+                .count();
+            assert!(count > 50, "{} samples in tenth {}", count, tenth);
+        }
+    }
+}
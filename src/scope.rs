@@ -0,0 +1,338 @@
+//! Scoped profiling with a thread-local stack of the enclosing scopes.
+//!
+//! This module provides the `ScopeGuard` struct, created by the `profile_scope!` macro. A guard
+//! logs the elapsed time of its scope when dropped. While alive, it sits on a thread-local stack
+//! of the active scopes, so deeply nested code can query with `elapsed!()` how long the
+//! innermost enclosing scope has been running, without access to its guard.
+//!
+//! # Stack semantics
+//!
+//! - Each thread has its own stack: a scope entered on a thread is not visible from others.
+//! - Creating a guard pushes it on the stack, dropping it pops it (and any guard above it that
+//!   was leaked, e.g. with `std::mem::forget`). Nested guards are dropped in reverse order of
+//!   creation, as Rust drops the locals of a block.
+//! - `elapsed!()` and `current_elapsed()` read the innermost scope, i.e. the top of the stack.
+//!
+//! # Self time
+//!
+//! When a guard is dropped, its elapsed time is added to the time of the children of its
+//! parent scope, the guard below it on the stack. Each scope thus knows both its inclusive
+//! time, and its exclusive (or self) time: the inclusive time minus the time of its nested
+//! scopes. Both are accumulated by name in a process-wide table fed by all threads, which
+//! `report()` renders. The inclusive time of a name nested in itself counts the nested calls
+//! twice, its exclusive time does not.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::marker::PhantomData;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::logging::Level;
+use crate::profiler::{format_message, log_gated};
+
+/// An active scope on the thread-local stack.
+struct Frame {
+    start: Instant,
+    children: Duration,
+}
+
+thread_local! {
+    static STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+static STATS: OnceLock<Mutex<HashMap<&'static str, ScopeStats>>> = OnceLock::new();
+
+/// Locks the table of the statistics by name.
+fn stats_table() -> MutexGuard<'static, HashMap<&'static str, ScopeStats>> {
+    STATS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The `ScopeStats` struct holds the accumulated inclusive and exclusive times of the scopes
+/// of a name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScopeStats {
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration,
+}
+
+impl ScopeStats {
+    /// Returns the number of scopes dropped.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Returns the total time of the scopes, nested scopes included.
+    pub fn inclusive(&self) -> Duration {
+        self.inclusive
+    }
+
+    /// Returns the total time of the scopes, nested scopes excluded.
+    pub fn exclusive(&self) -> Duration {
+        self.exclusive
+    }
+}
+
+/// The `profile_scope!` macro starts profiling the rest of the enclosing block, logging at the
+/// info level its elapsed time when the block ends. The scope is pushed on the thread-local
+/// stack read by `elapsed!()`.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{elapsed, profile_scope};
+///
+/// fn nested() {
+///     // Time since the start of the innermost enclosing scope.
+///     let so_far = elapsed!().unwrap();
+///     println!("{:?}", so_far);
+/// }
+///
+/// {
+///     profile_scope!(outer);
+///     nested();
+/// } // logs "TimeLapse outer - Elapsed time: ..."
+/// assert_eq!(elapsed!(), None);
+/// ```
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:ident) => {
+        let _profile_scope_guard = $crate::scope::ScopeGuard::new(stringify!($name));
+    };
+}
+
+/// The `elapsed!` macro returns the elapsed time of a profiler.
+///
+/// - `elapsed!(profiler)` expands to `profiler.elapsed()`.
+/// - `elapsed!()` returns the elapsed time of the innermost scope started by `profile_scope!`
+///   on the current thread, as an `Option<Duration>`, `None` outside of any scope.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{elapsed, profile_start, TimeLapse};
+///
+/// profile_start!(my_profiler);
+/// let so_far = elapsed!(my_profiler);
+/// ```
+#[macro_export]
+macro_rules! elapsed {
+    () => {
+        $crate::scope::current_elapsed()
+    };
+    ($profiler:expr) => {
+        $profiler.elapsed()
+    };
+}
+
+/// The `ScopeGuard` struct logs the time elapsed since its creation when dropped, and keeps its
+/// scope on the thread-local stack while alive. It is created by `profile_scope!`.
+#[must_use = "the scope ends when the guard is dropped"]
+pub struct ScopeGuard {
+    name: &'static str,
+    start: Instant,
+    depth: usize,
+    // The guard is tied to the stack of the thread that created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ScopeGuard {
+    /// Creates a new `ScopeGuard` with the given name, pushing it on the thread-local stack.
+    pub fn new(name: &'static str) -> Self {
+        let start = Instant::now();
+        let depth = STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            stack.push(Frame {
+                start,
+                children: Duration::ZERO,
+            });
+            stack.len() - 1
+        });
+        ScopeGuard {
+            name,
+            start,
+            depth,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns the time elapsed since the guard was created.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let inclusive = self.elapsed();
+        let children = STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let children = stack
+                .get(self.depth)
+                .map_or(Duration::ZERO, |frame| frame.children);
+            stack.truncate(self.depth);
+            if let Some(parent) = stack.last_mut() {
+                parent.children += inclusive;
+            }
+            children
+        });
+
+        let mut table = stats_table();
+        let stats = table.entry(self.name).or_default();
+        stats.calls += 1;
+        stats.inclusive += inclusive;
+        stats.exclusive += inclusive.saturating_sub(children);
+        drop(table);
+
+        log_gated(self.name, inclusive, Level::Info, || {
+            format_message(self.name, inclusive)
+        });
+    }
+}
+
+/// Returns the elapsed time of the innermost active scope of the current thread, or `None`
+/// outside of any scope.
+pub fn current_elapsed() -> Option<Duration> {
+    STACK.with(|stack| stack.borrow().last().map(|frame| frame.start.elapsed()))
+}
+
+/// Returns the accumulated statistics of the scopes of a name, if any was dropped.
+pub fn stats(name: &str) -> Option<ScopeStats> {
+    stats_table().get(name).copied()
+}
+
+/// Returns a table of the accumulated statistics of all names, with their number of calls,
+/// inclusive and exclusive times, sorted by descending exclusive time, ties being broken by
+/// name.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::{profile_scope, scope};
+///
+/// fn load() {
+///     profile_scope!(doc_load);
+///     parse();
+/// }
+///
+/// fn parse() {
+///     profile_scope!(doc_parse);
+/// }
+///
+/// load();
+/// let load = scope::stats("doc_load").unwrap();
+/// assert!(load.exclusive() <= load.inclusive());
+/// println!("{}", scope::report());
+/// ```
+pub fn report() -> String {
+    let mut rows: Vec<(&str, ScopeStats)> = stats_table()
+        .iter()
+        .map(|(name, stats)| (*name, *stats))
+        .collect();
+    rows.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then_with(|| a.0.cmp(b.0)));
+
+    let width = rows
+        .iter()
+        .map(|row| row.0.len())
+        .max()
+        .unwrap_or(0)
+        .max("Name".len());
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{:<width$} {:>8} {:>12} {:>12}",
+        "Name", "Calls", "Inclusive", "Exclusive"
+    );
+    for (name, stats) in rows {
+        let _ = writeln!(
+            report,
+            "{:<width$} {:>8} {:>12} {:>12}",
+            name,
+            stats.calls,
+            format!("{:?}", stats.inclusive),
+            format!("{:?}", stats.exclusive)
+        );
+    }
+    report
+}
+
+/// Removes the statistics of all names.
+pub fn clear() {
+    stats_table().clear();
+}
+
+/// Returns the number of active scopes on the current thread.
+pub fn depth() -> usize {
+    STACK.with(|stack| stack.borrow().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_nesting() {
+        assert_eq!(current_elapsed(), None);
+        {
+            profile_scope!(outer);
+            std::thread::sleep(Duration::from_millis(20));
+            assert_eq!(depth(), 1);
+            {
+                profile_scope!(inner);
+                assert_eq!(depth(), 2);
+                assert!(elapsed!().unwrap() < Duration::from_millis(20));
+            }
+            assert_eq!(depth(), 1);
+            assert!(elapsed!().unwrap() >= Duration::from_millis(20));
+        }
+        assert_eq!(depth(), 0);
+        assert_eq!(elapsed!(), None);
+    }
+
+    #[test]
+    fn test_scope_leaked_guard() {
+        let outer = ScopeGuard::new("outer");
+        std::mem::forget(ScopeGuard::new("leaked"));
+        assert_eq!(depth(), 2);
+        drop(outer);
+        assert_eq!(depth(), 0);
+    }
+
+    #[test]
+    fn test_scope_self_time() {
+        {
+            profile_scope!(test_scope_self_time_outer);
+            std::thread::sleep(Duration::from_millis(5));
+            for _ in 0..2 {
+                profile_scope!(test_scope_self_time_inner);
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        let outer = stats("test_scope_self_time_outer").unwrap();
+        let inner = stats("test_scope_self_time_inner").unwrap();
+        assert_eq!((outer.calls(), inner.calls()), (1, 2));
+        assert!(inner.inclusive() >= Duration::from_millis(20));
+        assert_eq!(inner.exclusive(), inner.inclusive());
+        assert!(outer.inclusive() >= inner.inclusive() + Duration::from_millis(5));
+        assert!(outer.exclusive() >= Duration::from_millis(5));
+        assert!(outer.exclusive() + inner.inclusive() <= outer.inclusive());
+
+        let report = report();
+        assert!(report.starts_with("Name"));
+        assert!(report.contains("Exclusive"));
+        assert!(report
+            .lines()
+            .any(|line| line.starts_with("test_scope_self_time_inner")));
+    }
+
+    #[test]
+    fn test_scope_threads() {
+        profile_scope!(main);
+        std::thread::spawn(|| assert_eq!(elapsed!(), None))
+            .join()
+            .unwrap();
+        assert!(elapsed!().is_some());
+    }
+}
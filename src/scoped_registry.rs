@@ -0,0 +1,212 @@
+//! Registries of elapsed times isolated by session.
+//!
+//! This module provides the `ScopedRegistry` struct, accumulating elapsed times by name like
+//! the global `registry`, but separately for each session id, e.g. the id of a request in a
+//! web server. Concurrent sessions never mix their timings, and the breakdown of each one is
+//! available with `report_for()` once it ends, to be cleared with `clear()`.
+//!
+//! The id is passed explicitly to every recording. In asynchronous code, the simplest way is
+//! to move a clone of the id, and of an `Arc` of the registry, into each spawned task:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use timelapse::scoped_registry::ScopedRegistry;
+//!
+//! let timings = Arc::new(ScopedRegistry::new());
+//! let request_id = "req-42".to_string();
+//!
+//! let worker = {
+//!     let (timings, request_id) = (Arc::clone(&timings), request_id.clone());
+//!     std::thread::spawn(move || {
+//!         // The same applies to the future given to the spawn of an async runtime.
+//!         let _guard = timings.scope(request_id, "db.query");
+//!         // ... querying ...
+//!     })
+//! };
+//! worker.join().unwrap();
+//!
+//! assert_eq!(timings.report_for(&request_id).len(), 1);
+//! timings.clear(&request_id);
+//! ```
+//!
+//! When the id is not at hand deep in the call stack, it can be kept in a task-local value of
+//! the async runtime (e.g. `tokio::task_local!`), set once per request and read where timings
+//! are recorded, or in a `thread_local!` for synchronous servers with a thread per request.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::registry::Entry;
+use crate::TimeLapse;
+
+/// The `ScopedRegistry` struct accumulates elapsed times by name, separately for each session
+/// id of type `K`. It is thread-safe, and is usually shared in an `Arc` or a static.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::scoped_registry::ScopedRegistry;
+///
+/// let timings = ScopedRegistry::new();
+/// timings.record(1u64, "parse", Duration::from_millis(3));
+/// timings.record(2u64, "parse", Duration::from_millis(5));
+/// timings.record(1u64, "render", Duration::from_millis(8));
+/// assert_eq!(
+///     timings.report_for(&1),
+///     [
+///         ("parse".to_string(), Duration::from_millis(3)),
+///         ("render".to_string(), Duration::from_millis(8)),
+///     ]
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ScopedRegistry<K> {
+    sessions: Mutex<HashMap<K, BTreeMap<String, Entry>>>,
+}
+
+impl<K: Eq + Hash> ScopedRegistry<K> {
+    /// Creates a new empty `ScopedRegistry`.
+    pub fn new() -> Self {
+        ScopedRegistry {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds an elapsed time under the given name to the session with the given id.
+    pub fn record(&self, id: K, name: &str, elapsed: Duration) {
+        let mut sessions = self.lock();
+        let entries = sessions.entry(id).or_default();
+        match entries.get_mut(name) {
+            Some(entry) => entry.add(elapsed),
+            None => entries.entry(name.to_string()).or_default().add(elapsed),
+        }
+    }
+
+    /// Returns a guard that records its elapsed time under the given name into the session
+    /// with the given id when dropped.
+    pub fn scope(&self, id: K, name: impl Into<String>) -> ScopedRegistryGuard<'_, K> {
+        ScopedRegistryGuard {
+            registry: self,
+            id: Some(id),
+            name: name.into(),
+            timer: TimeLapse::new(),
+        }
+    }
+
+    /// Returns the accumulated entry for the given name in the session with the given id, if
+    /// anything was recorded under it.
+    pub fn get(&self, id: &K, name: &str) -> Option<Entry> {
+        self.lock().get(id)?.get(name).copied()
+    }
+
+    /// Returns the total elapsed time of every name in the session with the given id, sorted
+    /// by name. An unknown session gives an empty report.
+    pub fn report_for(&self, id: &K) -> Vec<(String, Duration)> {
+        self.lock().get(id).map_or_else(Vec::new, |entries| {
+            entries
+                .iter()
+                .map(|(name, entry)| (name.clone(), entry.total()))
+                .collect()
+        })
+    }
+
+    /// Removes the session with the given id and all its entries.
+    pub fn clear(&self, id: &K) {
+        self.lock().remove(id);
+    }
+
+    /// Returns the number of sessions with recorded entries.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no session has recorded entries.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Locks the sessions. Sessions poisoned by a panic are still usable, as entries are only
+    /// updated once the new values are fully computed.
+    fn lock(&self) -> MutexGuard<'_, HashMap<K, BTreeMap<String, Entry>>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Implements the `Default` trait for the `ScopedRegistry` struct, creating an empty registry.
+impl<K: Eq + Hash> std::default::Default for ScopedRegistry<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `ScopedRegistryGuard` struct records the time elapsed since its creation into a session
+/// of a `ScopedRegistry` when dropped. It is created by `ScopedRegistry::scope()`.
+#[must_use = "the elapsed time is recorded when the guard is dropped"]
+pub struct ScopedRegistryGuard<'a, K: Eq + Hash> {
+    registry: &'a ScopedRegistry<K>,
+    id: Option<K>,
+    name: String,
+    timer: TimeLapse,
+}
+
+impl<K: Eq + Hash> Drop for ScopedRegistryGuard<'_, K> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.registry.record(id, &self.name, self.timer.elapsed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scoped_registry() {
+        let timings = ScopedRegistry::new();
+        assert!(timings.is_empty());
+        timings.record("a", "parse", Duration::from_millis(3));
+        timings.record("a", "parse", Duration::from_millis(2));
+        timings.record("b", "parse", Duration::from_millis(7));
+        drop(timings.scope("a", "emit"));
+
+        assert_eq!(timings.len(), 2);
+        assert_eq!(timings.get(&"a", "parse").unwrap().count(), 2);
+        let report = timings.report_for(&"a");
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[1], ("parse".to_string(), Duration::from_millis(5)));
+        assert_eq!(
+            timings.report_for(&"b"),
+            [("parse".to_string(), Duration::from_millis(7))]
+        );
+        assert!(timings.report_for(&"unknown").is_empty());
+
+        timings.clear(&"a");
+        assert!(timings.report_for(&"a").is_empty());
+        assert_eq!(timings.len(), 1);
+    }
+
+    #[test]
+    fn test_scoped_registry_threads() {
+        let timings = Arc::new(ScopedRegistry::new());
+        let requests: Vec<_> = (0..4u64)
+            .map(|id| {
+                let timings = Arc::clone(&timings);
+                std::thread::spawn(move || {
+                    for _ in 0..=id {
+                        timings.record(id, "handle", Duration::from_millis(1));
+                    }
+                })
+            })
+            .collect();
+        for request in requests {
+            request.join().unwrap();
+        }
+        for id in 0..4 {
+            assert_eq!(timings.get(&id, "handle").unwrap().count(), id + 1);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! A `TimeLapse` usable through shared references.
+//!
+//! This module provides the `SharedTimeLapse` struct, wrapping a `TimeLapse` in a `Mutex` so
+//! that laps can be recorded and the timer reset through a `&self`. It is `Send + Sync` and
+//! can live in an `Arc` shared between threads or async tasks, at the cost of a lock per call.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::TimeLapse;
+
+/// The `SharedTimeLapse` struct is a `TimeLapse` with interior mutability.
+///
+/// # Usage
+/// ```rust
+/// use std::sync::Arc;
+/// use timelapse::SharedTimeLapse;
+///
+/// let profiler = Arc::new(SharedTimeLapse::new());
+/// let worker = {
+///     let profiler = Arc::clone(&profiler);
+///     std::thread::spawn(move || {
+///         profiler.lap("worker");
+///     })
+/// };
+/// worker.join().unwrap();
+/// profiler.lap("main");
+/// assert_eq!(profiler.laps().len(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct SharedTimeLapse {
+    inner: Mutex<TimeLapse>,
+}
+
+impl SharedTimeLapse {
+    /// Creates a new `SharedTimeLapse` instance, starting the timer immediately.
+    pub fn new() -> Self {
+        SharedTimeLapse {
+            inner: Mutex::new(TimeLapse::new()),
+        }
+    }
+
+    /// Locks the inner timer. A timer poisoned by a panic is still usable.
+    fn lock(&self) -> MutexGuard<'_, TimeLapse> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the elapsed time since the instance was created or reset.
+    pub fn elapsed(&self) -> Duration {
+        self.lock().elapsed()
+    }
+
+    /// Records a lap with the given label and returns its duration.
+    /// See `TimeLapse::lap()`.
+    pub fn lap(&self, label: &str) -> Duration {
+        self.lock().lap(label)
+    }
+
+    /// Returns a copy of the laps recorded since the instance was created or reset.
+    pub fn laps(&self) -> Vec<(String, Duration)> {
+        self.lock().laps().to_vec()
+    }
+
+    /// Resets the timer, starting a new measurement from the current time.
+    /// See `TimeLapse::reset()`.
+    pub fn reset(&self) {
+        self.lock().reset();
+    }
+
+    /// Gives temporary access to the inner `TimeLapse` while holding the lock.
+    pub fn with<R>(&self, f: impl FnOnce(&mut TimeLapse) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Consumes the instance, returning the inner `TimeLapse`.
+    pub fn into_inner(self) -> TimeLapse {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_shared_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedTimeLapse>();
+
+        let profiler = Arc::new(SharedTimeLapse::new());
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let profiler = Arc::clone(&profiler);
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        profiler.lap("worker");
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let laps = profiler.laps();
+        assert_eq!(laps.len(), 20);
+        let total: Duration = laps.iter().map(|(_, lap)| *lap).sum();
+        assert!(total <= profiler.elapsed());
+
+        profiler.reset();
+        assert!(profiler.laps().is_empty());
+    }
+}
@@ -0,0 +1,385 @@
+//! A frozen measurement taken from a `TimeLapse`.
+//!
+//! This module provides the `Snapshot` struct, a lightweight `Copy` value holding an
+//! elapsed duration and an optional name. Unlike `TimeLapse`, a snapshot never changes
+//! once taken, which makes it suitable for reporting and for arithmetic in iterator pipelines.
+//!
+//! Snapshots can also be encoded into a compact binary form with `to_bytes()` and decoded
+//! back with `from_bytes()`.
+
+use std::iter::Sum;
+use std::ops::{Add, Sub};
+use std::time::{Duration, Instant};
+
+use crate::TimeLapse;
+
+/// The `Snapshot` struct is a frozen elapsed time, optionally carrying a name.
+///
+/// Snapshots can be added (saturating at `Duration::MAX`), subtracted (saturating at zero) and
+/// summed. The result of these operations carries no name.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::Snapshot;
+///
+/// let snapshots = [
+///     Snapshot::new(Some("parse"), Duration::from_millis(10)),
+///     Snapshot::new(Some("build"), Duration::from_millis(30)),
+/// ];
+///
+/// let total = snapshots.iter().copied().sum::<Snapshot>();
+/// assert_eq!(total.elapsed(), Duration::from_millis(40));
+/// assert_eq!(total.name(), None);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Snapshot<'a> {
+    name: Option<&'a str>,
+    elapsed: Duration,
+}
+
+impl<'a> Snapshot<'a> {
+    /// Creates a new `Snapshot` with the given name and elapsed time.
+    pub fn new(name: Option<&'a str>, elapsed: Duration) -> Self {
+        Snapshot { name, elapsed }
+    }
+
+    /// Returns the name of the snapshot, if any.
+    pub fn name(&self) -> Option<&'a str> {
+        self.name
+    }
+
+    /// Creates an unnamed `Snapshot` of the span between two timestamps in nanoseconds, e.g. read
+    /// from a recorded event log. An end before the start gives a zero elapsed time.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::Snapshot;
+    ///
+    /// let span = Snapshot::from_span_nanos(1_000_000, 3_500_000);
+    /// assert_eq!(span.elapsed(), Duration::from_micros(2500));
+    /// ```
+    pub fn from_span_nanos(start: u64, end: u64) -> Snapshot<'static> {
+        Snapshot::new(None, Duration::from_nanos(end.saturating_sub(start)))
+    }
+
+    /// Returns the elapsed time frozen in the snapshot.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Encodes the snapshot into a compact fixed-layout binary form.
+    ///
+    /// The wire layout is:
+    ///
+    /// | Offset | Size  | Content                                                    |
+    /// |--------|-------|------------------------------------------------------------|
+    /// | 0      | 8     | Elapsed time in nanoseconds, `u64` little-endian           |
+    /// | 8      | 4     | Name length in bytes, `u32` little-endian (`u32::MAX`: no name) |
+    /// | 12     | len   | Name as UTF-8 bytes                                        |
+    ///
+    /// Elapsed times larger than `u64::MAX` nanoseconds (more than 584 years) saturate. A name
+    /// of `u32::MAX` bytes or more cannot be encoded and gives `EncodeError::NameTooLong`.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use std::time::Duration;
+    /// use timelapse::Snapshot;
+    ///
+    /// let snapshot = Snapshot::new(Some("parse"), Duration::from_micros(1500));
+    /// let bytes = snapshot.to_bytes().unwrap();
+    /// assert_eq!(bytes.len(), 12 + "parse".len());
+    /// assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let nanos = u64::try_from(self.elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let name = self.name.unwrap_or("");
+        let len = match self.name {
+            Some(name) => encode_name_len(name.len())?,
+            None => NO_NAME,
+        };
+        let mut bytes = Vec::with_capacity(HEADER_LEN + name.len());
+        bytes.extend_from_slice(&nanos.to_le_bytes());
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        Ok(bytes)
+    }
+
+    /// Decodes a snapshot from the binary form produced by `to_bytes()`.
+    /// The decoded name borrows from `bytes`, no allocation takes place.
+    /// See `to_bytes()` for the wire layout.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DecodeError::Truncated);
+        }
+        let nanos = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let len = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let rest = &bytes[HEADER_LEN..];
+        let name = if len == NO_NAME {
+            if !rest.is_empty() {
+                return Err(DecodeError::TrailingBytes);
+            }
+            None
+        } else {
+            let len = len as usize;
+            if rest.len() < len {
+                return Err(DecodeError::Truncated);
+            }
+            if rest.len() > len {
+                return Err(DecodeError::TrailingBytes);
+            }
+            Some(std::str::from_utf8(rest).map_err(|_| DecodeError::InvalidName)?)
+        };
+        Ok(Snapshot::new(name, Duration::from_nanos(nanos)))
+    }
+}
+
+/// Size of the fixed part of the binary encoding: nanoseconds and name length.
+const HEADER_LEN: usize = 12;
+
+/// Name length sentinel used in the binary encoding for a snapshot without a name.
+const NO_NAME: u32 = u32::MAX;
+
+/// Returns the encoded length of a name of `len` bytes, which must be below the `NO_NAME`
+/// sentinel.
+pub(crate) fn encode_name_len(len: usize) -> Result<u32, EncodeError> {
+    u32::try_from(len)
+        .ok()
+        .filter(|len| *len != NO_NAME)
+        .ok_or(EncodeError::NameTooLong)
+}
+
+/// The error returned by `Snapshot::to_bytes()` and `Accumulator::to_bytes()` when the value
+/// cannot be encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The name is `u32::MAX` bytes long or more.
+    NameTooLong,
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::NameTooLong => write!(f, "name too long to encode"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// The error returned by `Snapshot::from_bytes()` and `Accumulator::from_bytes()` when the input is not a valid encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input is shorter than the encoding requires.
+    Truncated,
+    /// The input contains bytes past the end of the encoding.
+    TrailingBytes,
+    /// The encoded name is not valid UTF-8.
+    InvalidName,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "truncated snapshot encoding"),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after snapshot encoding"),
+            DecodeError::InvalidName => write!(f, "snapshot name is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl TimeLapse {
+    /// Returns an unnamed `Snapshot` of the current elapsed time.
+    pub fn snapshot(&self) -> Snapshot<'static> {
+        Snapshot::new(None, self.elapsed())
+    }
+
+    /// Returns a `Snapshot` of the current elapsed time carrying the given name.
+    pub fn snapshot_named<'a>(&self, name: &'a str) -> Snapshot<'a> {
+        Snapshot::new(Some(name), self.elapsed())
+    }
+
+    /// Returns an unnamed `Snapshot` of the span between two explicit instants, e.g. when
+    /// replaying recorded events instead of timing live. An end before the start gives a zero
+    /// elapsed time.
+    pub fn from_span(start: Instant, end: Instant) -> Snapshot<'static> {
+        Snapshot::new(None, end.saturating_duration_since(start))
+    }
+}
+
+/// Implements the `Display` trait for the `Snapshot` struct.
+/// The alternate flag (`{:#}`) gives a compact form with only the elapsed time (e.g. `10.2ms`).
+///
+/// Unlike a `TimeLapse`, whose elapsed time is read again each time it is formatted, a snapshot
+/// always renders its stored elapsed time, so formatting it is idempotent:
+///
+/// ```rust
+/// use timelapse::TimeLapse;
+///
+/// let snapshot = TimeLapse::new().snapshot_named("parse");
+/// let logged = format!("{snapshot}");
+/// std::thread::sleep(std::time::Duration::from_millis(1));
+/// assert_eq!(format!("{snapshot}"), logged);
+/// ```
+impl std::fmt::Display for Snapshot<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return write!(f, "{:?}", self.elapsed);
+        }
+        match self.name {
+            Some(name) => write!(f, "{} - Elapsed time: {:?}", name, self.elapsed),
+            None => write!(f, "Elapsed time: {:?}", self.elapsed),
+        }
+    }
+}
+
+/// Adds the elapsed times of two snapshots, saturating at `Duration::MAX`. The result carries
+/// no name.
+impl<'a> Add for Snapshot<'a> {
+    type Output = Snapshot<'a>;
+
+    fn add(self, other: Self) -> Self::Output {
+        Snapshot::new(None, self.elapsed.saturating_add(other.elapsed))
+    }
+}
+
+/// Subtracts the elapsed times of two snapshots, saturating at zero. The result carries no name.
+impl<'a> Sub for Snapshot<'a> {
+    type Output = Snapshot<'a>;
+
+    fn sub(self, other: Self) -> Self::Output {
+        Snapshot::new(None, self.elapsed.saturating_sub(other.elapsed))
+    }
+}
+
+/// Sums an iterator of snapshots. The result carries no name.
+impl<'a> Sum for Snapshot<'a> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Snapshot::default(), |acc, s| acc + s)
+    }
+}
+
+/// Sums an iterator of snapshot references. The result carries no name.
+impl<'a, 'b> Sum<&'b Snapshot<'a>> for Snapshot<'a> {
+    fn sum<I: Iterator<Item = &'b Snapshot<'a>>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::VirtualClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_snapshot_arithmetic() {
+        let a = Snapshot::new(Some("a"), Duration::from_millis(30));
+        let b = Snapshot::new(Some("b"), Duration::from_millis(10));
+
+        assert_eq!((a + b).elapsed(), Duration::from_millis(40));
+        assert_eq!((a - b).elapsed(), Duration::from_millis(20));
+        assert_eq!((b - a).elapsed(), Duration::ZERO);
+        assert_eq!((a + b).name(), None);
+
+        let max = Snapshot::new(None, Duration::MAX);
+        assert_eq!((max + a).elapsed(), Duration::MAX);
+        assert_eq!([max, max].iter().sum::<Snapshot>().elapsed(), Duration::MAX);
+    }
+
+    #[test]
+    fn test_snapshot_sum() {
+        let snapshots = [
+            Snapshot::new(Some("a"), Duration::from_millis(1)),
+            Snapshot::new(Some("b"), Duration::from_millis(2)),
+            Snapshot::new(None, Duration::from_millis(3)),
+        ];
+        let total: Snapshot = snapshots.iter().copied().sum();
+        assert_eq!(total.elapsed(), Duration::from_millis(6));
+        assert_eq!(snapshots.iter().sum::<Snapshot>(), total);
+    }
+
+    #[test]
+    fn test_snapshot_bytes_round_trip() {
+        let named = Snapshot::new(Some("région"), Duration::new(3, 141_592_653));
+        let unnamed = Snapshot::new(None, Duration::from_nanos(42));
+        let empty = Snapshot::new(Some(""), Duration::ZERO);
+
+        for snapshot in [named, unnamed, empty] {
+            let bytes = snapshot.to_bytes().unwrap();
+            assert_eq!(Snapshot::from_bytes(&bytes), Ok(snapshot));
+        }
+        assert_eq!(unnamed.to_bytes().unwrap().len(), HEADER_LEN);
+    }
+
+    #[test]
+    fn test_snapshot_bytes_errors() {
+        let bytes = Snapshot::new(Some("name"), Duration::from_nanos(1))
+            .to_bytes()
+            .unwrap();
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..4]),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..14]),
+            Err(DecodeError::Truncated)
+        );
+
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(Snapshot::from_bytes(&long), Err(DecodeError::TrailingBytes));
+
+        let mut invalid = bytes;
+        invalid[HEADER_LEN] = 0xff;
+        assert_eq!(
+            Snapshot::from_bytes(&invalid),
+            Err(DecodeError::InvalidName)
+        );
+
+        assert_eq!(encode_name_len(5), Ok(5));
+        assert_eq!(
+            encode_name_len(u32::MAX as usize),
+            Err(EncodeError::NameTooLong)
+        );
+    }
+
+    #[test]
+    fn test_snapshot_display() {
+        let snapshot = Snapshot::new(Some("parse"), Duration::from_micros(10_200));
+        assert_eq!(format!("{}", snapshot), "parse - Elapsed time: 10.2ms");
+        assert_eq!(format!("{:#}", snapshot), "10.2ms");
+        let unnamed = Snapshot::new(None, Duration::from_micros(10_200));
+        assert_eq!(format!("{}", unnamed), "Elapsed time: 10.2ms");
+    }
+
+    #[test]
+    fn test_snapshot_from_span() {
+        let start = Instant::now();
+        let end = start + Duration::from_millis(15);
+        assert_eq!(
+            TimeLapse::from_span(start, end).elapsed(),
+            Duration::from_millis(15)
+        );
+        assert_eq!(TimeLapse::from_span(end, start).elapsed(), Duration::ZERO);
+        assert_eq!(
+            Snapshot::from_span_nanos(10, 1_510).elapsed(),
+            Duration::from_nanos(1_500)
+        );
+        assert_eq!(Snapshot::from_span_nanos(10, 5).elapsed(), Duration::ZERO);
+        assert_eq!(Snapshot::from_span_nanos(10, 5).name(), None);
+    }
+
+    #[test]
+    fn test_snapshot_from_timelapse() {
+        let clock = Arc::new(VirtualClock::new());
+        let profiler = TimeLapse::new().with_clock(clock.clone());
+        clock.advance(Duration::from_millis(10));
+        let snapshot = profiler.snapshot_named("region");
+        assert_eq!(snapshot.name(), Some("region"));
+        assert_eq!(snapshot.elapsed(), Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,167 @@
+//! A stopwatch with the usual start, stop and reset semantics.
+//!
+//! This module provides the `Stopwatch` struct, a thin wrapper around `TimeLapse` for users
+//! expecting the semantics of the stopwatches of other languages and platforms:
+//!
+//! - a new stopwatch is stopped at zero, and counts once `start()` is called;
+//! - `stop()` pauses it and `start()` resumes it, the elapsed time accumulating over the
+//!   running periods;
+//! - `reset()` stops it and sets it back to zero, `restart()` sets it back to zero and starts it.
+//!
+//! A stopwatch converts from and into a `TimeLapse`, to use the rest of the crate with it.
+
+use std::time::Duration;
+
+use crate::TimeLapse;
+
+/// The `Stopwatch` struct measures the elapsed time over its running periods.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::Stopwatch;
+///
+/// let mut stopwatch = Stopwatch::new();
+/// assert!(!stopwatch.is_running());
+///
+/// stopwatch.start();
+/// // ... timed work ...
+/// stopwatch.stop();
+/// // ... untimed work ...
+/// stopwatch.start();
+/// // ... more timed work ...
+/// stopwatch.stop();
+/// println!("{:?}", stopwatch.elapsed());
+///
+/// stopwatch.reset();
+/// assert_eq!(stopwatch.elapsed(), std::time::Duration::ZERO);
+/// ```
+#[derive(Debug)]
+pub struct Stopwatch {
+    timer: TimeLapse,
+}
+
+impl Stopwatch {
+    /// Creates a new `Stopwatch`, stopped at zero.
+    pub fn new() -> Self {
+        Stopwatch {
+            timer: TimeLapse::uninit(),
+        }
+    }
+
+    /// Creates a new `Stopwatch` and starts it.
+    pub fn start_new() -> Self {
+        Stopwatch {
+            timer: TimeLapse::new(),
+        }
+    }
+
+    /// Starts or resumes the stopwatch. Starting a running stopwatch does nothing.
+    pub fn start(&mut self) {
+        self.timer.resume();
+    }
+
+    /// Stops the stopwatch, keeping its elapsed time, and returns the elapsed time.
+    /// Stopping a stopped stopwatch does nothing.
+    pub fn stop(&mut self) -> Duration {
+        if self.is_running() {
+            self.timer.pause()
+        } else {
+            self.timer.elapsed()
+        }
+    }
+
+    /// Stops the stopwatch and sets its elapsed time back to zero.
+    pub fn reset(&mut self) {
+        let timer = TimeLapse::uninit();
+        self.timer = match self.timer.clock() {
+            Some(clock) => timer.with_clock(clock),
+            None => timer,
+        };
+    }
+
+    /// Sets the elapsed time of the stopwatch back to zero and starts it.
+    pub fn restart(&mut self) {
+        self.timer.reset();
+    }
+
+    /// Returns `true` if the stopwatch is running.
+    pub fn is_running(&self) -> bool {
+        self.timer.is_running()
+    }
+
+    /// Returns the elapsed time over the running periods since the last reset.
+    pub fn elapsed(&self) -> Duration {
+        self.timer.elapsed()
+    }
+
+    /// Returns the underlying `TimeLapse`.
+    pub fn as_timelapse(&self) -> &TimeLapse {
+        &self.timer
+    }
+}
+
+/// Implements the `Default` trait for the `Stopwatch` struct, stopped at zero.
+impl std::default::Default for Stopwatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a `TimeLapse` into a `Stopwatch`, running if the timer is.
+impl From<TimeLapse> for Stopwatch {
+    fn from(timer: TimeLapse) -> Self {
+        Stopwatch { timer }
+    }
+}
+
+/// Converts a `Stopwatch` into its underlying `TimeLapse`.
+impl From<Stopwatch> for TimeLapse {
+    fn from(stopwatch: Stopwatch) -> Self {
+        stopwatch.timer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::VirtualClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_stopwatch() {
+        let clock = Arc::new(VirtualClock::new());
+        let mut stopwatch = Stopwatch::from(TimeLapse::uninit().with_clock(clock.clone()));
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+        assert_eq!(stopwatch.stop(), Duration::ZERO);
+
+        stopwatch.start();
+        clock.advance(Duration::from_millis(10));
+        stopwatch.start();
+        let first = stopwatch.stop();
+        assert_eq!(first, Duration::from_millis(10));
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(stopwatch.stop(), first);
+
+        stopwatch.start();
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(stopwatch.stop(), Duration::from_millis(20));
+
+        stopwatch.reset();
+        assert!(!stopwatch.is_running());
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+        stopwatch.restart();
+        assert!(stopwatch.is_running());
+        clock.advance(Duration::from_millis(3));
+        assert_eq!(stopwatch.elapsed(), Duration::from_millis(3));
+    }
+
+    #[test]
+    fn test_stopwatch_conversions() {
+        let stopwatch = Stopwatch::from(TimeLapse::new());
+        assert!(stopwatch.is_running());
+        let timer = TimeLapse::from(Stopwatch::start_new());
+        assert!(timer.is_running());
+        assert!(!Stopwatch::default().is_running());
+    }
+}
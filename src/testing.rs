@@ -0,0 +1,89 @@
+//! Helpers for testing timing code.
+//!
+//! This module provides the `VirtualClock` struct, a `Clock` that only moves when told to.
+//! Injected into a `TimeLapse` with `TimeLapse::with_clock()`, it makes the measured times
+//! exact and the tests fast, with no `thread::sleep()`:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use timelapse::testing::VirtualClock;
+//! use timelapse::TimeLapse;
+//!
+//! let clock = Arc::new(VirtualClock::new());
+//! let mut profiler = TimeLapse::new().with_clock(clock.clone());
+//! clock.advance(Duration::from_millis(10));
+//! assert_eq!(profiler.lap("parse"), Duration::from_millis(10));
+//! profiler.pause();
+//! clock.advance(Duration::from_secs(1)); // not counted
+//! profiler.resume();
+//! clock.advance(Duration::from_millis(5));
+//! assert_eq!(profiler.elapsed(), Duration::from_millis(15));
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+
+/// The `VirtualClock` struct is a clock advanced by hand with `advance()`. It is shared
+/// through an `Arc` between the timers reading it and the test driving it.
+#[derive(Debug)]
+pub struct VirtualClock {
+    origin: Instant,
+    offset_nanos: AtomicU64,
+}
+
+impl VirtualClock {
+    /// Creates a new `VirtualClock`, its time standing at the current time until advanced.
+    pub fn new() -> Self {
+        VirtualClock {
+            origin: Instant::now(),
+            offset_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Moves the clock forward by the given duration, saturating after about 584 years.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self
+            .offset_nanos
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |offset| {
+                Some(offset.saturating_add(nanos))
+            });
+    }
+
+    /// Returns the total duration the clock was advanced by since its creation.
+    pub fn offset(&self) -> Duration {
+        Duration::from_nanos(self.offset_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Implements the `Default` trait for the `VirtualClock` struct, standing at the current time.
+impl std::default::Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.offset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock() {
+        let clock = VirtualClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::from_millis(10));
+        clock.advance(Duration::from_micros(5));
+        assert_eq!(clock.now() - start, Duration::from_micros(10_005));
+        assert_eq!(clock.offset(), Duration::from_micros(10_005));
+    }
+}
@@ -0,0 +1,340 @@
+//! Per-region latency thresholds loaded from a configuration text.
+//!
+//! This module provides the `ThresholdMap` struct, parsed from lines of the form
+//! `region = duration`, e.g.:
+//!
+//! ```text
+//! # Slow query threshold
+//! db.query = 50ms
+//! render = 16.7ms
+//! ```
+//!
+//! Durations are read by `format::parse_duration()`: a number, optionally fractional, followed
+//! by a unit among `ns`, `us` (or `µs`), `ms`, `s` and `m`. Blank lines and lines starting
+//! with `#` are ignored.
+//!
+//! The map installed with `set_thresholds()` is consulted by name by
+//! `TimeLapse::log_if_slow()`, so what counts as slow can be tuned without recompiling.
+//!
+//! With the `backtrace` feature, the slow measurements are logged with a trimmed backtrace of
+//! the caller, so it is known where a slow region was entered from. The backtrace is only
+//! captured once the threshold is exceeded, as capturing it is expensive.
+
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+use std::time::Duration;
+
+use crate::format::parse_duration;
+use crate::logging::Level;
+use crate::profiler::{format_message, log_gated};
+use crate::TimeLapse;
+
+/// The `ThresholdMap` struct maps region names to latency thresholds.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::thresholds::{set_thresholds, ThresholdMap};
+/// use timelapse::TimeLapse;
+///
+/// let thresholds = ThresholdMap::parse("db.query = 50ms\nrender = 16.7ms").unwrap();
+/// assert_eq!(thresholds.get("render"), Some(Duration::from_micros(16_700)));
+/// set_thresholds(thresholds);
+///
+/// let profiler = TimeLapse::new();
+/// // ... querying ...
+/// profiler.log_if_slow("db.query"); // logs only if slower than 50ms
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThresholdMap {
+    thresholds: HashMap<String, Duration>,
+}
+
+impl ThresholdMap {
+    /// Creates a new empty `ThresholdMap`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a configuration text of `region = duration` lines. A region defined several
+    /// times keeps its last threshold.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut map = ThresholdMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |kind| ParseError {
+                line: index + 1,
+                kind,
+            };
+            let (name, duration) = line
+                .split_once('=')
+                .ok_or(error(ParseErrorKind::MissingSeparator))?;
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(error(ParseErrorKind::EmptyName));
+            }
+            let duration =
+                parse_duration(duration).map_err(|_| error(ParseErrorKind::InvalidDuration))?;
+            map.insert(name, duration);
+        }
+        Ok(map)
+    }
+
+    /// Sets the threshold of a region.
+    pub fn insert(&mut self, name: impl Into<String>, threshold: Duration) {
+        self.thresholds.insert(name.into(), threshold);
+    }
+
+    /// Returns the threshold of a region, if any.
+    pub fn get(&self, name: &str) -> Option<Duration> {
+        self.thresholds.get(name).copied()
+    }
+
+    /// Returns the number of regions with a threshold.
+    pub fn len(&self) -> usize {
+        self.thresholds.len()
+    }
+
+    /// Returns `true` if no region has a threshold.
+    pub fn is_empty(&self) -> bool {
+        self.thresholds.is_empty()
+    }
+}
+
+/// Implements the `FromStr` trait for the `ThresholdMap` struct. See `ThresholdMap::parse()`.
+impl std::str::FromStr for ThresholdMap {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        ThresholdMap::parse(text)
+    }
+}
+
+/// The error returned by `ThresholdMap::parse()` for an invalid line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
+
+impl ParseError {
+    /// Returns the number of the invalid line, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns what is wrong with the line.
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+}
+
+/// The kinds of `ParseError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The line has no `=` between the region and the duration.
+    MissingSeparator,
+    /// The region name is empty.
+    EmptyName,
+    /// The duration is not a number followed by one of the `ns`, `us`, `µs`, `ms`, `s`, `m`
+    /// units.
+    InvalidDuration,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self.kind {
+            ParseErrorKind::MissingSeparator => "expected `region = duration`",
+            ParseErrorKind::EmptyName => "empty region name",
+            ParseErrorKind::InvalidDuration => {
+                "invalid duration, expected a number with a ns, us, ms, s or m unit"
+            }
+        };
+        write!(f, "line {}: {}", self.line, message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The thresholds installed with `set_thresholds()`, `None` if none were.
+static THRESHOLDS: RwLock<Option<ThresholdMap>> = RwLock::new(None);
+
+/// Installs the thresholds consulted by `TimeLapse::log_if_slow()`.
+pub fn set_thresholds(thresholds: ThresholdMap) {
+    *THRESHOLDS.write().unwrap_or_else(PoisonError::into_inner) = Some(thresholds);
+}
+
+/// Returns the installed threshold of a region, if any.
+pub fn threshold(name: &str) -> Option<Duration> {
+    THRESHOLDS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .and_then(|thresholds| thresholds.get(name))
+}
+
+/// The maximum number of frames of the backtraces logged with the `backtrace` feature.
+#[cfg(feature = "backtrace")]
+const BACKTRACE_FRAMES: usize = 10;
+
+/// Captures the backtrace of the caller of the logging functions, trimmed by
+/// `trim_backtrace()`.
+#[cfg(feature = "backtrace")]
+fn trimmed_backtrace() -> String {
+    trim_backtrace(&std::backtrace::Backtrace::force_capture().to_string())
+}
+
+/// Trims a rendered backtrace down to the frames of interest: the frames of the logging
+/// functions themselves and those of the Rust runtime are dropped, and at most
+/// `BACKTRACE_FRAMES` frames are kept.
+#[cfg(feature = "backtrace")]
+fn trim_backtrace(backtrace: &str) -> String {
+    let mut frames: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in backtrace.lines() {
+        let trimmed = line.trim_start();
+        match trimmed.split_once(": ") {
+            Some((index, function))
+                if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                frames.push((function, vec![line]));
+            }
+            _ if trimmed.starts_with("at ") => {
+                if let Some((_, lines)) = frames.last_mut() {
+                    lines.push(line);
+                }
+            }
+            _ => {}
+        }
+    }
+    let own =
+        |function: &str| function.contains("trimmed_backtrace") || function.contains("log_if_slow");
+    let runtime = |function: &str| {
+        function.contains("__rust_begin_short_backtrace") || function.starts_with("std::rt::")
+    };
+    frames
+        .iter()
+        .skip_while(|(function, _)| own(function))
+        .take_while(|(function, _)| !runtime(function))
+        .take(BACKTRACE_FRAMES)
+        .flat_map(|(_, lines)| lines.iter().copied())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl TimeLapse {
+    /// Logs at the warn level the elapsed time with a given name if it is greater than a
+    /// threshold. Returns `true` if the elapsed time was logged, which is not the case either
+    /// when the region is disabled or the elapsed time is under the logging floor.
+    ///
+    /// With the `backtrace` feature, the message is followed by the first frames of the
+    /// backtrace of the caller.
+    pub fn log_if_slower_than(&self, name: &str, threshold: Duration) -> bool {
+        let elapsed = self.elapsed();
+        if elapsed <= threshold {
+            return false;
+        }
+        log_gated(name, elapsed, Level::Warn, || {
+            let message = format!(
+                "{} (slower than {:?})",
+                format_message(name, elapsed),
+                threshold
+            );
+            #[cfg(feature = "backtrace")]
+            let message = format!("{}\n{}", message, trimmed_backtrace());
+            message
+        })
+    }
+
+    /// Logs at the warn level the elapsed time with a given name if it is greater than the
+    /// threshold installed for that name with `set_thresholds()`. Nothing is logged for a name
+    /// without a threshold. Returns `true` if the elapsed time was logged.
+    pub fn log_if_slow(&self, name: &str) -> bool {
+        threshold(name).is_some_and(|threshold| self.log_if_slower_than(name, threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::VirtualClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_threshold_map_parse() {
+        let map = ThresholdMap::parse(
+            "# thresholds\n\ndb.query = 50ms\n  render=16.7 ms \nspin = 250ns\nio = .5s\nx = 3us\n",
+        )
+        .unwrap();
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.get("db.query"), Some(Duration::from_millis(50)));
+        assert_eq!(map.get("render"), Some(Duration::from_micros(16_700)));
+        assert_eq!(map.get("spin"), Some(Duration::from_nanos(250)));
+        assert_eq!(map.get("io"), Some(Duration::from_millis(500)));
+        assert_eq!(map.get("x"), Some(Duration::from_micros(3)));
+        assert_eq!(map.get("unknown"), None);
+
+        let error = "a = 1ms\nb 2ms".parse::<ThresholdMap>().unwrap_err();
+        assert_eq!(
+            (error.line(), error.kind()),
+            (2, ParseErrorKind::MissingSeparator)
+        );
+        assert_eq!(error.to_string(), "line 2: expected `region = duration`");
+        let error = ThresholdMap::parse(" = 2ms").unwrap_err();
+        assert_eq!((error.line(), error.kind()), (1, ParseErrorKind::EmptyName));
+        for invalid in [
+            "a = 5",
+            "a = ms",
+            "a = 1.2.3ms",
+            "a = 5min",
+            "a = -1ms",
+            "a = .",
+        ] {
+            let error = ThresholdMap::parse(invalid).unwrap_err();
+            assert_eq!(error.kind(), ParseErrorKind::InvalidDuration, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_log_if_slow() {
+        let clock = Arc::new(VirtualClock::new());
+        let profiler = TimeLapse::new().with_clock(clock.clone());
+        clock.advance(Duration::from_millis(5));
+        assert!(profiler.log_if_slower_than("test", Duration::from_millis(1)));
+        assert!(!profiler.log_if_slower_than("test", Duration::from_secs(1)));
+        crate::registry::set_region_enabled("test_log_if_slow_disabled", false);
+        assert!(!profiler.log_if_slower_than("test_log_if_slow_disabled", Duration::from_millis(1)));
+
+        let mut map = ThresholdMap::new();
+        map.insert("test_log_if_slow", Duration::from_millis(1));
+        set_thresholds(map);
+        assert!(profiler.log_if_slow("test_log_if_slow"));
+        assert!(!profiler.log_if_slow("test_log_if_slow_unknown"));
+    }
+
+    #[cfg(feature = "backtrace")]
+    #[test]
+    fn test_trim_backtrace() {
+        let backtrace = [
+            "   0: timelapse::thresholds::trimmed_backtrace",
+            "             at ./src/thresholds.rs:10:5",
+            "   1: timelapse::thresholds::<impl timelapse::profiler::TimeLapse>::log_if_slower_than",
+            "   2: app::load",
+            "             at ./src/load.rs:42:9",
+            "   3: app::main",
+            "             at ./src/main.rs:7:5",
+            "   4: std::sys::backtrace::__rust_begin_short_backtrace",
+            "   5: std::rt::lang_start::{{closure}}",
+        ];
+        assert_eq!(
+            trim_backtrace(&backtrace.join("\n")),
+            backtrace[3..7].join("\n")
+        );
+
+        let deep: String = (0..20).map(|i| format!("  {}: app::f{}\n", i, i)).collect();
+        assert_eq!(trim_backtrace(&deep).lines().count(), BACKTRACE_FRAMES);
+        assert!(!trimmed_backtrace().contains("trimmed_backtrace"));
+    }
+}
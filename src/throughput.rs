@@ -0,0 +1,167 @@
+//! Throughput of the work done in a timed region.
+//!
+//! This module adds to `TimeLapse` the logging of the number of units of work processed
+//! during the elapsed time, and of the derived rate, with the unit named so that the logs are
+//! self-describing (e.g. `processed 1M rows (500k rows/s)`). Byte counts have their own
+//! variant, rendering the amount and the bandwidth with decimal prefixes (e.g. `1.50 GB
+//! (750.00 MB/s)`).
+
+use std::time::Duration;
+
+use crate::logging::Level;
+use crate::profiler::{format_message, log_gated};
+use crate::TimeLapse;
+
+/// The decimal prefixes of the counts and bandwidths, each a thousand times the previous one.
+const PREFIXES: [&str; 5] = ["", "k", "M", "G", "T"];
+
+impl TimeLapse {
+    /// Logs at the info level the elapsed time with a given name, along with the number of
+    /// units of work processed and the rate, e.g.
+    /// `TimeLapse import - Elapsed time: 2s, processed 1M rows (500k rows/s)`. The rate is
+    /// omitted if no time elapsed.
+    ///
+    /// # Usage
+    /// ```rust
+    /// use timelapse::TimeLapse;
+    ///
+    /// let profiler = TimeLapse::new();
+    /// let rows = 1_000_000;
+    /// // ... importing the rows ...
+    /// profiler.log_throughput_unit("import", rows, "rows");
+    /// ```
+    pub fn log_throughput_unit(&self, name: &str, count: u64, unit: &str) {
+        let elapsed = self.elapsed();
+        log_gated(name, elapsed, Level::Info, || {
+            throughput_message(name, count, unit, elapsed)
+        });
+    }
+
+    /// Logs at the info level the elapsed time with a given name, along with the number of
+    /// bytes processed and the bandwidth, in decimal units, e.g.
+    /// `TimeLapse download - Elapsed time: 2s, 1.50 GB (750.00 MB/s)`. The bandwidth is
+    /// omitted if no time elapsed.
+    pub fn log_bandwidth(&self, name: &str, bytes: u64) {
+        let elapsed = self.elapsed();
+        log_gated(name, elapsed, Level::Info, || {
+            bandwidth_message(name, bytes, elapsed)
+        });
+    }
+}
+
+/// Returns the rate of a count over an elapsed time, per second, or `None` if no time elapsed.
+fn rate(count: f64, elapsed: Duration) -> Option<f64> {
+    let secs = elapsed.as_secs_f64();
+    (secs > 0.0).then(|| count / secs)
+}
+
+/// Returns the message of `TimeLapse::log_throughput_unit()`.
+fn throughput_message(name: &str, count: u64, unit: &str, elapsed: Duration) -> String {
+    let mut message = format!(
+        "{}, processed {} {}",
+        format_message(name, elapsed),
+        compact(count as f64),
+        unit
+    );
+    if let Some(rate) = rate(count as f64, elapsed) {
+        message.push_str(&format!(" ({} {}/s)", compact(rate), unit));
+    }
+    message
+}
+
+/// Returns the message of `TimeLapse::log_bandwidth()`.
+fn bandwidth_message(name: &str, bytes: u64, elapsed: Duration) -> String {
+    let mut message = format!(
+        "{}, {}",
+        format_message(name, elapsed),
+        bytes_of(bytes as f64)
+    );
+    if let Some(rate) = rate(bytes as f64, elapsed) {
+        message.push_str(&format!(" ({}/s)", bytes_of(rate)));
+    }
+    message
+}
+
+/// Splits a value into a mantissa below 1000 and the index of its decimal prefix.
+fn scaled(mut value: f64) -> (f64, usize) {
+    let mut index = 0;
+    while value >= 1000.0 && index + 1 < PREFIXES.len() {
+        value /= 1000.0;
+        index += 1;
+    }
+    (value, index)
+}
+
+/// Formats a number with a decimal prefix and at most three significant digits, trailing
+/// zeros removed, e.g. `1M`, `500k`, `1.23k` or `12.5`.
+fn compact(value: f64) -> String {
+    let (mut mantissa, mut index) = scaled(value);
+    let decimals = |mantissa: f64| match mantissa {
+        m if m < 10.0 => 2,
+        m if m < 100.0 => 1,
+        _ => 0,
+    };
+    // Rounding may carry over to the next prefix, e.g. 999.6k to 1M.
+    let factor = 10f64.powi(decimals(mantissa));
+    if (mantissa * factor).round() / factor >= 1000.0 && index + 1 < PREFIXES.len() {
+        mantissa /= 1000.0;
+        index += 1;
+    }
+    let text = format!("{:.*}", decimals(mantissa) as usize, mantissa);
+    let text = if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &text
+    };
+    format!("{}{}", text, PREFIXES[index])
+}
+
+/// Formats a number of bytes with a decimal prefix and two decimals, e.g. `750.00 MB`, whole
+/// numbers of bytes below a kilobyte being shown as such, e.g. `512 B`.
+fn bytes_of(bytes: f64) -> String {
+    match scaled(bytes) {
+        (bytes, 0) => format!("{:.0} B", bytes),
+        (mantissa, index) => format!("{:.2} {}B", mantissa, PREFIXES[index]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact() {
+        assert_eq!(compact(0.0), "0");
+        assert_eq!(compact(12.5), "12.5");
+        assert_eq!(compact(999.0), "999");
+        assert_eq!(compact(1_234.0), "1.23k");
+        assert_eq!(compact(500_000.0), "500k");
+        assert_eq!(compact(999_600.0), "1M");
+        assert_eq!(compact(1_000_000.0), "1M");
+        assert_eq!(compact(2.5e15), "2500T");
+        assert_eq!(bytes_of(512.0), "512 B");
+        assert_eq!(bytes_of(1_500_000_000.0), "1.50 GB");
+        assert_eq!(bytes_of(750e6), "750.00 MB");
+    }
+
+    #[test]
+    fn test_throughput_messages() {
+        let two_secs = Duration::from_secs(2);
+        assert_eq!(
+            throughput_message("import", 1_000_000, "rows", two_secs),
+            "TimeLapse import - Elapsed time: 2s, processed 1M rows (500k rows/s)"
+        );
+        assert_eq!(
+            throughput_message("import", 3, "rows", Duration::ZERO),
+            "TimeLapse import - Elapsed time: 0ns, processed 3 rows"
+        );
+        assert_eq!(
+            bandwidth_message("download", 1_500_000_000, two_secs),
+            "TimeLapse download - Elapsed time: 2s, 1.50 GB (750.00 MB/s)"
+        );
+
+        let profiler = TimeLapse::new();
+        profiler.log_throughput_unit("test", 10, "items");
+        profiler.log_bandwidth("test", 4_096);
+    }
+}
@@ -0,0 +1,98 @@
+//! Profiling of regions starting and ending in different functions.
+//!
+//! This module provides the `begin()` and `end()` functions. `begin()` starts a timer kept in
+//! a global table and returns an opaque `Token` identifying it. The token is `Copy` and can be
+//! passed along or stored anywhere, e.g. in a request structure, until `end()` logs the
+//! elapsed time and removes the timer from the table.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::Duration;
+
+use crate::logging::Level;
+use crate::TimeLapse;
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+static TIMERS: OnceLock<Mutex<HashMap<u64, (String, TimeLapse)>>> = OnceLock::new();
+
+/// Locks the table of the started timers.
+fn timers() -> MutexGuard<'static, HashMap<u64, (String, TimeLapse)>> {
+    TIMERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The `Token` struct identifies a timer started by `begin()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Token(u64);
+
+/// Starts a timer with the given name and returns the token identifying it.
+///
+/// # Usage
+/// ```rust
+/// use timelapse::token::{self, Token};
+///
+/// fn receive() -> Token {
+///     token::begin("request")
+/// }
+///
+/// fn respond(token: Token) {
+///     token::end(token);
+/// }
+///
+/// let token = receive();
+/// respond(token);
+/// ```
+pub fn begin(name: impl Into<String>) -> Token {
+    let token = Token(NEXT_TOKEN.fetch_add(1, Ordering::Relaxed));
+    timers().insert(token.0, (name.into(), TimeLapse::new()));
+    token
+}
+
+/// Logs at the info level the time elapsed since the `begin()` call that returned the token,
+/// and forgets the timer. Returns the elapsed time, or `None` if the token was already ended.
+pub fn end(token: Token) -> Option<Duration> {
+    end_log(token, Level::Info)
+}
+
+/// Logs at the given level the time elapsed since the `begin()` call that returned the token,
+/// and forgets the timer. Returns the elapsed time, or `None` if the token was already ended.
+pub fn end_log(token: Token, level: Level) -> Option<Duration> {
+    let (name, timer) = timers().remove(&token.0)?;
+    timer.log(&name, level);
+    Some(timer.elapsed())
+}
+
+/// Returns the time elapsed since the `begin()` call that returned the token, or `None` if the
+/// token was already ended.
+pub fn elapsed(token: Token) -> Option<Duration> {
+    timers().get(&token.0).map(|(_, timer)| timer.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_begin_end() {
+        let first = begin("first");
+        let second = begin("second");
+        assert_ne!(first, second);
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert!(elapsed(first).unwrap() >= Duration::from_millis(10));
+        assert!(end(first).unwrap() >= Duration::from_millis(10));
+        assert_eq!(end(first), None);
+        assert_eq!(elapsed(first), None);
+        assert!(end_log(second, Level::Debug).is_some());
+    }
+
+    #[test]
+    fn test_token_across_threads() {
+        let token = begin("thread");
+        let worker = std::thread::spawn(move || end(token));
+        assert!(worker.join().unwrap().is_some());
+    }
+}
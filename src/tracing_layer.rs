@@ -0,0 +1,251 @@
+//! Span timing for `tracing` users.
+//!
+//! This module, available with the `tracing` feature, provides the `HistogramLayer` struct, a
+//! `tracing_subscriber` layer timing every span from its creation to its close, and recording
+//! the durations into a `Histogram` per span name. The histograms can be queried at any time,
+//! e.g. at shutdown, from a clone of the layer kept before installing it.
+//!
+//! The layer is installed alongside the other layers of a subscriber built on the
+//! `tracing_subscriber` registry:
+//!
+//! ```rust
+//! use timelapse::tracing_layer::HistogramLayer;
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let layer = HistogramLayer::new();
+//! let subscriber = tracing_subscriber::registry().with(layer.clone());
+//! tracing::subscriber::with_default(subscriber, || {
+//!     for _ in 0..3 {
+//!         let _span = tracing::info_span!("load").entered();
+//!         // ... loading ...
+//!     }
+//! });
+//! assert_eq!(layer.histogram("load").unwrap().count(), 3);
+//! ```
+//!
+//! A span lives until its last handle is dropped, so its duration covers the time it was
+//! entered and any time between its entries, as for a span following a request across
+//! asynchronous tasks.
+//!
+//! The module also provides the `record_elapsed()` function, returning a guard that records
+//! its elapsed time as the `elapsed_ms` field of a span when dropped, so that the duration is
+//! part of the span data seen by any subscriber, e.g. a formatting layer, with or without a
+//! `HistogramLayer`. As with any `tracing` field, `elapsed_ms` must be declared when the span
+//! is created, usually as `tracing::field::Empty`:
+//!
+//! ```rust
+//! use timelapse::tracing_layer::record_elapsed;
+//!
+//! let span = tracing::info_span!("load", elapsed_ms = tracing::field::Empty);
+//! {
+//!     let _elapsed = record_elapsed(&span);
+//!     let _entered = span.enter();
+//!     // ... loading ...
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::{Histogram, TimeLapse};
+
+/// The name of the span field recorded by `SpanElapsedGuard`.
+pub const ELAPSED_FIELD: &str = "elapsed_ms";
+
+/// The creation time of a span, stored in its extensions.
+struct Opened(Instant);
+
+/// The `HistogramLayer` struct records the durations of the spans into a histogram per span
+/// name. Its clones share the same histograms.
+#[derive(Clone, Debug)]
+pub struct HistogramLayer {
+    histograms: Arc<Mutex<HashMap<&'static str, Histogram>>>,
+    template: Histogram,
+}
+
+impl HistogramLayer {
+    /// Creates a new `HistogramLayer` recording into default histograms (see
+    /// `Histogram::default()`).
+    pub fn new() -> Self {
+        Self::with_histogram(Histogram::default())
+    }
+
+    /// Creates a new `HistogramLayer` recording into copies of the given empty histogram, to
+    /// choose the bucket bounds.
+    pub fn with_histogram(template: Histogram) -> Self {
+        HistogramLayer {
+            histograms: Arc::default(),
+            template,
+        }
+    }
+
+    /// Returns a copy of the histogram of the spans with the given name, if any was closed.
+    pub fn histogram(&self, name: &str) -> Option<Histogram> {
+        self.lock().get(name).cloned()
+    }
+
+    /// Returns a copy of the histograms of all span names, sorted by name.
+    pub fn histograms(&self) -> Vec<(&'static str, Histogram)> {
+        let mut histograms: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(name, histogram)| (*name, histogram.clone()))
+            .collect();
+        histograms.sort_by_key(|(name, _)| *name);
+        histograms
+    }
+
+    /// Removes all the histograms.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Locks the histograms.
+    fn lock(&self) -> MutexGuard<'_, HashMap<&'static str, Histogram>> {
+        self.histograms
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Implements the `Default` trait for the `HistogramLayer` struct, recording into default
+/// histograms.
+impl std::default::Default for HistogramLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for HistogramLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(elapsed) = span
+            .extensions()
+            .get::<Opened>()
+            .map(|opened| opened.0.elapsed())
+        else {
+            return;
+        };
+        self.lock()
+            .entry(span.name())
+            .or_insert_with(|| self.template.clone())
+            .record(elapsed);
+    }
+}
+
+/// Returns a guard recording the time elapsed from now until it is dropped as the
+/// `elapsed_ms` field of the given span, in fractional milliseconds.
+///
+/// The field must be declared when the span is created, e.g. with
+/// `elapsed_ms = tracing::field::Empty`, otherwise nothing is recorded. Nothing is recorded
+/// either for a disabled span, e.g. when no subscriber is installed.
+pub fn record_elapsed(span: &tracing::Span) -> SpanElapsedGuard {
+    SpanElapsedGuard {
+        span: span.clone(),
+        timer: TimeLapse::new(),
+    }
+}
+
+/// The `SpanElapsedGuard` struct records the time elapsed since its creation as the
+/// `elapsed_ms` field of a span when dropped. It is created by `record_elapsed()`, and keeps
+/// the span open until it is dropped.
+#[derive(Debug)]
+#[must_use = "the elapsed time is recorded when the guard is dropped"]
+pub struct SpanElapsedGuard {
+    span: tracing::Span,
+    timer: TimeLapse,
+}
+
+impl Drop for SpanElapsedGuard {
+    fn drop(&mut self) {
+        let elapsed_ms = self.timer.elapsed().as_secs_f64() * 1000.0;
+        self.span.record(ELAPSED_FIELD, elapsed_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_histogram_layer() {
+        let layer = HistogramLayer::with_histogram(Histogram::new(vec![Duration::from_millis(5)]));
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("outer");
+            outer.in_scope(|| {
+                for _ in 0..2 {
+                    let _inner = tracing::debug_span!("inner").entered();
+                }
+                let _slow = tracing::info_span!("slow").entered();
+                std::thread::sleep(Duration::from_millis(10));
+            });
+            assert!(layer.histogram("outer").is_none());
+        });
+
+        let names: Vec<&str> = layer.histograms().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["inner", "outer", "slow"]);
+        assert_eq!(layer.histogram("inner").unwrap().counts(), &[2, 0]);
+        assert_eq!(layer.histogram("slow").unwrap().counts(), &[0, 1]);
+        assert!(layer.histogram("outer").unwrap().max() >= Duration::from_millis(10));
+
+        layer.clear();
+        assert!(layer.histograms().is_empty());
+    }
+
+    /// A layer keeping the last `elapsed_ms` value recorded on a span.
+    #[derive(Clone, Default)]
+    struct ElapsedField(Arc<Mutex<Option<f64>>>);
+
+    impl tracing::field::Visit for ElapsedField {
+        fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+            if field.name() == ELAPSED_FIELD {
+                *self.0.lock().unwrap() = Some(value);
+            }
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: Subscriber> Layer<S> for ElapsedField {
+        fn on_record(&self, _id: &Id, values: &tracing::span::Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    #[test]
+    fn test_record_elapsed() {
+        let field = ElapsedField::default();
+        let subscriber = tracing_subscriber::registry().with(field.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("load", elapsed_ms = tracing::field::Empty);
+            let guard = record_elapsed(&span);
+            std::thread::sleep(Duration::from_millis(5));
+            assert!(field.0.lock().unwrap().is_none());
+            drop(guard);
+        });
+        assert!(field.0.lock().unwrap().unwrap() >= 5.0);
+
+        // Without a subscriber, the span is disabled and the guard records nothing.
+        let span = tracing::info_span!("load", elapsed_ms = tracing::field::Empty);
+        drop(record_elapsed(&span));
+    }
+}
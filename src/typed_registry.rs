@@ -0,0 +1,197 @@
+//! Registries of elapsed times keyed by an enum.
+//!
+//! This module provides the `TypedRegistry` struct, accumulating elapsed times like the global
+//! `registry`, but under the variants of a key type implementing the `TimerKey` trait instead
+//! of strings. A misspelled region is a compile error, and the report lists every variant, in
+//! declaration order, including the ones never recorded.
+//!
+//! The `timers!` macro declares such an enum and implements `TimerKey` for it:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use timelapse::timers;
+//! use timelapse::typed_registry::{TimerKey, TypedRegistry};
+//!
+//! timers! {
+//!     enum Stage { Parse, Build, Emit }
+//! }
+//!
+//! static STAGES: TypedRegistry<Stage> = TypedRegistry::new();
+//!
+//! STAGES.record(Stage::Parse, Duration::from_millis(3));
+//! drop(STAGES.scope(Stage::Emit));
+//! let report = STAGES.report();
+//! assert_eq!(report.len(), 3);
+//! assert_eq!(report[0], (Stage::Parse, Duration::from_millis(3)));
+//! assert_eq!(Stage::Build.name(), "Build");
+//! ```
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::registry::Entry;
+use crate::TimeLapse;
+
+/// The `TimerKey` trait is implemented by the key types of a `TypedRegistry`, usually unit
+/// enums declared with the `timers!` macro.
+pub trait TimerKey: Copy + 'static {
+    /// All the keys, in the order of the reports.
+    const ALL: &'static [Self];
+
+    /// Returns the position of the key in `ALL`.
+    fn index(self) -> usize;
+
+    /// Returns the name of the key in the messages, e.g. with `TimeLapse::log()`.
+    fn name(self) -> &'static str;
+}
+
+/// The `TypedRegistry` struct accumulates elapsed times by key, the keys being the values of
+/// a `TimerKey` type. It is thread-safe, and can be created in a static.
+///
+/// # Usage
+/// ```rust
+/// use std::time::Duration;
+/// use timelapse::timers;
+/// use timelapse::typed_registry::TypedRegistry;
+///
+/// timers! {
+///     enum Query { Select, Insert }
+/// }
+///
+/// let timings = TypedRegistry::new();
+/// timings.record(Query::Insert, Duration::from_millis(4));
+/// assert_eq!(
+///     timings.report(),
+///     [(Query::Select, Duration::ZERO), (Query::Insert, Duration::from_millis(4))]
+/// );
+/// ```
+#[derive(Debug)]
+pub struct TypedRegistry<K> {
+    entries: Mutex<Vec<Entry>>,
+    keys: std::marker::PhantomData<K>,
+}
+
+impl<K: TimerKey> TypedRegistry<K> {
+    /// Creates a new empty `TypedRegistry`.
+    pub const fn new() -> Self {
+        TypedRegistry {
+            entries: Mutex::new(Vec::new()),
+            keys: std::marker::PhantomData,
+        }
+    }
+
+    /// Adds an elapsed time under the given key.
+    pub fn record(&self, key: K, elapsed: Duration) {
+        let mut entries = self.lock();
+        if entries.len() < K::ALL.len() {
+            entries.resize(K::ALL.len(), Entry::default());
+        }
+        entries[key.index()].add(elapsed);
+    }
+
+    /// Returns a guard that records its elapsed time under the given key when dropped.
+    pub fn scope(&self, key: K) -> TypedRegistryGuard<'_, K> {
+        TypedRegistryGuard {
+            registry: self,
+            key,
+            timer: TimeLapse::new(),
+        }
+    }
+
+    /// Returns the accumulated entry of the given key, with a zero count if nothing was
+    /// recorded under it.
+    pub fn get(&self, key: K) -> Entry {
+        self.lock()
+            .get(key.index())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the total elapsed time of every key, in the order of `TimerKey::ALL`, the keys
+    /// never recorded having a zero time.
+    pub fn report(&self) -> Vec<(K, Duration)> {
+        let entries = self.lock();
+        K::ALL
+            .iter()
+            .map(|key| {
+                let total = entries.get(key.index()).map_or(Duration::ZERO, Entry::total);
+                (*key, total)
+            })
+            .collect()
+    }
+
+    /// Removes all the entries.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Locks the entries. Entries poisoned by a panic are still usable, as they are only
+    /// updated once the new values are fully computed.
+    fn lock(&self) -> MutexGuard<'_, Vec<Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Implements the `Default` trait for the `TypedRegistry` struct, creating an empty registry.
+impl<K: TimerKey> std::default::Default for TypedRegistry<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `TypedRegistryGuard` struct records the time elapsed since its creation under a key of
+/// a `TypedRegistry` when dropped. It is created by `TypedRegistry::scope()`.
+#[must_use = "the elapsed time is recorded when the guard is dropped"]
+pub struct TypedRegistryGuard<'a, K: TimerKey> {
+    registry: &'a TypedRegistry<K>,
+    key: K,
+    timer: TimeLapse,
+}
+
+impl<K: TimerKey> Drop for TypedRegistryGuard<'_, K> {
+    fn drop(&mut self) {
+        self.registry.record(self.key, self.timer.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timers;
+
+    timers! {
+        /// The stages of the tests.
+        enum Stage {
+            Parse,
+            /// The build.
+            Build,
+            Emit,
+        }
+    }
+
+    #[test]
+    fn test_timer_key() {
+        assert_eq!(Stage::ALL, &[Stage::Parse, Stage::Build, Stage::Emit]);
+        assert_eq!(Stage::Emit.index(), 2);
+        assert_eq!(Stage::Build.name(), "Build");
+    }
+
+    #[test]
+    fn test_typed_registry() {
+        let timings = TypedRegistry::new();
+        assert_eq!(timings.get(Stage::Parse).count(), 0);
+        timings.record(Stage::Emit, Duration::from_millis(2));
+        timings.record(Stage::Emit, Duration::from_millis(3));
+        drop(timings.scope(Stage::Parse));
+
+        assert_eq!(timings.get(Stage::Emit).count(), 2);
+        assert_eq!(timings.get(Stage::Parse).count(), 1);
+        let report = timings.report();
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[1], (Stage::Build, Duration::ZERO));
+        assert_eq!(report[2], (Stage::Emit, Duration::from_millis(5)));
+
+        timings.clear();
+        assert!(timings.report().iter().all(|(_, total)| total.is_zero()));
+    }
+}